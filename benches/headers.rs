@@ -0,0 +1,101 @@
+use std::{hint::black_box, sync::LazyLock};
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use regex::Regex;
+use rust_http::headers::Headers;
+
+/// What a browser sends for a page load, every name a common one.
+const BROWSER_HEADERS: &[u8] = b"Host: example.com\r\n\
+User-Agent: Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0\r\n\
+Accept: text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8\r\n\
+Accept-Language: en-US,en;q=0.5\r\n\
+Accept-Encoding: gzip, deflate, br\r\n\
+Connection: keep-alive\r\n\
+Cookie: session=0123456789abcdef\r\n\
+If-None-Match: \"5d8c72a5-264\"\r\n\
+Cache-Control: max-age=0\r\n\
+\r\n";
+
+/// The same block with names of the same length that aren't interned.
+const UNCOMMON_HEADERS: &[u8] = b"Xost: example.com\r\n\
+Xser-Agent: Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0\r\n\
+Xccept: text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8\r\n\
+Xccept-Language: en-US,en;q=0.5\r\n\
+Xccept-Encoding: gzip, deflate, br\r\n\
+Xonnection: keep-alive\r\n\
+Xookie: session=0123456789abcdef\r\n\
+Xf-None-Match: \"5d8c72a5-264\"\r\n\
+Xache-Control: max-age=0\r\n\
+\r\n";
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("headers/parse_all");
+    group.bench_function("common_names", |b| {
+        b.iter(|| Headers::parse_all(black_box(BROWSER_HEADERS)).unwrap())
+    });
+    group.bench_function("uncommon_names", |b| {
+        b.iter(|| Headers::parse_all(black_box(UNCOMMON_HEADERS)).unwrap())
+    });
+    group.finish();
+}
+
+/// The field name check the parser used before the byte loop, with the
+/// pattern compiled once rather than per line.
+static FIELD_NAME: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[A-Za-z0-9!#$%&'*+\-.^_`|~]+$").unwrap());
+
+/// The byte loop the parser checks field names with, as in `headers.rs`.
+fn is_valid_field_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+/// Many short fields, so checking each name is most of the work. The
+/// parse is measured alongside the check alone, byte loop and regex, for
+/// the 50 names it makes.
+fn field_names(c: &mut Criterion) {
+    let names: Vec<String> = (0..50).map(|i| format!("X-Field-{i}")).collect();
+    let block: Vec<u8> = names
+        .iter()
+        .flat_map(|name| format!("{name}: 1\r\n").into_bytes())
+        .chain(*b"\r\n")
+        .collect();
+    LazyLock::force(&FIELD_NAME);
+
+    let mut group = c.benchmark_group("headers/field_names/50_short_fields");
+    group.bench_function("parse_all", |b| {
+        b.iter(|| Headers::parse_all(black_box(&block)).unwrap())
+    });
+    group.bench_function("byte_loop", |b| {
+        b.iter(|| {
+            black_box(&names)
+                .iter()
+                .all(|name| is_valid_field_name(name))
+        })
+    });
+    group.bench_function("regex", |b| {
+        b.iter(|| {
+            black_box(&names)
+                .iter()
+                .all(|name| FIELD_NAME.is_match(name))
+        })
+    });
+    group.finish();
+}
+
+fn get(c: &mut Criterion) {
+    let (headers, _) = Headers::parse_all(BROWSER_HEADERS).unwrap();
+    let mut group = c.benchmark_group("headers/get");
+    group.bench_function("lowercase", |b| {
+        b.iter(|| headers.get(black_box("accept-encoding")))
+    });
+    group.bench_function("mixed_case", |b| {
+        b.iter(|| headers.get(black_box("Accept-Encoding")))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse, field_names, get);
+criterion_main!(benches);
@@ -0,0 +1,51 @@
+use std::{collections::HashMap, hint::black_box};
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use rust_http::{Method, Request, Response, Router, StatusCode, request_from_reader};
+
+const ROUTES: usize = 1000;
+
+fn request(target: &str) -> Request {
+    let data = format!("GET {target} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+    request_from_reader(data.as_bytes()).unwrap()
+}
+
+fn ok(_: &Request) -> Response {
+    Response::new(StatusCode::Ok)
+}
+
+fn route(c: &mut Criterion) {
+    let mut router = Router::new();
+    let mut map: HashMap<String, fn(&Request) -> Response> = HashMap::new();
+    for i in 0..ROUTES {
+        router.add_route(Method::Get, &format!("/static/{i}/page"), ok);
+        router.add_route(Method::Get, &format!("/r{i}/:id"), ok);
+        map.insert(format!("/static/{i}/page"), ok);
+    }
+
+    let mut static_req = request("/static/999/page");
+    let mut param_req = request("/r999/42");
+    let mut missing_req = request("/static/1000/page");
+
+    let mut group = c.benchmark_group("router/1000_routes");
+    // What exact-match routing costs: a lookup of the whole path.
+    group.bench_function("map_static", |b| {
+        b.iter(|| {
+            let target = &static_req.request_line.as_ref().unwrap().request_target;
+            map.get(black_box(target.as_str())).map(|h| h(&static_req))
+        })
+    });
+    group.bench_function("static", |b| {
+        b.iter(|| router.handle(black_box(&mut static_req)))
+    });
+    group.bench_function("param", |b| {
+        b.iter(|| router.handle(black_box(&mut param_req)))
+    });
+    group.bench_function("missing", |b| {
+        b.iter(|| router.handle(black_box(&mut missing_req)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, route);
+criterion_main!(benches);
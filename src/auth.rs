@@ -0,0 +1,50 @@
+use crate::{headers::is_token_char, request::Request};
+
+impl Request {
+    /// The `Authorization` scheme and the credentials after it, split on the
+    /// first space, e.g. `("Digest", "realm=\"api\", nonce=\"abc\"")`. Works
+    /// for any scheme; compare the scheme case-insensitively. The credentials
+    /// are empty for a bare scheme such as `Negotiate`. `None` when the header
+    /// is missing or the scheme isn't a valid token.
+    pub fn auth_scheme(&self) -> Option<(&str, &str)> {
+        let value = self.headers.get("authorization")?.trim();
+        let (scheme, credentials) = value.split_once(' ').unwrap_or((value, ""));
+
+        if scheme.is_empty() || !scheme.bytes().all(is_token_char) {
+            return None;
+        }
+
+        Some((scheme, credentials.trim_start()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chunk_reader::ChunkReader,
+        request::{Request, request_from_reader},
+    };
+
+    fn request(headers: &str) -> Request {
+        let data = format!("GET / HTTP/1.1\r\nHost: localhost\r\n{headers}\r\n");
+        request_from_reader(ChunkReader::new(&data, 8)).unwrap()
+    }
+
+    #[test]
+    fn test_auth_scheme() {
+        let req = request("Authorization: Digest realm=\"api\", nonce=\"abc\"\r\n");
+        assert_eq!(
+            req.auth_scheme(),
+            Some(("Digest", "realm=\"api\", nonce=\"abc\""))
+        );
+
+        let req = request("Authorization: Basic dXNlcjpwYXNz\r\n");
+        assert_eq!(req.auth_scheme(), Some(("Basic", "dXNlcjpwYXNz")));
+
+        let req = request("Authorization: Negotiate\r\n");
+        assert_eq!(req.auth_scheme(), Some(("Negotiate", "")));
+
+        assert_eq!(request("").auth_scheme(), None);
+        assert_eq!(request("Authorization: a/b c\r\n").auth_scheme(), None);
+    }
+}
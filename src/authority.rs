@@ -0,0 +1,167 @@
+use crate::request::{Request, RequestLine};
+
+/// The host and optional port of a URI authority, e.g. `example.com:443`.
+///
+/// The host is lowercased. IPv6 literals are stored without their brackets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Authority {
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+impl Authority {
+    /// Parses `[userinfo@]host[:port]`, dropping any userinfo.
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.rsplit_once('@').map_or(s, |(_, rest)| rest);
+
+        let (host, port) = match s.strip_prefix('[') {
+            Some(rest) => {
+                let (host, rest) = rest.split_once(']')?;
+                if host.parse::<std::net::Ipv6Addr>().is_err() {
+                    return None;
+                }
+                match rest {
+                    "" => (host, None),
+                    rest => (host, Some(rest.strip_prefix(':')?)),
+                }
+            }
+            None => match s.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (s, None),
+            },
+        };
+
+        // An unbracketed IPv6 literal leaves a colon in what should be the port.
+        let port = match port {
+            None | Some("") => None,
+            Some(port) if port.bytes().all(|b| b.is_ascii_digit()) => Some(port.parse().ok()?),
+            Some(_) => return None,
+        };
+
+        if host.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            host: host.to_lowercase(),
+            port,
+        })
+    }
+}
+
+impl RequestLine {
+    /// The authority named by an absolute-form target
+    /// (`http://example.com:8080/path`) or an authority-form one
+    /// (`CONNECT example.com:443`). Origin-form targets (`/path`) have none.
+    pub fn authority(&self) -> Option<Authority> {
+        let target = &self.request_target;
+        if target.starts_with('/') || target == "*" {
+            return None;
+        }
+
+        let authority = match target.split_once("://") {
+            Some((_, rest)) => rest.split(['/', '?', '#']).next().unwrap_or_default(),
+            None => target,
+        };
+
+        Authority::parse(authority)
+    }
+}
+
+impl Request {
+    /// The authority from the request target, or failing that the `Host`
+    /// header.
+    pub fn authority(&self) -> Option<Authority> {
+        self.request_line
+            .as_ref()
+            .and_then(RequestLine::authority)
+            .or_else(|| Authority::parse(self.host()?))
+    }
+
+    /// The `Host` header split into host and port, e.g. `("::1", Some(443))`
+    /// for `[::1]:443`. `None` without a `Host` header, or when it isn't a
+    /// valid `host[:port]`, such as a non-numeric or out-of-range port.
+    pub fn host_and_port(&self) -> Option<(String, Option<u16>)> {
+        let host = self.host()?;
+        if host.contains('@') {
+            return None;
+        }
+
+        let Authority { host, port } = Authority::parse(host)?;
+        Some((host, port))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        authority::Authority,
+        chunk_reader::ChunkReader,
+        request::{Request, request_from_reader},
+    };
+
+    fn request(line: &str, host: &str) -> Request {
+        let data = format!("{line}\r\nHost: {host}\r\n\r\n");
+        request_from_reader(ChunkReader::new(&data, 16)).unwrap()
+    }
+
+    fn authority(host: &str, port: Option<u16>) -> Option<Authority> {
+        Some(Authority {
+            host: host.to_string(),
+            port,
+        })
+    }
+
+    #[test]
+    fn test_ipv6_literal_with_port() {
+        let req = request("GET http://[::1]:8080/index.html HTTP/1.1", "[::1]:8080");
+
+        assert_eq!(req.authority(), authority("::1", Some(8080)));
+    }
+
+    #[test]
+    fn test_connect_authority_form() {
+        let req = request("CONNECT Example.com:443 HTTP/1.1", "example.com:443");
+
+        assert_eq!(
+            req.request_line.as_ref().unwrap().authority(),
+            authority("example.com", Some(443))
+        );
+    }
+
+    #[test]
+    fn test_bare_host_falls_back_to_host_header() {
+        let req = request("GET /path HTTP/1.1", "example.com");
+
+        assert_eq!(req.request_line.as_ref().unwrap().authority(), None);
+        assert_eq!(req.authority(), authority("example.com", None));
+    }
+
+    #[test]
+    fn test_invalid_authorities() {
+        for invalid in ["::1", "[::1", "[nope]:80", "host:http", "host:99999", ":80"] {
+            assert_eq!(Authority::parse(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_host_and_port() {
+        let host_and_port = |host| request("GET / HTTP/1.1", host).host_and_port();
+
+        assert_eq!(
+            host_and_port("example.com:8080"),
+            Some(("example.com".to_string(), Some(8080)))
+        );
+        assert_eq!(
+            host_and_port("Example.com"),
+            Some(("example.com".to_string(), None))
+        );
+        assert_eq!(
+            host_and_port("[::1]:443"),
+            Some(("::1".to_string(), Some(443)))
+        );
+        assert_eq!(host_and_port("example.com:http"), None);
+        assert_eq!(host_and_port("example.com:65536"), None);
+        assert_eq!(host_and_port("user@example.com"), None);
+    }
+}
@@ -1,6 +1,6 @@
 use std::{io::Result, net::TcpListener};
 
-use rust_http::request;
+use rust_http::{Response, request};
 
 fn main() -> Result<()> {
     let listener = TcpListener::bind("0.0.0.0:42069")?;
@@ -11,7 +11,7 @@ fn main() -> Result<()> {
             Ok(stream) => {
                 println!("Accepted connection");
 
-                match request::request_from_reader(stream) {
+                match request::request_from_reader(&stream) {
                     Ok(req) => {
                         if let Some(line) = req.request_line {
                             println!("Request line:");
@@ -26,7 +26,16 @@ fn main() -> Result<()> {
                             }
                         }
                     }
-                    Err(err) => eprintln!("Failed to parse request: {err}"),
+                    Err(err) => {
+                        eprintln!("Failed to parse request: {err}");
+                        let status = err.status_code();
+                        let res = Response::new(status)
+                            .with_header("Connection", "close")
+                            .with_body(format!("{}: {err}", status.reason()));
+                        if let Err(err) = res.write_to(&mut &stream) {
+                            eprintln!("Failed to send error response: {err}");
+                        }
+                    }
                 }
 
                 println!("Closed connection");
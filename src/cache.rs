@@ -0,0 +1,369 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    headers::CacheControl,
+    method::Method,
+    request::Request,
+    response::{Response, StatusCode},
+};
+
+/// An in-memory cache for `GET` responses, applied as a middleware around a
+/// handler with [`ResponseCache::wrap`].
+///
+/// Responses are keyed by method and request target, plus the request's
+/// values for whatever headers the response lists in `Vary`. Only `200 OK`
+/// responses with a complete (not streamed) body are stored, each for a fixed
+/// TTL. `Cache-Control: no-store` on either the request or the response
+/// bypasses the cache, as does `Vary: *`.
+///
+/// Being shared between clients, the cache never stores a response meant
+/// for one of them: one marked `private` or `no-cache`, one setting a
+/// cookie, or one to a request with `Authorization`.
+///
+/// Once `max_entries` responses are stored, expired entries are dropped
+/// first, then the one closest to expiring.
+pub struct ResponseCache {
+    ttl: Duration,
+    max_entries: usize,
+    entries: Mutex<HashMap<String, Vec<Entry>>>,
+}
+
+struct Entry {
+    /// The request's value for each header named in the response's `Vary`.
+    vary: Vec<(String, Option<String>)>,
+    response: Response,
+    expires: Instant,
+}
+
+impl Entry {
+    fn matches(&self, req: &Request) -> bool {
+        self.vary
+            .iter()
+            .all(|(name, value)| req.headers.get(name) == value.as_ref())
+    }
+}
+
+impl ResponseCache {
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            ttl,
+            max_entries,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn wrap<H>(self, handler: H) -> impl Fn(&Request) -> Response + Send + Sync
+    where
+        H: Fn(&Request) -> Response + Send + Sync,
+    {
+        move |req| self.handle(req, &handler)
+    }
+
+    fn handle<H>(&self, req: &Request, handler: &H) -> Response
+    where
+        H: Fn(&Request) -> Response,
+    {
+        let Some(key) = cache_key(req) else {
+            return handler(req);
+        };
+
+        if let Some(res) = self.lookup(&key, req) {
+            return res;
+        }
+
+        let res = handler(req);
+        self.store(key, req, &res);
+        res
+    }
+
+    fn lookup(&self, key: &str, req: &Request) -> Option<Response> {
+        let now = Instant::now();
+        let entries = self.entries.lock().unwrap();
+
+        entries
+            .get(key)?
+            .iter()
+            .find(|entry| entry.expires > now && entry.matches(req))
+            .map(|entry| entry.response.clone())
+    }
+
+    fn store(&self, key: String, req: &Request, res: &Response) {
+        let cache_control = res.headers.cache_control();
+        if self.max_entries == 0
+            || res.status != StatusCode::Ok
+            || res.is_streaming()
+            || cache_control.no_store
+            || cache_control.private
+            || cache_control.no_cache
+            || res.headers.get("set-cookie").is_some()
+        {
+            return;
+        }
+
+        let vary: Vec<String> = res
+            .headers
+            .get("vary")
+            .map(|v| {
+                v.split(',')
+                    .map(|name| name.trim().to_lowercase())
+                    .collect()
+            })
+            .unwrap_or_default();
+        if vary.iter().any(|name| name == "*") {
+            return;
+        }
+
+        let entry = Entry {
+            vary: vary
+                .into_iter()
+                .filter(|name| !name.is_empty())
+                .map(|name| {
+                    let value = req.headers.get(&name).cloned();
+                    (name, value)
+                })
+                .collect(),
+            response: res.clone(),
+            expires: Instant::now() + self.ttl,
+        };
+
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(variants) = entries.get_mut(&key) {
+            variants.retain(|existing| existing.vary != entry.vary);
+        }
+        if entries.values().map(Vec::len).sum::<usize>() >= self.max_entries {
+            evict(&mut entries, self.max_entries - 1);
+        }
+        entries.entry(key).or_default().push(entry);
+    }
+}
+
+/// Drops entries until at most `keep` remain: expired ones first, then
+/// those closest to expiring.
+fn evict(entries: &mut HashMap<String, Vec<Entry>>, keep: usize) {
+    let now = Instant::now();
+    for variants in entries.values_mut() {
+        variants.retain(|entry| entry.expires > now);
+    }
+
+    let mut len: usize = entries.values().map(Vec::len).sum();
+    while len > keep {
+        let oldest = entries
+            .iter()
+            .flat_map(|(key, variants)| {
+                variants
+                    .iter()
+                    .enumerate()
+                    .map(move |(i, entry)| (entry.expires, key, i))
+            })
+            .min()
+            .map(|(_, key, i)| (key.clone(), i));
+
+        let Some((key, i)) = oldest else { break };
+        entries.get_mut(&key).unwrap().remove(i);
+        len -= 1;
+    }
+
+    entries.retain(|_, variants| !variants.is_empty());
+}
+
+/// `GET /target`, or `None` when the request must not be served from the
+/// cache.
+fn cache_key(req: &Request) -> Option<String> {
+    let line = req.request_line.as_ref()?;
+    if line.method != Method::Get
+        || req.headers.cache_control().no_store
+        || req.headers.get("authorization").is_some()
+    {
+        return None;
+    }
+
+    Some(format!("{} {}", line.method, line.request_target))
+}
+
+impl Response {
+    /// Sets `Cache-Control` to the directives set in `value`.
+    pub fn cache_control(self, value: CacheControl) -> Self {
+        self.with_header("Cache-Control", &value.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        thread,
+        time::Duration,
+    };
+
+    use crate::{
+        cache::ResponseCache,
+        chunk_reader::ChunkReader,
+        headers::CacheControl,
+        request::{Request, request_from_reader},
+        response::{Response, StatusCode},
+    };
+
+    fn request(target: &str, headers: &str) -> Request {
+        let data = format!("GET {target} HTTP/1.1\r\nHost: localhost\r\n{headers}\r\n");
+        request_from_reader(ChunkReader::new(&data, 16)).unwrap()
+    }
+
+    fn counting_handler(
+        ttl: Duration,
+        max_entries: usize,
+        headers: &'static [(&'static str, &'static str)],
+    ) -> (impl Fn(&Request) -> Response, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let handler = ResponseCache::new(ttl, max_entries).wrap(move |req| {
+            let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            let target = &req.request_line.as_ref().unwrap().request_target;
+            let mut res = Response::new(StatusCode::Ok).with_body(format!("{target} #{n}"));
+            for (name, value) in headers {
+                res.headers.set(name, value);
+            }
+            res
+        });
+
+        (handler, calls)
+    }
+
+    #[test]
+    fn test_hit_within_ttl() {
+        let (handler, calls) = counting_handler(Duration::from_secs(60), 16, &[]);
+
+        assert_eq!(handler(&request("/a", "")).body, b"/a #1");
+        assert_eq!(handler(&request("/a", "")).body, b"/a #1");
+        assert_eq!(handler(&request("/b", "")).body, b"/b #2");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_miss_after_expiry() {
+        let (handler, calls) = counting_handler(Duration::from_millis(20), 16, &[]);
+
+        assert_eq!(handler(&request("/a", "")).body, b"/a #1");
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(handler(&request("/a", "")).body, b"/a #2");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_no_store_bypasses_cache() {
+        let (handler, calls) = counting_handler(Duration::from_secs(60), 16, &[]);
+        handler(&request("/a", "Cache-Control: no-cache, no-store\r\n"));
+        handler(&request("/a", "Cache-Control: no-store\r\n"));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let (handler, calls) = counting_handler(
+            Duration::from_secs(60),
+            16,
+            &[("Cache-Control", "no-store")],
+        );
+        handler(&request("/a", ""));
+        handler(&request("/a", ""));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_private_response_is_not_stored() {
+        let responses: [&'static [(&str, &str)]; 3] = [
+            &[("Cache-Control", "private")],
+            &[("Cache-Control", "private=\"x-user\", max-age=60")],
+            &[("Cache-Control", "no-cache")],
+        ];
+
+        for headers in responses {
+            let (handler, calls) = counting_handler(Duration::from_secs(60), 16, headers);
+            handler(&request("/a", ""));
+            handler(&request("/a", ""));
+            assert_eq!(calls.load(Ordering::SeqCst), 2, "{headers:?}");
+        }
+    }
+
+    #[test]
+    fn test_response_setting_cookie_is_not_stored() {
+        let (handler, calls) =
+            counting_handler(Duration::from_secs(60), 16, &[("Set-Cookie", "id=1")]);
+        handler(&request("/a", ""));
+        handler(&request("/a", ""));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_authorized_request_bypasses_cache() {
+        let (handler, calls) = counting_handler(Duration::from_secs(60), 16, &[]);
+        assert_eq!(
+            handler(&request("/a", "Authorization: Basic YTpi\r\n")).body,
+            b"/a #1"
+        );
+        assert_eq!(handler(&request("/a", "")).body, b"/a #2");
+        assert_eq!(
+            handler(&request("/a", "Authorization: Basic YTpi\r\n")).body,
+            b"/a #3"
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_vary_keeps_variants_apart() {
+        let (handler, calls) =
+            counting_handler(Duration::from_secs(60), 16, &[("Vary", "Accept-Language")]);
+
+        assert_eq!(
+            handler(&request("/", "Accept-Language: en\r\n")).body,
+            b"/ #1"
+        );
+        assert_eq!(
+            handler(&request("/", "Accept-Language: de\r\n")).body,
+            b"/ #2"
+        );
+        assert_eq!(
+            handler(&request("/", "Accept-Language: en\r\n")).body,
+            b"/ #1"
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_evicts_when_full() {
+        let (handler, calls) = counting_handler(Duration::from_secs(60), 2, &[]);
+
+        handler(&request("/a", ""));
+        handler(&request("/b", ""));
+        handler(&request("/c", ""));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        // `/a` was closest to expiring, so it made room for `/c`.
+        assert_eq!(handler(&request("/c", "")).body, b"/c #3");
+        assert_eq!(handler(&request("/b", "")).body, b"/b #2");
+        assert_eq!(handler(&request("/a", "")).body, b"/a #4");
+    }
+
+    #[test]
+    fn test_cache_control_response_header() {
+        let res = Response::new(StatusCode::Ok).cache_control(CacheControl {
+            no_store: true,
+            ..CacheControl::default()
+        });
+        assert_eq!(res.headers.get("cache-control").unwrap(), "no-store");
+
+        let res = Response::new(StatusCode::Ok).cache_control(CacheControl {
+            public: true,
+            max_age: Some(3600),
+            must_revalidate: true,
+            ..CacheControl::default()
+        });
+        assert_eq!(
+            res.headers.get("cache-control").unwrap(),
+            "public, max-age=3600, must-revalidate"
+        );
+    }
+}
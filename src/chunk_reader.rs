@@ -1,9 +1,13 @@
-use std::io::Read;
+use std::{io::Read, thread, time::Duration};
 
 pub struct ChunkReader {
     data: Vec<u8>,
     num_bytes_per_read: usize,
     pos: usize,
+    delay: Option<Duration>,
+    /// Sizes of the next reads, in reverse so each read pops its own. Once
+    /// empty, reads are `num_bytes_per_read` long.
+    script: Vec<usize>,
 }
 
 #[allow(dead_code)]
@@ -13,8 +17,35 @@ impl ChunkReader {
             data: data.as_bytes().to_vec(),
             num_bytes_per_read,
             pos: 0,
+            delay: None,
+            script: Vec::new(),
         }
     }
+
+    pub fn from_bytes(data: &[u8], num_bytes_per_read: usize) -> Self {
+        Self {
+            data: data.to_vec(),
+            num_bytes_per_read,
+            pos: 0,
+            delay: None,
+            script: Vec::new(),
+        }
+    }
+
+    /// Returns exactly `sizes` bytes from successive reads (given a large
+    /// enough buffer), then the rest in one read, to put read boundaries at
+    /// chosen offsets.
+    pub fn scripted(data: &str, sizes: &[usize]) -> Self {
+        let mut reader = Self::new(data, usize::MAX);
+        reader.script = sizes.iter().rev().copied().collect();
+        reader
+    }
+
+    /// Sleeps for `delay` before each read, like a slow client.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
 }
 
 impl Read for ChunkReader {
@@ -22,8 +53,12 @@ impl Read for ChunkReader {
         if self.pos >= self.data.len() {
             return Ok(0);
         }
+        if let Some(delay) = self.delay {
+            thread::sleep(delay);
+        }
 
-        let end = (self.pos + self.num_bytes_per_read).min(self.data.len());
+        let size = self.script.pop().unwrap_or(self.num_bytes_per_read);
+        let end = self.pos.saturating_add(size).min(self.data.len());
         let chunk = &self.data[self.pos..end];
 
         let n = chunk.len().min(buf.len());
@@ -0,0 +1,349 @@
+use crate::{
+    config::{ChunkExtensionPolicy, RequestParserConfig},
+    error::RequestError,
+    headers::{split_unquoted, unquote},
+    line::find_line_end,
+};
+
+/// Longest chunk-size line (size plus extensions) we are willing to buffer.
+const MAX_CHUNK_LINE_LEN: usize = 1024;
+
+#[derive(Debug, Clone)]
+enum ChunkState {
+    Size,
+    Data(usize),
+    DataEnd,
+    Trailers,
+    Done,
+}
+
+/// One `name[=value]` chunk extension, kept under
+/// [`ChunkExtensionPolicy::Preserve`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkExtension {
+    pub name: String,
+    pub value: Option<String>,
+}
+
+/// Incremental decoder for the `chunked` transfer coding.
+#[derive(Debug, Clone)]
+pub(crate) struct ChunkedDecoder {
+    state: ChunkState,
+    strict: bool,
+    extension_policy: ChunkExtensionPolicy,
+    extensions: Vec<ChunkExtension>,
+    max_chunks: usize,
+    chunks: usize,
+}
+
+impl ChunkedDecoder {
+    pub(crate) fn new(config: &RequestParserConfig) -> Self {
+        Self {
+            state: ChunkState::Size,
+            strict: config.strict,
+            extension_policy: config.chunk_extensions,
+            extensions: Vec::new(),
+            max_chunks: config.max_chunks,
+            chunks: 0,
+        }
+    }
+
+    /// Extensions seen so far, in order, if they are being preserved.
+    pub(crate) fn extensions(&self) -> &[ChunkExtension] {
+        &self.extensions
+    }
+
+    pub(crate) fn is_done(&self) -> bool {
+        matches!(self.state, ChunkState::Done)
+    }
+
+    /// Bytes left in the current chunk, when in the middle of one.
+    pub(crate) fn data_remaining(&self) -> Option<usize> {
+        match self.state {
+            ChunkState::Data(remaining) => Some(remaining),
+            _ => None,
+        }
+    }
+
+    /// The largest read that can't run past the end of the chunked body.
+    /// Inside chunk data that's the rest of the chunk; elsewhere the decoder
+    /// is scanning for line ends and can only safely take one byte at a time.
+    pub(crate) fn max_safe_read(&self) -> usize {
+        match self.state {
+            ChunkState::Data(remaining) => remaining,
+            ChunkState::Done => 0,
+            _ => 1,
+        }
+    }
+
+    /// Decodes as much of `data` as possible, appending chunk payloads to
+    /// `out`. Returns the number of bytes consumed; stops early when more
+    /// input is needed to finish a line.
+    pub(crate) fn decode(&mut self, data: &[u8], out: &mut Vec<u8>) -> Result<usize, RequestError> {
+        let strict = self.strict;
+        let mut consumed = 0;
+
+        while !self.is_done() && consumed < data.len() {
+            let rest = &data[consumed..];
+
+            match self.state {
+                ChunkState::Size => {
+                    let Some((n, line_len)) = next_line(rest, strict)? else {
+                        break;
+                    };
+
+                    let line = std::str::from_utf8(&rest[..n])
+                        .map_err(|_| RequestError::InvalidChunkedBody("invalid chunk size"))?;
+                    let (size, extensions) = match line.split_once(';') {
+                        // Only an extension may be preceded by whitespace.
+                        Some((size, extensions)) => {
+                            (size.trim_end_matches([' ', '\t']), extensions)
+                        }
+                        None => (line, ""),
+                    };
+                    let size = parse_chunk_size(size)
+                        .ok_or(RequestError::InvalidChunkedBody("invalid chunk size"))?;
+                    if line.contains(';') {
+                        self.handle_extensions(extensions)?;
+                    }
+
+                    self.state = if size == 0 {
+                        ChunkState::Trailers
+                    } else {
+                        self.chunks += 1;
+                        if self.chunks > self.max_chunks {
+                            return Err(RequestError::InvalidChunkedBody("too many chunks"));
+                        }
+                        ChunkState::Data(size)
+                    };
+                    consumed += line_len;
+                }
+                ChunkState::Data(remaining) => {
+                    let n = remaining.min(rest.len());
+                    out.extend_from_slice(&rest[..n]);
+
+                    self.state = if n == remaining {
+                        ChunkState::DataEnd
+                    } else {
+                        ChunkState::Data(remaining - n)
+                    };
+                    consumed += n;
+                }
+                ChunkState::DataEnd => {
+                    let Some((n, line_len)) = next_line(rest, strict)? else {
+                        break;
+                    };
+
+                    if n != 0 {
+                        return Err(RequestError::InvalidChunkedBody(
+                            "missing CRLF after chunk data",
+                        ));
+                    }
+
+                    self.state = ChunkState::Size;
+                    consumed += line_len;
+                }
+                ChunkState::Trailers => {
+                    let Some((n, line_len)) = next_line(rest, strict)? else {
+                        break;
+                    };
+
+                    // Trailer fields are accepted but not merged into the
+                    // request headers.
+                    if n == 0 {
+                        self.state = ChunkState::Done;
+                    }
+                    consumed += line_len;
+                }
+                ChunkState::Done => break,
+            }
+        }
+
+        Ok(consumed)
+    }
+
+    fn handle_extensions(&mut self, extensions: &str) -> Result<(), RequestError> {
+        match self.extension_policy {
+            ChunkExtensionPolicy::Ignore => Ok(()),
+            ChunkExtensionPolicy::Reject => Err(RequestError::InvalidChunkedBody(
+                "chunk extensions are not allowed",
+            )),
+            ChunkExtensionPolicy::Preserve => {
+                for ext in split_unquoted(extensions, ';') {
+                    let (name, value) = match ext.split_once('=') {
+                        Some((name, value)) => (name.trim(), Some(unquote(value.trim()))),
+                        None => (ext.trim(), None),
+                    };
+
+                    if name.is_empty() {
+                        return Err(RequestError::InvalidChunkedBody("invalid chunk extension"));
+                    }
+
+                    self.extensions.push(ChunkExtension {
+                        name: name.to_string(),
+                        value,
+                    });
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn next_line(data: &[u8], strict: bool) -> Result<Option<(usize, usize)>, RequestError> {
+    let line_end = find_line_end(data, strict).map_err(RequestError::InvalidChunkedBody)?;
+
+    if line_end.is_none() && data.len() > MAX_CHUNK_LINE_LEN {
+        return Err(RequestError::InvalidChunkedBody("chunk line too long"));
+    }
+
+    Ok(line_end)
+}
+
+/// The most hex digits a chunk size may have, enough for any `u64`.
+const MAX_CHUNK_SIZE_DIGITS: usize = 16;
+
+/// A chunk size of hex digits only: no sign, whitespace or `0x` prefix,
+/// which `from_str_radix` would otherwise let through.
+fn parse_chunk_size(s: &str) -> Option<usize> {
+    if s.is_empty() || s.len() > MAX_CHUNK_SIZE_DIGITS || !s.bytes().all(|b| b.is_ascii_hexdigit())
+    {
+        return None;
+    }
+    usize::from_str_radix(s, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chunked::{ChunkExtension, ChunkedDecoder},
+        config::{ChunkExtensionPolicy, RequestParserConfig},
+        error::RequestError,
+    };
+
+    fn decoder(policy: ChunkExtensionPolicy) -> ChunkedDecoder {
+        let config = RequestParserConfig::builder()
+            .strict(true)
+            .chunk_extensions(policy)
+            .build();
+        ChunkedDecoder::new(&config)
+    }
+
+    #[test]
+    fn test_decode_in_one_pass() {
+        let data = b"5\r\nhello\r\n7;ext=1\r\n, world\r\n0\r\n\r\n";
+        let mut decoder = decoder(ChunkExtensionPolicy::Ignore);
+        let mut out = Vec::new();
+
+        let n = decoder.decode(data, &mut out).unwrap();
+        assert_eq!(n, data.len());
+        assert!(decoder.is_done());
+        assert_eq!(out, b"hello, world");
+    }
+
+    #[test]
+    fn test_decode_byte_by_byte() {
+        let data = b"3\r\nabc\r\n0\r\nTrailer: x\r\n\r\n";
+        let mut decoder = decoder(ChunkExtensionPolicy::Ignore);
+        let mut out = Vec::new();
+        let mut pending = Vec::new();
+
+        for &b in data {
+            pending.push(b);
+            let n = decoder.decode(&pending, &mut out).unwrap();
+            pending.drain(..n);
+        }
+
+        assert!(decoder.is_done());
+        assert!(pending.is_empty());
+        assert_eq!(out, b"abc");
+    }
+
+    #[test]
+    fn test_invalid_chunk_size() {
+        for size in ["zz", "+3", "-3", " 3", "3 ", "0x3", "", "11111111111111111"] {
+            let mut decoder = decoder(ChunkExtensionPolicy::Ignore);
+            let data = format!("{size}\r\nabc\r\n0\r\n\r\n");
+            assert!(
+                decoder.decode(data.as_bytes(), &mut Vec::new()).is_err(),
+                "{size:?}"
+            );
+        }
+
+        let mut decoder = decoder(ChunkExtensionPolicy::Ignore);
+        let mut out = Vec::new();
+        decoder
+            .decode(b"3 ;ext\r\nabc\r\n0\r\n\r\n", &mut out)
+            .unwrap();
+        assert_eq!(out, b"abc");
+    }
+
+    #[test]
+    fn test_max_chunks() {
+        let config = RequestParserConfig::builder().max_chunks(3).build();
+
+        let mut out = Vec::new();
+        ChunkedDecoder::new(&config)
+            .decode(b"1\r\na\r\n1\r\nb\r\n1\r\nc\r\n0\r\n\r\n", &mut out)
+            .unwrap();
+        assert_eq!(out, b"abc");
+
+        let err = ChunkedDecoder::new(&config)
+            .decode(
+                b"1\r\na\r\n1\r\nb\r\n1\r\nc\r\n1\r\nd\r\n0\r\n\r\n",
+                &mut out,
+            )
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            RequestError::InvalidChunkedBody("too many chunks")
+        ));
+    }
+
+    const WITH_EXTENSIONS: &[u8] = b"5;name=\"a;b\";flag\r\nhello\r\n0\r\n\r\n";
+
+    #[test]
+    fn test_extensions_ignored_by_default() {
+        let mut decoder = decoder(ChunkExtensionPolicy::Ignore);
+        let mut out = Vec::new();
+
+        decoder.decode(WITH_EXTENSIONS, &mut out).unwrap();
+        assert_eq!(out, b"hello");
+        assert!(decoder.extensions().is_empty());
+    }
+
+    #[test]
+    fn test_extensions_preserved() {
+        let mut decoder = decoder(ChunkExtensionPolicy::Preserve);
+        let mut out = Vec::new();
+
+        decoder.decode(WITH_EXTENSIONS, &mut out).unwrap();
+        assert_eq!(out, b"hello");
+        assert_eq!(
+            decoder.extensions(),
+            [
+                ChunkExtension {
+                    name: "name".to_string(),
+                    value: Some("a;b".to_string())
+                },
+                ChunkExtension {
+                    name: "flag".to_string(),
+                    value: None
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extensions_rejected() {
+        let mut decoder = decoder(ChunkExtensionPolicy::Reject);
+
+        let err = decoder
+            .decode(WITH_EXTENSIONS, &mut Vec::new())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            RequestError::InvalidChunkedBody("chunk extensions are not allowed")
+        ));
+    }
+}
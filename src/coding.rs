@@ -0,0 +1,33 @@
+#[cfg(feature = "gzip")]
+use std::io::Read;
+
+use crate::error::RequestError;
+
+/// Undoes a single transfer or content coding. `gzip` and `deflate` need the
+/// `gzip` feature; `identity` is a no-op. Output past `limit` bytes is
+/// [`RequestError::BodyTooLarge`], so a small compressed body can't expand
+/// without bound.
+#[cfg_attr(not(feature = "gzip"), allow(unused_variables))]
+pub(crate) fn decode(coding: &str, data: Vec<u8>, limit: usize) -> Result<Vec<u8>, RequestError> {
+    match coding {
+        "identity" => Ok(data),
+        #[cfg(feature = "gzip")]
+        "gzip" | "x-gzip" => read_all(flate2::read::GzDecoder::new(&data[..]), limit),
+        #[cfg(feature = "gzip")]
+        "deflate" => read_all(flate2::read::ZlibDecoder::new(&data[..]), limit),
+        _ => Err(RequestError::UnsupportedCoding(coding.to_string())),
+    }
+}
+
+#[cfg(feature = "gzip")]
+fn read_all<R: Read>(r: R, limit: usize) -> Result<Vec<u8>, RequestError> {
+    let mut out = Vec::new();
+    r.take((limit as u64).saturating_add(1))
+        .read_to_end(&mut out)
+        .map_err(|_| RequestError::InvalidCodedBody)?;
+
+    if out.len() > limit {
+        return Err(RequestError::BodyTooLarge);
+    }
+    Ok(out)
+}
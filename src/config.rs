@@ -0,0 +1,294 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::headers::lookup_key;
+
+/// What to do when a header name appears on more than one line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Join the values with `", "`, as allowed for list-valued headers.
+    CommaList,
+    /// Keep only the last value.
+    Replace,
+    /// Fail the parse; for headers where a repeat signals an attack or a
+    /// broken client (`Host`, `Content-Length`).
+    Reject,
+    /// Keep every value separately, for headers that can't be joined with
+    /// commas (`Set-Cookie`). Read them back with `Headers::get_all`.
+    KeepAll,
+}
+
+/// What to do with chunk extensions (`5;name=value`) in a chunked body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChunkExtensionPolicy {
+    /// Skip them; nothing in this crate gives them meaning.
+    #[default]
+    Ignore,
+    /// Collect them, readable through `Request::chunk_extensions`.
+    Preserve,
+    /// Fail the parse. Extensions are rarely legitimate, and disagreement
+    /// between servers on how to parse them is a request smuggling vector.
+    Reject,
+}
+
+/// What to do with a header value that isn't valid UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InvalidUtf8Policy {
+    /// Fail the parse.
+    #[default]
+    Reject,
+    /// Replace each invalid sequence with U+FFFD, like
+    /// `String::from_utf8_lossy`.
+    Lossy,
+    /// Decode the value as ISO-8859-1, which legacy clients sometimes send.
+    Latin1,
+}
+
+/// What to do with a header whose name isn't in
+/// [`RequestParserConfig::allowed_header_names`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownHeaderPolicy {
+    /// Fail the parse.
+    #[default]
+    Reject,
+    /// Leave the header out of the parsed request.
+    Drop,
+}
+
+/// Knobs controlling how strictly incoming requests are parsed and how large
+/// they may get.
+///
+/// The defaults are meant for production use:
+///
+/// | limit                  | default |
+/// |------------------------|---------|
+/// | `max_request_line_len` | 8 KiB   |
+/// | `max_header_bytes`     | 16 KiB  |
+/// | `max_header_count`     | 100     |
+/// | `max_body_size`        | 10 MiB  |
+/// | `max_chunks`           | 100 000 |
+/// | `max_merged_value_len` | 8 KiB   |
+#[derive(Debug, Clone)]
+pub struct RequestParserConfig {
+    /// Reject sloppy-but-recoverable input instead of normalizing it.
+    /// Lenient (`false`) by default to tolerate buggy clients.
+    pub strict: bool,
+    /// Longest accepted request line, excluding the line ending.
+    pub max_request_line_len: usize,
+    /// Total bytes allowed for the header section, line endings included.
+    pub max_header_bytes: usize,
+    /// Number of header lines allowed, counting repeated names separately.
+    pub max_header_count: usize,
+    /// Largest accepted `Content-Length`.
+    pub max_body_size: usize,
+    /// Number of chunks allowed in a chunked body. Tiny chunks cost far more
+    /// to process than their size suggests, so this bounds the work
+    /// `max_body_size` alone doesn't.
+    pub max_chunks: usize,
+    /// Longest value a header may reach by merging repeated lines under
+    /// [`MergePolicy::CommaList`] or [`MergePolicy::KeepAll`].
+    pub max_merged_value_len: usize,
+    /// Per-header policy for repeated names, keyed by lowercase name.
+    pub merge_policies: HashMap<String, MergePolicy>,
+    /// Policy for repeated names not listed in `merge_policies`.
+    pub default_merge_policy: MergePolicy,
+    /// How chunk extensions in chunked bodies are treated; ignored by default.
+    pub chunk_extensions: ChunkExtensionPolicy,
+    /// Accept HTTP/0.9 simple requests (`GET /path` with no version and no
+    /// headers). Off by default.
+    pub http09: bool,
+    /// How header values that aren't valid UTF-8 are treated; rejected by
+    /// default. Values that are valid UTF-8 are never reinterpreted.
+    pub invalid_utf8: InvalidUtf8Policy,
+    /// When set, the only header names accepted, in lowercase; anything else
+    /// is handled per `unknown_headers`. Every name is allowed by default.
+    /// `Content-Length` and `Transfer-Encoding` are always allowed: dropping
+    /// them would leave the body to be parsed as the next request.
+    pub allowed_header_names: Option<HashSet<String>>,
+    /// How headers missing from `allowed_header_names` are treated.
+    pub unknown_headers: UnknownHeaderPolicy,
+    /// When set, a request with neither `Content-Length` nor
+    /// `Transfer-Encoding` has a body running up to this sequence, which is
+    /// consumed but not part of the body. Every such request must then end
+    /// with it, even a `GET`. Off by default.
+    pub body_terminator: Option<Vec<u8>>,
+}
+
+/// The default `max_body_size`, also what [`Request::decoded_body`]
+/// allows a request that wasn't parsed under a config.
+///
+/// [`Request::decoded_body`]: crate::request::Request::decoded_body
+pub(crate) const DEFAULT_MAX_BODY_SIZE: usize = 10 * 1024 * 1024;
+
+impl Default for RequestParserConfig {
+    fn default() -> Self {
+        Self {
+            strict: false,
+            max_request_line_len: 8 * 1024,
+            max_header_bytes: 16 * 1024,
+            max_header_count: 100,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            max_chunks: 100_000,
+            max_merged_value_len: 8 * 1024,
+            merge_policies: HashMap::from([
+                ("host".to_string(), MergePolicy::Reject),
+                ("content-length".to_string(), MergePolicy::Reject),
+                ("set-cookie".to_string(), MergePolicy::KeepAll),
+            ]),
+            default_merge_policy: MergePolicy::CommaList,
+            chunk_extensions: ChunkExtensionPolicy::Ignore,
+            http09: false,
+            invalid_utf8: InvalidUtf8Policy::Reject,
+            allowed_header_names: None,
+            unknown_headers: UnknownHeaderPolicy::Reject,
+            body_terminator: None,
+        }
+    }
+}
+
+impl RequestParserConfig {
+    pub fn builder() -> RequestParserConfigBuilder {
+        RequestParserConfigBuilder::default()
+    }
+
+    pub fn merge_policy(&self, field_name: &str) -> MergePolicy {
+        self.merge_policies
+            .get(lookup_key(field_name).as_ref())
+            .copied()
+            .unwrap_or(self.default_merge_policy)
+    }
+
+    pub fn is_header_allowed(&self, field_name: &str) -> bool {
+        let field_name = lookup_key(field_name);
+        matches!(field_name.as_ref(), "content-length" | "transfer-encoding")
+            || self
+                .allowed_header_names
+                .as_ref()
+                .is_none_or(|names| names.contains(field_name.as_ref()))
+    }
+}
+
+/// Chainable construction of a [`RequestParserConfig`], starting from the
+/// defaults.
+#[derive(Debug, Clone, Default)]
+pub struct RequestParserConfigBuilder {
+    config: RequestParserConfig,
+}
+
+impl RequestParserConfigBuilder {
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.config.strict = strict;
+        self
+    }
+
+    pub fn max_request_line_len(mut self, len: usize) -> Self {
+        self.config.max_request_line_len = len;
+        self
+    }
+
+    pub fn max_header_bytes(mut self, bytes: usize) -> Self {
+        self.config.max_header_bytes = bytes;
+        self
+    }
+
+    pub fn max_header_count(mut self, count: usize) -> Self {
+        self.config.max_header_count = count;
+        self
+    }
+
+    pub fn max_body_size(mut self, size: usize) -> Self {
+        self.config.max_body_size = size;
+        self
+    }
+
+    pub fn max_chunks(mut self, count: usize) -> Self {
+        self.config.max_chunks = count;
+        self
+    }
+
+    pub fn max_merged_value_len(mut self, len: usize) -> Self {
+        self.config.max_merged_value_len = len;
+        self
+    }
+
+    pub fn merge_policy(mut self, field_name: &str, policy: MergePolicy) -> Self {
+        self.config
+            .merge_policies
+            .insert(field_name.to_lowercase(), policy);
+        self
+    }
+
+    pub fn default_merge_policy(mut self, policy: MergePolicy) -> Self {
+        self.config.default_merge_policy = policy;
+        self
+    }
+
+    pub fn chunk_extensions(mut self, policy: ChunkExtensionPolicy) -> Self {
+        self.config.chunk_extensions = policy;
+        self
+    }
+
+    pub fn http09(mut self, http09: bool) -> Self {
+        self.config.http09 = http09;
+        self
+    }
+
+    pub fn invalid_utf8(mut self, policy: InvalidUtf8Policy) -> Self {
+        self.config.invalid_utf8 = policy;
+        self
+    }
+
+    /// Accepts only the given header names, compared case-insensitively.
+    pub fn allowed_header_names<'a>(mut self, names: impl IntoIterator<Item = &'a str>) -> Self {
+        self.config.allowed_header_names = Some(names.into_iter().map(str::to_lowercase).collect());
+        self
+    }
+
+    pub fn unknown_headers(mut self, policy: UnknownHeaderPolicy) -> Self {
+        self.config.unknown_headers = policy;
+        self
+    }
+
+    pub fn body_terminator(mut self, terminator: impl Into<Vec<u8>>) -> Self {
+        self.config.body_terminator = Some(terminator.into());
+        self
+    }
+
+    pub fn build(self) -> RequestParserConfig {
+        self.config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{ChunkExtensionPolicy, MergePolicy, RequestParserConfig};
+
+    #[test]
+    fn test_default_limits() {
+        let config = RequestParserConfig::default();
+
+        assert!(!config.strict);
+        assert_eq!(config.max_request_line_len, 8 * 1024);
+        assert_eq!(config.max_header_bytes, 16 * 1024);
+        assert_eq!(config.max_header_count, 100);
+        assert_eq!(config.max_body_size, 10 * 1024 * 1024);
+        assert_eq!(config.max_chunks, 100_000);
+        assert_eq!(config.max_merged_value_len, 8 * 1024);
+        assert_eq!(config.merge_policy("Set-Cookie"), MergePolicy::KeepAll);
+        assert_eq!(config.merge_policy("accept"), MergePolicy::CommaList);
+        assert_eq!(config.chunk_extensions, ChunkExtensionPolicy::Ignore);
+    }
+
+    #[test]
+    fn test_builder_overrides_stick() {
+        let config = RequestParserConfig::builder()
+            .strict(true)
+            .max_header_count(10)
+            .max_body_size(1024)
+            .build();
+
+        assert!(config.strict);
+        assert_eq!(config.max_header_count, 10);
+        assert_eq!(config.max_body_size, 1024);
+        assert_eq!(config.max_request_line_len, 8 * 1024);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::{
+    method::Method,
+    request::Request,
+    response::{Response, StatusCode},
+};
+
+/// Cross-origin resource sharing policy, applied as a middleware around a
+/// handler with [`Cors::wrap`].
+///
+/// Preflight `OPTIONS` requests are answered directly with `204 No Content`;
+/// other requests reach the handler and get the allow headers added to the
+/// response. Requests from origins not on the list get no CORS headers at all.
+#[derive(Debug, Clone)]
+pub struct Cors {
+    allowed_origins: Vec<String>,
+    allowed_methods: Vec<Method>,
+}
+
+impl Cors {
+    /// `"*"` in `allowed_origins` allows any origin (the request's `Origin`
+    /// is still echoed back rather than sending a literal `*`).
+    pub fn new(allowed_origins: &[&str], allowed_methods: &[Method]) -> Self {
+        Self {
+            allowed_origins: allowed_origins.iter().map(|o| o.to_string()).collect(),
+            allowed_methods: allowed_methods.to_vec(),
+        }
+    }
+
+    pub fn wrap<H>(self, handler: H) -> impl Fn(&Request) -> Response + Send + Sync
+    where
+        H: Fn(&Request) -> Response + Send + Sync,
+    {
+        move |req| self.handle(req, &handler)
+    }
+
+    fn handle<H>(&self, req: &Request, handler: &H) -> Response
+    where
+        H: Fn(&Request) -> Response,
+    {
+        let origin = req
+            .headers
+            .get("origin")
+            .filter(|o| self.allows(o))
+            .cloned();
+
+        if is_preflight(req) {
+            let mut res = Response::new(StatusCode::NoContent);
+            if let Some(origin) = origin {
+                self.set_allow_headers(&mut res, &origin);
+                res.headers
+                    .set("Access-Control-Allow-Methods", &self.methods_list());
+                if let Some(requested) = req.headers.get("access-control-request-headers") {
+                    res.headers.set("Access-Control-Allow-Headers", requested);
+                }
+            }
+            return res;
+        }
+
+        let mut res = handler(req);
+        if let Some(origin) = origin {
+            self.set_allow_headers(&mut res, &origin);
+        }
+        res
+    }
+
+    fn allows(&self, origin: &str) -> bool {
+        self.allowed_origins
+            .iter()
+            .any(|allowed| allowed == "*" || allowed.eq_ignore_ascii_case(origin))
+    }
+
+    fn set_allow_headers(&self, res: &mut Response, origin: &str) {
+        res.headers.set("Access-Control-Allow-Origin", origin);
+        res.vary(&["Origin"]);
+    }
+
+    fn methods_list(&self) -> String {
+        self.allowed_methods
+            .iter()
+            .map(|m| m.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+fn is_preflight(req: &Request) -> bool {
+    let is_options = req
+        .request_line
+        .as_ref()
+        .is_some_and(|line| line.method == Method::Options);
+
+    is_options && req.headers.get("access-control-request-method").is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chunk_reader::ChunkReader,
+        cors::Cors,
+        method::Method,
+        request::{Request, request_from_reader},
+        response::{Response, StatusCode},
+    };
+
+    fn handler() -> impl Fn(&Request) -> Response {
+        Cors::new(&["https://app.example"], &[Method::Get, Method::Post])
+            .wrap(|_| Response::new(StatusCode::Ok).with_body("ok"))
+    }
+
+    #[test]
+    fn test_preflight_from_allowed_origin() {
+        let req = request_from_reader(ChunkReader::new(
+            concat!(
+                "OPTIONS /api HTTP/1.1\r\n",
+                "Host: api.example\r\n",
+                "Origin: https://app.example\r\n",
+                "Access-Control-Request-Method: POST\r\n",
+                "Access-Control-Request-Headers: content-type\r\n",
+                "\r\n",
+            ),
+            16,
+        ))
+        .unwrap();
+
+        let res = handler()(&req);
+        assert_eq!(res.status, StatusCode::NoContent);
+        assert_eq!(
+            res.headers.get("Access-Control-Allow-Origin"),
+            Some(&"https://app.example".to_string())
+        );
+        assert_eq!(
+            res.headers.get("Access-Control-Allow-Methods"),
+            Some(&"GET, POST".to_string())
+        );
+        assert_eq!(
+            res.headers.get("Access-Control-Allow-Headers"),
+            Some(&"content-type".to_string())
+        );
+    }
+
+    #[test]
+    fn test_preflight_from_disallowed_origin() {
+        let req = request_from_reader(ChunkReader::new(
+            concat!(
+                "OPTIONS /api HTTP/1.1\r\n",
+                "Host: api.example\r\n",
+                "Origin: https://evil.example\r\n",
+                "Access-Control-Request-Method: POST\r\n",
+                "\r\n",
+            ),
+            16,
+        ))
+        .unwrap();
+
+        let res = handler()(&req);
+        assert_eq!(res.status, StatusCode::NoContent);
+        assert!(res.headers.get("Access-Control-Allow-Origin").is_none());
+        assert!(res.headers.get("Access-Control-Allow-Methods").is_none());
+    }
+
+    #[test]
+    fn test_simple_request_gets_allow_origin() {
+        let req = request_from_reader(ChunkReader::new(
+            concat!(
+                "GET /api HTTP/1.1\r\n",
+                "Host: api.example\r\n",
+                "Origin: https://app.example\r\n",
+                "\r\n",
+            ),
+            16,
+        ))
+        .unwrap();
+
+        let res = handler()(&req);
+        assert_eq!(res.status, StatusCode::Ok);
+        assert_eq!(res.body, b"ok");
+        assert_eq!(
+            res.headers.get("Access-Control-Allow-Origin"),
+            Some(&"https://app.example".to_string())
+        );
+    }
+}
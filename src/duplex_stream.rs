@@ -0,0 +1,152 @@
+use std::{
+    collections::VecDeque,
+    io::{Read, Result, Write},
+    sync::{Arc, Condvar, Mutex},
+};
+
+#[derive(Default)]
+struct Pipe {
+    state: Mutex<PipeState>,
+    readable: Condvar,
+}
+
+#[derive(Default)]
+struct PipeState {
+    buf: VecDeque<u8>,
+    closed: bool,
+}
+
+/// One end of an in-memory, bidirectional byte stream.
+///
+/// Bytes written to one end are read from the other. Reads block until data
+/// arrives, and return EOF once the other end has been dropped, so a server
+/// loop behaves as it would over a socket.
+pub struct DuplexStream {
+    incoming: Arc<Pipe>,
+    outgoing: Arc<Pipe>,
+}
+
+impl DuplexStream {
+    pub fn pair() -> (Self, Self) {
+        let a_to_b = Arc::new(Pipe::default());
+        let b_to_a = Arc::new(Pipe::default());
+
+        (
+            Self {
+                incoming: Arc::clone(&b_to_a),
+                outgoing: Arc::clone(&a_to_b),
+            },
+            Self {
+                incoming: a_to_b,
+                outgoing: b_to_a,
+            },
+        )
+    }
+}
+
+impl Read for DuplexStream {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let mut state = self.incoming.state.lock().unwrap();
+
+        while state.buf.is_empty() && !state.closed {
+            state = self.incoming.readable.wait(state).unwrap();
+        }
+
+        let n = state.buf.len().min(buf.len());
+        for (dst, src) in buf.iter_mut().zip(state.buf.drain(..n)) {
+            *dst = src;
+        }
+
+        Ok(n)
+    }
+}
+
+impl Write for DuplexStream {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let mut state = self.outgoing.state.lock().unwrap();
+        state.buf.extend(buf);
+        self.outgoing.readable.notify_all();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for DuplexStream {
+    fn drop(&mut self) {
+        for pipe in [&self.incoming, &self.outgoing] {
+            pipe.state.lock().unwrap().closed = true;
+            pipe.readable.notify_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        thread,
+    };
+
+    use crate::{
+        duplex_stream::DuplexStream,
+        request::RequestReader,
+        response::{Response, StatusCode},
+    };
+
+    #[test]
+    fn test_bytes_cross_between_ends() {
+        let (mut a, mut b) = DuplexStream::pair();
+
+        a.write_all(b"ping").unwrap();
+        let mut buf = [0u8; 4];
+        b.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"ping");
+
+        b.write_all(b"pong").unwrap();
+        a.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"pong");
+    }
+
+    #[test]
+    fn test_dropping_one_end_is_eof() {
+        let (a, mut b) = DuplexStream::pair();
+        drop(a);
+
+        let mut out = Vec::new();
+        assert_eq!(b.read_to_end(&mut out).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_handler_round_trip() {
+        let (mut client, server) = DuplexStream::pair();
+
+        let worker = thread::spawn(move || {
+            let mut server = server;
+            let req = RequestReader::new(&mut server)
+                .next_request()
+                .unwrap()
+                .expect("request");
+            let target = req.request_line.unwrap().request_target;
+
+            Response::new(StatusCode::Ok)
+                .with_body(format!("you asked for {target}"))
+                .write_to(&mut server)
+                .unwrap();
+        });
+
+        client
+            .write_all(b"GET /coffee HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+
+        let mut out = Vec::new();
+        client.read_to_end(&mut out).unwrap();
+        worker.join().unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(out.ends_with("\r\n\r\nyou asked for /coffee"));
+    }
+}
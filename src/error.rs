@@ -0,0 +1,249 @@
+use std::{fmt, io};
+
+use crate::{method::Method, response::StatusCode};
+
+/// Everything that can go wrong while reading a request off the wire.
+///
+/// Offsets are byte positions from the start of the request (the first byte
+/// of the request line) to the start of the offending line.
+#[derive(Debug)]
+pub enum RequestError {
+    Io(io::Error),
+    UnexpectedEof,
+    InvalidUtf8 {
+        offset: usize,
+    },
+    MalformedRequestLine {
+        offset: usize,
+        reason: &'static str,
+    },
+    MalformedHeader {
+        offset: usize,
+        reason: String,
+    },
+    InvalidHost,
+    InvalidContentLength,
+    InvalidMaxForwards,
+    RequestLineTooLong,
+    /// A well-formed version other than HTTP/1.x.
+    UnsupportedVersion(String),
+    HeadersTooLarge,
+    TooManyHeaders,
+    BodyTooLarge,
+    InvalidTransferEncoding,
+    /// Both `Transfer-Encoding` and `Content-Length`; only rejected in strict
+    /// mode.
+    ConflictingFraming,
+    InvalidChunkedBody(&'static str),
+    UnsupportedCoding(String),
+    InvalidCodedBody,
+    InvalidQuery(String),
+    InvalidPath(&'static str),
+    /// Body framing on a method whose requests shouldn't have a body; only
+    /// checked in strict mode.
+    BodyNotAllowed(Method),
+    UnsupportedCharset(String),
+    /// The body isn't valid text in the named charset.
+    InvalidBodyText(String),
+}
+
+impl RequestError {
+    /// The byte offset of the error within the request, if it points at a
+    /// specific line.
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            RequestError::InvalidUtf8 { offset }
+            | RequestError::MalformedRequestLine { offset, .. }
+            | RequestError::MalformedHeader { offset, .. } => Some(*offset),
+            _ => None,
+        }
+    }
+
+    /// Recovers the parse error an `io::Error` was made from, e.g. by
+    /// [`Request::read_body_with`](crate::request::Request::read_body_with),
+    /// wrapping any other error as `Io`.
+    pub(crate) fn from_io(err: io::Error) -> Self {
+        if !err
+            .get_ref()
+            .is_some_and(|inner| inner.is::<RequestError>())
+        {
+            return RequestError::Io(err);
+        }
+
+        let kind = err.kind();
+        match err
+            .into_inner()
+            .map(|inner| inner.downcast::<RequestError>())
+        {
+            Some(Ok(err)) => *err,
+            _ => RequestError::Io(kind.into()),
+        }
+    }
+
+    /// The status to answer the request with. `Io` and `UnexpectedEof`
+    /// mean the client is gone or went quiet and usually get no answer at
+    /// all; they map to `400` for completeness.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            RequestError::BodyTooLarge => StatusCode::PayloadTooLarge,
+            RequestError::RequestLineTooLong => StatusCode::UriTooLong,
+            RequestError::HeadersTooLarge | RequestError::TooManyHeaders => {
+                StatusCode::RequestHeaderFieldsTooLarge
+            }
+            RequestError::UnsupportedVersion(_) => StatusCode::HttpVersionNotSupported,
+            // RFC 9112 section 6.1: an unknown transfer coding is a 501.
+            RequestError::UnsupportedCoding(_) => StatusCode::NotImplemented,
+            RequestError::UnsupportedCharset(_) => StatusCode::UnsupportedMediaType,
+            RequestError::Io(_)
+            | RequestError::UnexpectedEof
+            | RequestError::InvalidUtf8 { .. }
+            | RequestError::MalformedRequestLine { .. }
+            | RequestError::MalformedHeader { .. }
+            | RequestError::InvalidHost
+            | RequestError::InvalidContentLength
+            | RequestError::InvalidMaxForwards
+            | RequestError::InvalidTransferEncoding
+            | RequestError::ConflictingFraming
+            | RequestError::InvalidChunkedBody(_)
+            | RequestError::InvalidCodedBody
+            | RequestError::InvalidQuery(_)
+            | RequestError::InvalidPath(_)
+            | RequestError::BodyNotAllowed(_)
+            | RequestError::InvalidBodyText(_) => StatusCode::BadRequest,
+        }
+    }
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::Io(err) => write!(f, "i/o error: {err}"),
+            RequestError::UnexpectedEof => write!(f, "EOF before request complete"),
+            RequestError::InvalidUtf8 { offset } => {
+                write!(f, "invalid UTF-8 at offset {offset}")
+            }
+            RequestError::MalformedRequestLine { offset, reason } => {
+                write!(f, "malformed request line at offset {offset}: {reason}")
+            }
+            RequestError::MalformedHeader { offset, reason } => {
+                write!(f, "malformed header at offset {offset}: {reason}")
+            }
+            RequestError::InvalidHost => {
+                write!(f, "invalid host header: expected an authority, not a URL")
+            }
+            RequestError::InvalidContentLength => write!(f, "invalid content-length"),
+            RequestError::InvalidMaxForwards => write!(f, "invalid max-forwards"),
+            RequestError::RequestLineTooLong => write!(f, "request line too long"),
+            RequestError::UnsupportedVersion(version) => {
+                write!(f, "unsupported http version: {version}")
+            }
+            RequestError::HeadersTooLarge => write!(f, "header section too large"),
+            RequestError::TooManyHeaders => write!(f, "too many headers"),
+            RequestError::BodyTooLarge => write!(f, "body exceeds the maximum size"),
+            RequestError::InvalidTransferEncoding => {
+                write!(
+                    f,
+                    "invalid transfer-encoding: chunked must be the final coding"
+                )
+            }
+            RequestError::ConflictingFraming => {
+                write!(f, "both transfer-encoding and content-length present")
+            }
+            RequestError::InvalidChunkedBody(reason) => {
+                write!(f, "invalid chunked body: {reason}")
+            }
+            RequestError::UnsupportedCoding(coding) => {
+                write!(f, "unsupported coding: {coding}")
+            }
+            RequestError::InvalidCodedBody => write!(f, "body could not be decoded"),
+            RequestError::InvalidQuery(reason) => write!(f, "invalid query string: {reason}"),
+            RequestError::InvalidPath(reason) => write!(f, "invalid request path: {reason}"),
+            RequestError::BodyNotAllowed(method) => write!(f, "{method} request with a body"),
+            RequestError::UnsupportedCharset(charset) => {
+                write!(f, "unsupported charset: {charset}")
+            }
+            RequestError::InvalidBodyText(charset) => write!(f, "body is not valid {charset}"),
+        }
+    }
+}
+
+impl std::error::Error for RequestError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RequestError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RequestError {
+    fn from(err: io::Error) -> Self {
+        RequestError::Io(err)
+    }
+}
+
+impl From<RequestError> for io::Error {
+    fn from(err: RequestError) -> Self {
+        match err {
+            RequestError::Io(err) => err,
+            RequestError::UnexpectedEof => io::Error::new(io::ErrorKind::UnexpectedEof, err),
+            err => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use crate::{error::RequestError, method::Method, response::StatusCode};
+
+    #[test]
+    fn test_status_codes() {
+        let cases = [
+            (RequestError::BodyTooLarge, 413),
+            (RequestError::RequestLineTooLong, 414),
+            (RequestError::HeadersTooLarge, 431),
+            (RequestError::TooManyHeaders, 431),
+            (RequestError::UnsupportedVersion("2.0".into()), 505),
+            (RequestError::UnsupportedCoding("br".into()), 501),
+            (RequestError::UnsupportedCharset("utf-16".into()), 415),
+            (
+                RequestError::MalformedRequestLine {
+                    offset: 0,
+                    reason: "missing method",
+                },
+                400,
+            ),
+            (
+                RequestError::MalformedHeader {
+                    offset: 16,
+                    reason: "bad".into(),
+                },
+                400,
+            ),
+            (RequestError::InvalidUtf8 { offset: 0 }, 400),
+            (RequestError::InvalidHost, 400),
+            (RequestError::InvalidContentLength, 400),
+            (RequestError::InvalidMaxForwards, 400),
+            (RequestError::InvalidTransferEncoding, 400),
+            (RequestError::ConflictingFraming, 400),
+            (RequestError::InvalidChunkedBody("bad size"), 400),
+            (RequestError::InvalidCodedBody, 400),
+            (RequestError::InvalidQuery("bad".into()), 400),
+            (RequestError::InvalidPath("encoded slash"), 400),
+            (RequestError::BodyNotAllowed(Method::Get), 400),
+            (RequestError::InvalidBodyText("utf-8".into()), 400),
+            (RequestError::UnexpectedEof, 400),
+            (RequestError::Io(io::ErrorKind::BrokenPipe.into()), 400),
+        ];
+
+        for (err, code) in cases {
+            assert_eq!(err.status_code().code(), code, "{err}");
+        }
+        assert_eq!(
+            RequestError::HeadersTooLarge.status_code(),
+            StatusCode::RequestHeaderFieldsTooLarge
+        );
+    }
+}
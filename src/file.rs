@@ -0,0 +1,430 @@
+use std::{
+    fs::File,
+    io::{self, Read},
+    net::TcpStream,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{
+    headers::ContentRange,
+    httpdate::format_http_date,
+    method::Method,
+    path::canonical_request_path,
+    request::Request,
+    response::{Response, StatusCode},
+};
+
+/// A file served by [`serve_static`], with what middleware may want to know
+/// about it, e.g. to log the path or key a cache on size and mtime.
+#[derive(Debug, Clone)]
+pub struct FileResponse {
+    pub response: Response,
+    /// The file on disk the request resolved to.
+    pub path: PathBuf,
+    pub size: u64,
+    /// `None` where the platform doesn't record it.
+    pub modified: Option<SystemTime>,
+}
+
+impl From<FileResponse> for Response {
+    fn from(file: FileResponse) -> Self {
+        file.response
+    }
+}
+
+/// Serves the file under `root` named by the request's path, with
+/// `Last-Modified` set from its mtime. The body is sent from the open file
+/// when the response is written, not read up front. The path is canonicalized first, so
+/// `..` can't climb out of `root`, and segments a filesystem could read as
+/// a separator, drive or root (`\`, `:`, NUL) are refused. The file must
+/// also resolve, symlinks followed, to somewhere inside `root`. Fails with
+/// `NotFound` for directories and missing files, and `InvalidInput` for
+/// paths that don't canonicalize or that lead outside `root`.
+///
+/// A `GET` with a single `bytes` range gets `206` and just that range, or
+/// `416` if the range lies past the end of the file. An `If-Range` that no
+/// longer matches the file's mtime turns the range request back into a
+/// plain `200`.
+pub fn serve_static(root: &Path, req: &Request) -> io::Result<FileResponse> {
+    let line = req
+        .request_line
+        .as_ref()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no request line"))?;
+    let target = canonical_request_path(line)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+
+    let path = resolve(root, &target)?;
+    let file = File::open(&path)?;
+    let metadata = file.metadata()?;
+    if !metadata.is_file() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "not a file"));
+    }
+
+    let size = metadata.len();
+    let modified = metadata.modified().ok();
+    let range = req
+        .headers
+        .range()
+        .filter(|_| line.method == Method::Get && req.headers.range_applies(None, modified));
+
+    let mut response = match range.map(|range| range.resolve(size)) {
+        None => Response::new(StatusCode::Ok).with_file_body(file, 0, size),
+        Some(Some((start, end))) => {
+            let content_range = ContentRange::Bytes {
+                start,
+                end,
+                total: Some(size),
+            };
+            Response::new(StatusCode::PartialContent)
+                .with_header("Content-Range", &content_range.to_string())
+                .with_file_body(file, start, end - start + 1)
+        }
+        Some(None) => {
+            let content_range = ContentRange::Unsatisfiable { total: size };
+            Response::new(StatusCode::RangeNotSatisfiable)
+                .with_header("Content-Range", &content_range.to_string())
+        }
+    };
+    response = response.with_header("Accept-Ranges", "bytes");
+    if let Some(modified) = modified {
+        response = response.with_header("Last-Modified", &format_http_date(modified));
+    }
+
+    Ok(FileResponse {
+        response,
+        path,
+        size,
+        modified,
+    })
+}
+
+/// `target`, a canonical request path, as a file under `root`.
+fn resolve(root: &Path, target: &str) -> io::Result<PathBuf> {
+    let outside = || io::Error::new(io::ErrorKind::InvalidInput, "path outside root");
+
+    let mut path = root.to_path_buf();
+    for segment in target.split('/').filter(|segment| !segment.is_empty()) {
+        if segment.contains(['\\', ':', '\0']) || Path::new(segment).has_root() {
+            return Err(outside());
+        }
+        path.push(segment);
+    }
+
+    let path = path.canonicalize()?;
+    if !path.starts_with(root.canonicalize()?) {
+        return Err(outside());
+    }
+    Ok(path)
+}
+
+/// Copies `file` from its current position to the end onto `stream`.
+///
+/// With the `sendfile` feature on Linux the bytes go straight from the page
+/// cache to the socket via `sendfile(2)`, without passing through userspace.
+/// Everywhere else, or if the kernel refuses, it falls back to a buffered
+/// copy. Returns the number of bytes sent.
+pub fn send_file(file: &mut File, stream: &TcpStream) -> io::Result<u64> {
+    send_file_range(file, u64::MAX, stream)
+}
+
+/// Like [`send_file`], stopping after `len` bytes. The server writes file
+/// bodies with it.
+pub(crate) fn send_file_range(file: &mut File, len: u64, stream: &TcpStream) -> io::Result<u64> {
+    #[cfg(all(feature = "sendfile", target_os = "linux"))]
+    if let Some(sent) = sendfile::send(file, len, stream)? {
+        return Ok(sent);
+    }
+
+    let mut stream = stream;
+    io::copy(&mut file.by_ref().take(len), &mut stream)
+}
+
+#[cfg(all(feature = "sendfile", target_os = "linux"))]
+mod sendfile {
+    use std::{
+        fs::File,
+        io::{self, Seek, SeekFrom},
+        net::TcpStream,
+        os::fd::AsRawFd,
+    };
+
+    /// The most Linux transfers in one `sendfile` call.
+    const MAX_SENDFILE_LEN: u64 = 0x7fff_f000;
+
+    /// Returns `None`, having sent nothing, when the file or socket doesn't
+    /// support `sendfile` and the caller should copy instead.
+    pub(super) fn send(file: &mut File, len: u64, stream: &TcpStream) -> io::Result<Option<u64>> {
+        let start = file.stream_position()?;
+        let end = file.metadata()?.len().min(start.saturating_add(len));
+        let mut offset = start as libc::off_t;
+
+        while (offset as u64) < end {
+            let count = (end - offset as u64).min(MAX_SENDFILE_LEN) as usize;
+            // SAFETY: both descriptors are open for the duration of the call
+            // and `offset` is a valid, exclusively borrowed `off_t`.
+            let n =
+                unsafe { libc::sendfile(stream.as_raw_fd(), file.as_raw_fd(), &mut offset, count) };
+
+            if n < 0 {
+                let err = io::Error::last_os_error();
+                match err.raw_os_error() {
+                    Some(libc::EINTR) => continue,
+                    Some(libc::EINVAL | libc::ENOSYS) if offset as u64 == start => {
+                        return Ok(None);
+                    }
+                    _ => return Err(err),
+                }
+            }
+
+            // The file shrank underneath us.
+            if n == 0 {
+                break;
+            }
+        }
+
+        file.seek(SeekFrom::Start(offset as u64))?;
+        Ok(Some(offset as u64 - start))
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::{
+        env,
+        fs::{self, File},
+        io::{self, Read, Seek, SeekFrom, Write},
+        net::{TcpListener, TcpStream},
+        process, thread,
+        time::Duration,
+    };
+
+    use crate::{
+        chunk_reader::ChunkReader,
+        file::{send_file, serve_static},
+        httpdate::format_http_date,
+        request::request_from_reader,
+        response::{Response, StatusCode},
+        server::{Server, ServerConfig},
+    };
+
+    /// Sends `file` to a fresh local socket with `send` and returns what the
+    /// other end received.
+    fn receive(file: &mut File, send: fn(&mut File, &TcpStream) -> io::Result<u64>) -> Vec<u8> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let receiver = thread::spawn(move || {
+            let (mut conn, _) = listener.accept().unwrap();
+            let mut out = Vec::new();
+            conn.read_to_end(&mut out).unwrap();
+            out
+        });
+
+        let stream = TcpStream::connect(addr).unwrap();
+        let sent = send(file, &stream).unwrap();
+        drop(stream);
+
+        let out = receiver.join().unwrap();
+        assert_eq!(sent, out.len() as u64);
+        out
+    }
+
+    /// What writing `response` sends as its body.
+    fn body(response: &Response) -> Vec<u8> {
+        let mut out = Vec::new();
+        response.write_to(&mut out).unwrap();
+        let start = out.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+        out.split_off(start)
+    }
+
+    #[test]
+    fn test_large_file_matches_buffered_copy() {
+        let path = env::temp_dir().join(format!("rust-http-send-file-{}", process::id()));
+        let data: Vec<u8> = (0..3 * 1024 * 1024 + 17u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+            .collect();
+        File::create(&path).unwrap().write_all(&data).unwrap();
+
+        let mut file = File::open(&path).unwrap();
+        let sent = receive(&mut file, send_file);
+
+        file.seek(SeekFrom::Start(0)).unwrap();
+        let copied = receive(&mut file, |file, mut stream| io::copy(file, &mut stream));
+
+        file.seek(SeekFrom::Start(1000)).unwrap();
+        let tail = receive(&mut file, send_file);
+
+        fs::remove_file(&path).unwrap();
+        assert!(sent == data, "send_file output differs from the file");
+        assert!(copied == sent);
+        assert!(tail == data[1000..]);
+    }
+
+    #[test]
+    fn test_serve_static_metadata() {
+        let root = env::temp_dir().join(format!("rust-http-static-{}", process::id()));
+        fs::create_dir_all(root.join("css")).unwrap();
+        let path = root.join("css/site.css");
+        fs::write(&path, "body {}").unwrap();
+
+        let get = |target: &str| {
+            let data = format!("GET {target} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+            let req = request_from_reader(ChunkReader::new(&data, 16)).unwrap();
+            serve_static(&root, &req)
+        };
+
+        let file = get("/img/../css/site.css").unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+        assert_eq!(file.path, path);
+        assert_eq!(file.size, 7);
+        assert_eq!(file.modified, Some(metadata.modified().unwrap()));
+        assert_eq!(body(&file.response), b"body {}");
+        assert_eq!(
+            file.response.headers.get("last-modified"),
+            Some(&format_http_date(metadata.modified().unwrap()))
+        );
+
+        let missing = get("/css").unwrap_err().kind();
+        let invalid = get("/css%2Fsite.css").unwrap_err().kind();
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(missing, io::ErrorKind::NotFound);
+        assert_eq!(invalid, io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_serve_static_ranges() {
+        let root = env::temp_dir().join(format!("rust-http-static-range-{}", process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("digits"), "0123456789").unwrap();
+        let modified = fs::metadata(root.join("digits"))
+            .unwrap()
+            .modified()
+            .unwrap();
+
+        let get = |method: &str, headers: &str| {
+            let data = format!("{method} /digits HTTP/1.1\r\nHost: localhost\r\n{headers}\r\n");
+            let req = request_from_reader(ChunkReader::new(&data, 16)).unwrap();
+            let response = serve_static(&root, &req).unwrap().response;
+            let content_range = response.headers.get("content-range").cloned();
+            (response.status, content_range, body(&response))
+        };
+
+        let partial = get("GET", "Range: bytes=2-4\r\n");
+        let suffix = get("GET", "Range: bytes=-3\r\n");
+        let past_end = get("GET", "Range: bytes=10-\r\n");
+        let head = get("HEAD", "Range: bytes=2-4\r\n");
+        let current = format!(
+            "Range: bytes=2-4\r\nIf-Range: {}\r\n",
+            format_http_date(modified)
+        );
+        let if_range_matches = get("GET", &current);
+        let stale = format!(
+            "Range: bytes=2-4\r\nIf-Range: {}\r\n",
+            format_http_date(modified - Duration::from_secs(60))
+        );
+        let if_range_stale = get("GET", &stale);
+        let if_range_etag = get("GET", "Range: bytes=2-4\r\nIf-Range: \"v1\"\r\n");
+        fs::remove_dir_all(&root).unwrap();
+
+        let range = |value: &str| Some(value.to_string());
+        assert_eq!(
+            partial,
+            (
+                StatusCode::PartialContent,
+                range("bytes 2-4/10"),
+                b"234".to_vec()
+            )
+        );
+        assert_eq!(
+            suffix,
+            (
+                StatusCode::PartialContent,
+                range("bytes 7-9/10"),
+                b"789".to_vec()
+            )
+        );
+        assert_eq!(
+            past_end,
+            (
+                StatusCode::RangeNotSatisfiable,
+                range("bytes */10"),
+                Vec::new()
+            )
+        );
+        assert_eq!(head, (StatusCode::Ok, None, b"0123456789".to_vec()));
+        assert_eq!(if_range_matches, partial);
+        assert_eq!(if_range_stale, head);
+        assert_eq!(if_range_etag, head);
+    }
+
+    #[test]
+    fn test_server_sends_static_file() {
+        let root = env::temp_dir().join(format!("rust-http-static-server-{}", process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(root.join("blob"), &data).unwrap();
+
+        let server = Server::bind("127.0.0.1:0", ServerConfig::default()).unwrap();
+        let addr = server.local_addr().unwrap();
+        let served = root.clone();
+        thread::spawn(move || server.serve(move |req| serve_static(&served, req).unwrap().into()));
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /blob HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        stream
+            .write_all(b"GET /blob HTTP/1.1\r\nRange: bytes=-5\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut out = Vec::new();
+        stream.read_to_end(&mut out).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        let head = b"HTTP/1.1 200 OK\r\n";
+        assert!(out.starts_with(head));
+        let full_end = out.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+        assert!(out[full_end..].starts_with(&data));
+        let partial = &out[full_end + data.len()..];
+        assert!(partial.starts_with(b"HTTP/1.1 206 Partial Content\r\n"));
+        assert!(partial.ends_with(&data[data.len() - 5..]));
+    }
+
+    #[test]
+    fn test_serve_static_stays_inside_root() {
+        let base = env::temp_dir().join(format!("rust-http-static-escape-{}", process::id()));
+        let root = base.join("root");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(base.join("secret"), "secret").unwrap();
+        fs::write(root.join("ok"), "ok").unwrap();
+        std::os::unix::fs::symlink(base.join("secret"), root.join("link")).unwrap();
+
+        let get = |target: &str| {
+            let data = format!("GET {target} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+            let req = request_from_reader(ChunkReader::new(&data, 16)).unwrap();
+            serve_static(&root, &req).map_err(|err| err.kind())
+        };
+
+        let ok = get("/ok").map(|file| body(&file.response));
+        let escapes = [
+            "/..%5Csecret",
+            "/%5C..%5Csecret",
+            "/C:%5Csecret",
+            "/C:",
+            "/%5C%5Cserver%5Cshare",
+            "/ok%00",
+            "/link",
+        ]
+        .map(get);
+        fs::remove_dir_all(&base).unwrap();
+
+        assert_eq!(ok, Ok(b"ok".to_vec()));
+        for (target, result) in escapes.iter().enumerate() {
+            assert_eq!(
+                result.as_ref().map(|_| ()),
+                Err(&io::ErrorKind::InvalidInput),
+                "escape #{target}"
+            );
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use std::net::{IpAddr, SocketAddr};
+
+use crate::{
+    headers::{split_unquoted, unquote},
+    request::Request,
+};
+
+impl Request {
+    /// The originating client address when running behind proxies.
+    ///
+    /// The hop chain comes from `Forwarded` (RFC 7239) or, failing that,
+    /// `X-Forwarded-For`, followed by the socket peer. It is walked from the
+    /// nearest hop outwards, skipping addresses in `trusted_proxies`; the
+    /// first untrusted address is the client. Anything further left was
+    /// supplied by that client and can't be trusted. Returns `None` when that
+    /// hop is obfuscated (`unknown`, `_hidden`) or unparseable, and when
+    /// there is no socket peer: without a trusted proxy in front, the headers
+    /// could have been written by anyone.
+    pub fn client_ip(&self, trusted_proxies: &[IpAddr]) -> Option<IpAddr> {
+        let peer = self.peer_addr?.ip();
+        if !trusted_proxies.contains(&peer) {
+            return Some(peer);
+        }
+
+        let chain = self.forwarded_chain();
+        for hop in chain.iter().rev() {
+            match hop {
+                Some(ip) if trusted_proxies.contains(ip) => continue,
+                hop => return *hop,
+            }
+        }
+
+        // Every hop was a trusted proxy; the leftmost one is the best we have.
+        chain.first().copied().flatten().or(Some(peer))
+    }
+
+    /// The scheme the client used, `http` or `https`.
+    ///
+    /// A locally terminated TLS connection is always `https`. Otherwise, when
+    /// `trust_forwarded` is set, the scheme the first proxy saw is taken from
+    /// `Forwarded` (`proto=`) or `X-Forwarded-Proto`. Only enable that when
+    /// every request passes through a proxy that overwrites these headers.
+    pub fn scheme(&self, trust_forwarded: bool) -> &str {
+        if self.tls {
+            return "https";
+        }
+
+        if trust_forwarded && let Some(proto) = self.forwarded_proto() {
+            return proto;
+        }
+
+        "http"
+    }
+
+    fn forwarded_proto(&self) -> Option<&'static str> {
+        let proto = if let Some(forwarded) = self.headers.get("forwarded") {
+            let first = split_unquoted(forwarded, ',').into_iter().next()?;
+            split_unquoted(first, ';')
+                .into_iter()
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(name, _)| name.trim().eq_ignore_ascii_case("proto"))
+                .map(|(_, value)| unquote(value.trim()))?
+        } else {
+            let xfp = self.headers.get("x-forwarded-proto")?;
+            xfp.split(',').next()?.trim().to_string()
+        };
+
+        if proto.eq_ignore_ascii_case("https") {
+            Some("https")
+        } else if proto.eq_ignore_ascii_case("http") {
+            Some("http")
+        } else {
+            None
+        }
+    }
+
+    fn forwarded_chain(&self) -> Vec<Option<IpAddr>> {
+        if let Some(forwarded) = self.headers.get("forwarded") {
+            return split_unquoted(forwarded, ',')
+                .into_iter()
+                .map(|element| {
+                    split_unquoted(element, ';')
+                        .into_iter()
+                        .filter_map(|pair| pair.split_once('='))
+                        .find(|(name, _)| name.trim().eq_ignore_ascii_case("for"))
+                        .and_then(|(_, value)| parse_node(&unquote(value.trim())))
+                })
+                .collect();
+        }
+
+        if let Some(xff) = self.headers.get("x-forwarded-for") {
+            return xff.split(',').map(|hop| parse_node(hop.trim())).collect();
+        }
+
+        Vec::new()
+    }
+}
+
+/// Parses a forwarded node: a bare IP, `ip:port`, or `[ipv6]:port`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    if let Ok(ip) = node.parse::<IpAddr>() {
+        return Some(ip);
+    }
+
+    if let Ok(addr) = node.parse::<SocketAddr>() {
+        return Some(addr.ip());
+    }
+
+    node.strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+        .and_then(|ip| ip.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use crate::{chunk_reader::ChunkReader, request::request_from_reader};
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_x_forwarded_for_chain() {
+        let mut req = request_from_reader(ChunkReader::new(
+            concat!(
+                "GET / HTTP/1.1\r\n",
+                "Host: localhost\r\n",
+                "X-Forwarded-For: 203.0.113.7, 198.51.100.2, 10.0.0.2\r\n",
+                "\r\n",
+            ),
+            16,
+        ))
+        .unwrap();
+        req.peer_addr = Some("10.0.0.1:5000".parse().unwrap());
+
+        let trusted = [ip("10.0.0.1"), ip("10.0.0.2")];
+        assert_eq!(req.client_ip(&trusted), Some(ip("198.51.100.2")));
+
+        let trusted = [ip("10.0.0.1"), ip("10.0.0.2"), ip("198.51.100.2")];
+        assert_eq!(req.client_ip(&trusted), Some(ip("203.0.113.7")));
+    }
+
+    #[test]
+    fn test_untrusted_peer_ignores_headers() {
+        let mut req = request_from_reader(ChunkReader::new(
+            "GET / HTTP/1.1\r\nX-Forwarded-For: 1.2.3.4\r\n\r\n",
+            16,
+        ))
+        .unwrap();
+        req.peer_addr = Some("192.0.2.9:5000".parse().unwrap());
+
+        assert_eq!(req.client_ip(&[ip("10.0.0.1")]), Some(ip("192.0.2.9")));
+    }
+
+    #[test]
+    fn test_forwarded_header() {
+        let mut req = request_from_reader(ChunkReader::new(
+            concat!(
+                "GET / HTTP/1.1\r\n",
+                "Forwarded: for=1.2.3.4;proto=https, for=\"[2001:db8::1]:4711\"\r\n",
+                "\r\n",
+            ),
+            16,
+        ))
+        .unwrap();
+
+        // No socket peer, so nothing vouches for the header.
+        assert_eq!(req.client_ip(&[]), None);
+        assert_eq!(req.client_ip(&[ip("2001:db8::1")]), None);
+
+        req.peer_addr = Some("10.0.0.1:5000".parse().unwrap());
+        let proxy = ip("10.0.0.1");
+        assert_eq!(req.client_ip(&[proxy]), Some(ip("2001:db8::1")));
+        assert_eq!(
+            req.client_ip(&[proxy, ip("2001:db8::1")]),
+            Some(ip("1.2.3.4"))
+        );
+    }
+
+    #[test]
+    fn test_plaintext_scheme() {
+        let req = request_from_reader(ChunkReader::new(
+            "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n",
+            16,
+        ))
+        .unwrap();
+
+        assert_eq!(req.scheme(true), "http");
+    }
+
+    #[test]
+    fn test_forwarded_proto_scheme_only_when_trusted() {
+        let req = request_from_reader(ChunkReader::new(
+            "GET / HTTP/1.1\r\nX-Forwarded-Proto: https\r\n\r\n",
+            16,
+        ))
+        .unwrap();
+
+        assert_eq!(req.scheme(false), "http");
+        assert_eq!(req.scheme(true), "https");
+
+        let req = request_from_reader(ChunkReader::new(
+            "GET / HTTP/1.1\r\nForwarded: for=1.2.3.4;proto=https, for=10.0.0.1;proto=http\r\n\r\n",
+            16,
+        ))
+        .unwrap();
+
+        assert_eq!(req.scheme(true), "https");
+    }
+
+    #[test]
+    fn test_tls_scheme_ignores_forwarded_proto() {
+        let mut req = request_from_reader(ChunkReader::new(
+            "GET / HTTP/1.1\r\nX-Forwarded-Proto: http\r\n\r\n",
+            16,
+        ))
+        .unwrap();
+        req.tls = true;
+
+        assert_eq!(req.scheme(true), "https");
+    }
+}
@@ -1,53 +1,578 @@
 #![allow(dead_code, unused_variables)]
-use std::collections::HashMap;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use regex::Regex;
+use crate::{
+    config::{InvalidUtf8Policy, MergePolicy, RequestParserConfig, UnknownHeaderPolicy},
+    error::RequestError,
+    httpdate::parse_http_date,
+    line::find_line_end,
+};
 
-#[derive(Debug)]
-pub struct Headers(pub HashMap<String, String>);
+/// Header fields keyed by lowercase name.
+///
+/// The map holds each name's first value. Values after it, kept on lines
+/// of their own under [`MergePolicy::KeepAll`] or added with
+/// [`Headers::append`], are held apart; [`Headers::get_all`] reads them
+/// all.
+///
+/// Names and values set through these methods can't contain CR, LF or NUL,
+/// which would end a header line early when the headers are written.
+#[derive(Debug, Clone, Default)]
+pub struct Headers(pub HashMap<String, String>, Repeated);
+
+/// The values after the first of each name that has several, in order.
+#[derive(Debug, Clone, Default)]
+struct Repeated(HashMap<String, Vec<String>>);
+
+/// Whether `s` can be written into a header line as is: CR and LF would
+/// end the line, letting the text after them pass as a header of its own,
+/// and NUL is refused by many parsers.
+pub(crate) fn is_header_safe(s: &str) -> bool {
+    !s.bytes().any(|b| matches!(b, b'\r' | b'\n' | b'\0'))
+}
+
+fn assert_header_safe(key: &str, value: &str) {
+    assert!(
+        is_header_safe(key) && is_header_safe(value),
+        "header {key:?} has CR, LF or NUL in its name or value"
+    );
+}
+
+/// Expands to a match of a lowercase name against `names`, yielding the
+/// static copy of the one it equals.
+macro_rules! match_common_name {
+    ($lower:expr, $($name:literal),+ $(,)?) => {
+        match $lower {
+            $($name => Some($name),)+
+            _ => None,
+        }
+    };
+}
+
+/// Longer than any name [`common_name`] knows.
+const MAX_COMMON_NAME_LEN: usize = 24;
+
+/// The static lowercase spelling of `name` if it is common enough in
+/// requests and responses to be worth interning.
+fn common_name(name: &str) -> Option<&'static str> {
+    let mut buf = [0u8; MAX_COMMON_NAME_LEN];
+    let lower = buf.get_mut(..name.len())?;
+    lower.copy_from_slice(name.as_bytes());
+    lower.make_ascii_lowercase();
+
+    match_common_name!(
+        std::str::from_utf8(lower).ok()?,
+        "accept",
+        "accept-encoding",
+        "accept-language",
+        "authorization",
+        "cache-control",
+        "connection",
+        "content-encoding",
+        "content-length",
+        "content-type",
+        "cookie",
+        "date",
+        "etag",
+        "host",
+        "if-modified-since",
+        "if-none-match",
+        "last-modified",
+        "location",
+        "origin",
+        "referer",
+        "server",
+        "set-cookie",
+        "transfer-encoding",
+        "upgrade",
+        "user-agent",
+        "vary",
+        "x-forwarded-for",
+    )
+}
+
+/// `name` lowercased as a map key, copied from [`common_name`] when it is
+/// one rather than lowercased character by character.
+fn intern(name: &str) -> String {
+    common_name(name).map_or_else(|| name.to_lowercase(), str::to_owned)
+}
+
+/// `name` as a lookup key: itself when already lowercase, so looking up a
+/// lowercase name allocates nothing.
+pub(crate) fn lookup_key(name: &str) -> Cow<'_, str> {
+    if name
+        .bytes()
+        .any(|b| b.is_ascii_uppercase() || !b.is_ascii())
+    {
+        Cow::Owned(name.to_lowercase())
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+/// A parsed `Content-Type` value such as `text/html; charset=utf-8`.
+///
+/// The type, subtype and parameter names are lowercased; parameter values
+/// keep their case with any surrounding quotes removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentType {
+    pub media_type: String,
+    pub subtype: String,
+    pub params: HashMap<String, String>,
+}
+
+impl ContentType {
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = split_unquoted(value, ';').into_iter();
+        let (media_type, subtype) = parts.next()?.trim().split_once('/')?;
+        let (media_type, subtype) = (media_type.trim(), subtype.trim());
+
+        if !Headers::is_valid_field_name(media_type) || !Headers::is_valid_field_name(subtype) {
+            return None;
+        }
+
+        let mut params = HashMap::new();
+        for param in parts {
+            let param = param.trim();
+            if param.is_empty() {
+                continue;
+            }
+
+            let (name, value) = param.split_once('=')?;
+            params.insert(name.trim().to_lowercase(), unquote(value.trim()));
+        }
+
+        Some(Self {
+            media_type: media_type.to_lowercase(),
+            subtype: subtype.to_lowercase(),
+            params,
+        })
+    }
+
+    /// The `type/subtype` essence without parameters.
+    pub fn essence(&self) -> String {
+        format!("{}/{}", self.media_type, self.subtype)
+    }
+}
+
+/// The validator in an `If-Range` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IfRange {
+    /// An entity tag, quotes and any `W/` prefix included.
+    ETag(String),
+    Date(SystemTime),
+}
+
+impl IfRange {
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if value.starts_with('"') || value.starts_with("W/\"") {
+            return Some(IfRange::ETag(value.to_string()));
+        }
+
+        parse_http_date(value).map(IfRange::Date)
+    }
+
+    /// Whether the range may be served for a resource with these current
+    /// validators. ETags must match strongly, so a weak tag on either side
+    /// never does; dates must equal `last_modified` to the second.
+    pub fn matches(&self, etag: Option<&str>, last_modified: Option<SystemTime>) -> bool {
+        match self {
+            IfRange::ETag(tag) => !tag.starts_with("W/") && etag.is_some_and(|etag| etag == tag),
+            IfRange::Date(date) => {
+                let secs = |t: SystemTime| t.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs());
+                last_modified
+                    .and_then(secs)
+                    .is_some_and(|lm| Some(lm) == secs(*date))
+            }
+        }
+    }
+}
+
+/// A parsed `Content-Range` value, as sent on a `206` or `416` response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentRange {
+    /// `bytes 0-499/1234`, or `bytes 0-499/*` when the total is unknown.
+    /// `end` is inclusive.
+    Bytes {
+        start: u64,
+        end: u64,
+        total: Option<u64>,
+    },
+    /// `bytes */1234`, the form used when no requested range could be served.
+    Unsatisfiable { total: u64 },
+}
+
+impl ContentRange {
+    pub fn parse(value: &str) -> Option<Self> {
+        let (unit, rest) = value.trim().split_once(' ')?;
+        if !unit.eq_ignore_ascii_case("bytes") {
+            return None;
+        }
+
+        let (range, total) = rest.trim().split_once('/')?;
+        let total = match total {
+            "*" => None,
+            total => Some(parse_digits(total)?),
+        };
+
+        if range == "*" {
+            return total.map(|total| ContentRange::Unsatisfiable { total });
+        }
+
+        let (start, end) = range.split_once('-')?;
+        let (start, end) = (parse_digits(start)?, parse_digits(end)?);
+        if start > end || total.is_some_and(|total| end >= total) {
+            return None;
+        }
+
+        Some(ContentRange::Bytes { start, end, total })
+    }
+}
+
+impl fmt::Display for ContentRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContentRange::Bytes { start, end, total } => match total {
+                Some(total) => write!(f, "bytes {start}-{end}/{total}"),
+                None => write!(f, "bytes {start}-{end}/*"),
+            },
+            ContentRange::Unsatisfiable { total } => write!(f, "bytes */{total}"),
+        }
+    }
+}
+
+/// A single range from a `Range: bytes=...` header. Requests for several
+/// ranges at once don't parse, so they get the whole resource.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// `bytes=500-999`, or `bytes=500-` to the end.
+    From { start: u64, end: Option<u64> },
+    /// `bytes=-500`, the last 500 bytes.
+    Suffix(u64),
+}
+
+impl ByteRange {
+    pub fn parse(value: &str) -> Option<Self> {
+        let (unit, range) = value.trim().split_once('=')?;
+        if !unit.trim().eq_ignore_ascii_case("bytes") {
+            return None;
+        }
+
+        let (start, end) = range.trim().split_once('-')?;
+        if start.is_empty() {
+            return Some(ByteRange::Suffix(parse_digits(end)?));
+        }
+
+        let start = parse_digits(start)?;
+        let end = match end {
+            "" => None,
+            end => Some(parse_digits(end)?),
+        };
+        if end.is_some_and(|end| start > end) {
+            return None;
+        }
+
+        Some(ByteRange::From { start, end })
+    }
+
+    /// The first and last byte, inclusive, this range covers in a resource
+    /// of `len` bytes, or `None` if it covers none of them.
+    pub fn resolve(self, len: u64) -> Option<(u64, u64)> {
+        match self {
+            ByteRange::From { start, .. } if start >= len => None,
+            ByteRange::From { start, end } => {
+                Some((start, end.map_or(len - 1, |end| end.min(len - 1))))
+            }
+            ByteRange::Suffix(0) => None,
+            ByteRange::Suffix(_) if len == 0 => None,
+            ByteRange::Suffix(n) => Some((len.saturating_sub(n), len - 1)),
+        }
+    }
+}
+
+/// The directives of a `Cache-Control` header that matter to this crate.
+/// Others are ignored when parsing.
+///
+/// Written back out, only the directives set are listed, e.g. `no-store`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheControl {
+    /// `max-age`, in seconds.
+    pub max_age: Option<u64>,
+    /// `no-cache`, with or without a list of header names.
+    pub no_cache: bool,
+    pub no_store: bool,
+    /// `private`, with or without a list of header names.
+    pub private: bool,
+    pub public: bool,
+    pub must_revalidate: bool,
+}
+
+impl CacheControl {
+    /// Parses a comma-separated list of directives. Names are matched
+    /// case-insensitively and values may be quoted; a `max-age` that isn't
+    /// a number is ignored.
+    pub fn parse(value: &str) -> Self {
+        let mut cc = CacheControl::default();
+
+        for directive in split_unquoted(value, ',') {
+            let (name, arg) = match directive.split_once('=') {
+                Some((name, arg)) => (name.trim(), Some(unquote(arg.trim()))),
+                None => (directive.trim(), None),
+            };
+
+            match name.to_ascii_lowercase().as_str() {
+                "max-age" => cc.max_age = arg.as_deref().and_then(parse_digits),
+                "no-cache" => cc.no_cache = true,
+                "no-store" => cc.no_store = true,
+                "private" => cc.private = true,
+                "public" => cc.public = true,
+                "must-revalidate" => cc.must_revalidate = true,
+                _ => {}
+            }
+        }
+
+        cc
+    }
+}
+
+impl fmt::Display for CacheControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let max_age = self.max_age.map(|secs| format!("max-age={secs}"));
+        let flags = [
+            (self.public, "public"),
+            (self.private, "private"),
+            (self.no_cache, "no-cache"),
+            (self.no_store, "no-store"),
+        ];
+
+        let directives: Vec<&str> = flags
+            .into_iter()
+            .filter_map(|(set, name)| set.then_some(name))
+            .chain(max_age.as_deref())
+            .chain(self.must_revalidate.then_some("must-revalidate"))
+            .collect();
+
+        write!(f, "{}", directives.join(", "))
+    }
+}
+
+/// Decodes a header line that isn't valid UTF-8 by applying `policy` to its
+/// value. `None` if the policy rejects it or the name itself is invalid.
+fn decode_non_utf8_line(line: &[u8], policy: InvalidUtf8Policy) -> Option<String> {
+    let colon = line.iter().position(|&b| b == b':')?;
+    let name = std::str::from_utf8(&line[..colon]).ok()?;
+    let value = &line[colon + 1..];
+
+    let value = match policy {
+        InvalidUtf8Policy::Reject => return None,
+        InvalidUtf8Policy::Lossy => String::from_utf8_lossy(value).into_owned(),
+        InvalidUtf8Policy::Latin1 => value.iter().map(|&b| b as char).collect(),
+    };
+
+    Some(format!("{name}:{value}"))
+}
+
+/// Parses a non-empty run of ASCII digits, without the sign `u64::from_str`
+/// would also accept.
+pub(crate) fn parse_digits(s: &str) -> Option<u64> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+/// Splits `s` on `sep`, ignoring separators inside double-quoted strings.
+pub(crate) fn split_unquoted(s: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            c if c == sep && !in_quotes => {
+                parts.push(&s[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+
+    parts.push(&s[start..]);
+    parts
+}
+
+/// Strips surrounding double quotes and resolves backslash escapes.
+pub(crate) fn unquote(s: &str) -> String {
+    match s.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        Some(inner) => {
+            let mut out = String::with_capacity(inner.len());
+            let mut chars = inner.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => out.extend(chars.next()),
+                    c => out.push(c),
+                }
+            }
+            out
+        }
+        None => s.to_string(),
+    }
+}
 
 impl Headers {
     pub fn new() -> Self {
-        Headers(HashMap::new())
+        Headers(HashMap::new(), Repeated::default())
     }
 
     pub fn parse(&mut self, data: &[u8]) -> (usize, bool, Option<String>) {
-        let s = match std::str::from_utf8(data) {
-            Ok(s) => s,
-            Err(err) => {
-                return (
-                    0,
-                    false,
-                    Some("Unable to decode data as UTF-8 string: {err}".to_string()),
-                );
+        self.parse_with_config(data, &RequestParserConfig::default())
+    }
+
+    /// Parses a whole header block up to and including the blank line that
+    /// ends it, returning the headers and the bytes consumed. Error offsets
+    /// are from the start of `data`; a block without its blank line is
+    /// `UnexpectedEof`.
+    pub fn parse_all(data: &[u8]) -> Result<(Headers, usize), RequestError> {
+        Self::parse_all_with_config(data, &RequestParserConfig::default())
+    }
+
+    /// [`Headers::parse_all`] under `config`'s rules.
+    pub fn parse_all_with_config(
+        data: &[u8],
+        config: &RequestParserConfig,
+    ) -> Result<(Headers, usize), RequestError> {
+        let mut headers = Headers::new();
+        let mut consumed = 0;
+
+        loop {
+            let (n, done, err) = headers.parse_with_config(&data[consumed..], config);
+            if let Some(reason) = err {
+                return Err(RequestError::MalformedHeader {
+                    offset: consumed,
+                    reason,
+                });
+            }
+            consumed += n;
+
+            if done {
+                return Ok((headers, consumed));
+            }
+            if n == 0 {
+                return Err(RequestError::UnexpectedEof);
             }
+        }
+    }
+
+    pub fn parse_with_config(
+        &mut self,
+        data: &[u8],
+        config: &RequestParserConfig,
+    ) -> (usize, bool, Option<String>) {
+        let line_end = match find_line_end(data, config.strict) {
+            Ok(line_end) => line_end,
+            Err(reason) => return (0, false, Some(reason.to_string())),
         };
 
-        if let Some(n) = s.find("\r\n") {
+        if let Some((n, consumed)) = line_end {
             if n == 0 {
-                return (2, true, None);
+                return (consumed, true, None);
             }
 
-            let line = s[..n].trim();
+            let s = match std::str::from_utf8(&data[..n]) {
+                Ok(s) => Cow::Borrowed(s),
+                Err(err) => match decode_non_utf8_line(&data[..n], config.invalid_utf8) {
+                    Some(s) => Cow::Owned(s),
+                    None => {
+                        return (
+                            0,
+                            false,
+                            Some(format!("Unable to decode data as UTF-8 string: {err}")),
+                        );
+                    }
+                },
+            };
+
+            // Only the start is trimmed here: whitespace between the name and
+            // the colon must stay visible so it can be rejected below.
+            let line = s.trim_start();
             let mut parts = line.splitn(2, ':');
             let key = parts.next();
             let value = parts.next().map(str::trim);
 
+            if let (Some(k), Some(_)) = (key, value)
+                && k.ends_with(|c: char| c.is_ascii_whitespace())
+            {
+                return (
+                    0,
+                    false,
+                    Some("Invalid header format: whitespace before colon".to_string()),
+                );
+            }
+
             match (key, value) {
                 (Some(k), Some(v)) if Headers::is_valid_field_name(k) => {
-                    let field_name = k.to_string().to_lowercase();
+                    let field_name = intern(k);
+                    if !config.is_header_allowed(&field_name) {
+                        return match config.unknown_headers {
+                            UnknownHeaderPolicy::Reject => {
+                                (0, false, Some(format!("Header not allowed: {k}")))
+                            }
+                            UnknownHeaderPolicy::Drop => (consumed, false, None),
+                        };
+                    }
+                    let policy = config.merge_policy(&field_name);
 
+                    let repeated = &mut self.1.0;
                     match self.0.entry(field_name) {
                         std::collections::hash_map::Entry::Vacant(e) => {
                             e.insert(v.to_string());
                         }
-                        std::collections::hash_map::Entry::Occupied(mut e) => {
-                            e.get_mut().push_str(", ");
-                            e.get_mut().push_str(v);
-                        }
+                        std::collections::hash_map::Entry::Occupied(mut e) => match policy {
+                            MergePolicy::CommaList | MergePolicy::KeepAll
+                                if e.get().len()
+                                    + repeated.get(e.key()).map_or(0, |values| {
+                                        values.iter().map(|v| v.len() + 2).sum()
+                                    })
+                                    + 2
+                                    + v.len()
+                                    > config.max_merged_value_len =>
+                            {
+                                return (
+                                    0,
+                                    false,
+                                    Some(format!("Header value too long after merging: {k}")),
+                                );
+                            }
+                            MergePolicy::CommaList => {
+                                e.get_mut().push_str(", ");
+                                e.get_mut().push_str(v);
+                            }
+                            MergePolicy::KeepAll => {
+                                repeated
+                                    .entry(e.key().clone())
+                                    .or_default()
+                                    .push(v.to_string());
+                            }
+                            MergePolicy::Replace => {
+                                e.insert(v.to_string());
+                            }
+                            MergePolicy::Reject => {
+                                return (0, false, Some(format!("Duplicate header: {k}")));
+                            }
+                        },
                     }
 
-                    return (n + 2, false, None);
+                    return (consumed, false, None);
                 }
                 _ => {
                     return (
@@ -62,24 +587,157 @@ impl Headers {
         (0, false, None)
     }
 
+    /// The first value of `key`; see [`Headers::get_all`] for the rest.
     pub fn get(&self, key: &str) -> Option<&String> {
-        let key = key.to_lowercase();
-        self.0.get(&key)
+        self.0.get(lookup_key(key).as_ref())
+    }
+
+    /// Every value of `key` in order, including those kept on lines of
+    /// their own under [`MergePolicy::KeepAll`].
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        let key = lookup_key(key);
+        let Some(first) = self.0.get(key.as_ref()) else {
+            return Vec::new();
+        };
+
+        let rest = self.1.0.get(key.as_ref()).into_iter().flatten();
+        std::iter::once(first)
+            .chain(rest)
+            .map(String::as_str)
+            .collect()
+    }
+
+    pub fn content_type(&self) -> Option<ContentType> {
+        self.get("content-type").and_then(|v| ContentType::parse(v))
+    }
+
+    pub fn content_range(&self) -> Option<ContentRange> {
+        self.get("content-range")
+            .and_then(|v| ContentRange::parse(v))
+    }
+
+    /// The parsed `Cache-Control` header; all directives unset when it is
+    /// missing.
+    pub fn cache_control(&self) -> CacheControl {
+        self.get("cache-control")
+            .map(|v| CacheControl::parse(v))
+            .unwrap_or_default()
+    }
+
+    pub fn if_range(&self) -> Option<IfRange> {
+        self.get("if-range").and_then(|v| IfRange::parse(v))
+    }
+
+    pub fn range(&self) -> Option<ByteRange> {
+        self.get("range").and_then(|v| ByteRange::parse(v))
+    }
+
+    /// Whether a `Range` in these headers should be honoured for a resource
+    /// with these current validators: there is no `If-Range`, or it still
+    /// matches. Otherwise the whole resource is served with `200`. An
+    /// `If-Range` that doesn't parse never matches.
+    pub fn range_applies(&self, etag: Option<&str>, last_modified: Option<SystemTime>) -> bool {
+        match self.get("if-range") {
+            None => true,
+            Some(value) => IfRange::parse(value).is_some_and(|v| v.matches(etag, last_modified)),
+        }
+    }
+
+    /// Sets `key` to `value`, replacing any previous values.
+    ///
+    /// # Panics
+    ///
+    /// If `key` or `value` contains CR, LF or NUL.
+    pub fn set(&mut self, key: &str, value: &str) {
+        assert_header_safe(key, value);
+        let key = intern(key);
+        self.1.0.remove(&key);
+        self.0.insert(key, value.to_string());
+    }
+
+    /// Adds `value` to those of `key`, to be written on a line of its own.
+    ///
+    /// # Panics
+    ///
+    /// If `key` or `value` contains CR, LF or NUL.
+    pub fn append(&mut self, key: &str, value: &str) {
+        assert_header_safe(key, value);
+        match self.0.entry(intern(key)) {
+            std::collections::hash_map::Entry::Vacant(e) => {
+                e.insert(value.to_string());
+            }
+            std::collections::hash_map::Entry::Occupied(e) => {
+                self.1
+                    .0
+                    .entry(e.key().clone())
+                    .or_default()
+                    .push(value.to_string());
+            }
+        }
+    }
+
+    /// Removes every value of `key`, returning the first.
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let key = lookup_key(key);
+        self.1.0.remove(key.as_ref());
+        self.0.remove(key.as_ref())
     }
 
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
 
+    /// The number of distinct field names.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+        self.1.0.clear();
+    }
+
+    /// Every field line as a lowercase name and one value, repeated names
+    /// once per value in order, names in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().flat_map(|(k, first)| {
+            let rest = self.1.0.get(k).into_iter().flatten();
+            std::iter::once(first)
+                .chain(rest)
+                .map(move |v| (k.as_str(), v.as_str()))
+        })
+    }
+
+    /// The lowercase names of all fields present, sorted, each listed once
+    /// however many lines it arrived on.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.0.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Whether `field_name` is a non-empty `token`. Runs once per header
+    /// line, so it is a byte loop rather than a pattern match.
     fn is_valid_field_name(field_name: &str) -> bool {
-        let re = Regex::new(r"^[A-Za-z0-9!#$%&'*+\-.^_`|~]+$").unwrap();
-        re.is_match(field_name)
+        !field_name.is_empty() && field_name.bytes().all(is_token_char)
     }
 }
 
+/// `tchar` from RFC 9110 section 5.6.2.
+pub(crate) fn is_token_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::headers::Headers;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::{
+        config::{InvalidUtf8Policy, MergePolicy, RequestParserConfig, UnknownHeaderPolicy},
+        error::RequestError,
+        headers::{ByteRange, CacheControl, ContentRange, Headers, IfRange},
+        request::{RequestReader, request_from_reader_with_config},
+    };
 
     #[test]
     fn test_valid_single_header() {
@@ -104,6 +762,72 @@ mod tests {
         assert!(!done);
     }
 
+    #[test]
+    fn test_whitespace_before_colon_is_rejected() {
+        for data in [
+            &b"Host : localhost\r\n\r\n"[..],
+            b"Host\t: localhost\r\n\r\n",
+        ] {
+            let mut headers = Headers::new();
+            let (n, done, err) = headers.parse(data);
+
+            assert_eq!(
+                err.as_deref(),
+                Some("Invalid header format: whitespace before colon")
+            );
+            assert_eq!(n, 0);
+            assert!(!done);
+            assert!(headers.get("Host").is_none());
+        }
+
+        let mut headers = Headers::new();
+        let (_, _, err) = headers.parse(b"  Host: localhost\r\n\r\n");
+        assert!(err.is_none());
+        assert_eq!(headers.get("Host"), Some(&"localhost".to_string()));
+    }
+
+    #[test]
+    fn test_names() {
+        let data = b"Host: a\r\nAccept: */*\r\nX-Trace: 1\r\naccept: text/html\r\n\r\n";
+        let (headers, _) = Headers::parse_all(data).unwrap();
+
+        assert_eq!(headers.names(), ["accept", "host", "x-trace"]);
+    }
+
+    #[test]
+    fn test_invalid_utf8_value_policies() {
+        let data = b"X-Name: Ren\xe9\r\n\r\n";
+        let parse = |policy| {
+            let config = RequestParserConfig::builder().invalid_utf8(policy).build();
+            let mut headers = Headers::new();
+            let (_, _, err) = headers.parse_with_config(data, &config);
+            (headers.get("x-name").cloned(), err)
+        };
+
+        let (value, err) = parse(InvalidUtf8Policy::Reject);
+        assert!(value.is_none());
+        assert!(err.unwrap().starts_with("Unable to decode data as UTF-8"));
+
+        assert_eq!(
+            parse(InvalidUtf8Policy::Lossy),
+            (Some("Ren\u{fffd}".to_string()), None)
+        );
+        assert_eq!(
+            parse(InvalidUtf8Policy::Latin1),
+            (Some("René".to_string()), None)
+        );
+
+        let config = RequestParserConfig::builder()
+            .invalid_utf8(InvalidUtf8Policy::Latin1)
+            .build();
+        let req = request_from_reader_with_config(
+            &b"GET / HTTP/1.1\r\nX-Name: Ren\xe9\r\n\r\n"[..],
+            config,
+        )
+        .unwrap();
+        assert_eq!(req.headers.get("x-name"), Some(&"René".to_string()));
+    }
+
     #[test]
     fn test_special_character_header_value() {
         let mut headers = Headers::new();
@@ -195,7 +919,6 @@ mod tests {
 
     #[test]
     fn test_multiple_values_for_single_header_field_name() {
-        let mut headers = Headers::new();
         let data = concat!(
             "set-person: lane-loves-go\r\n",
             "set-person: prime-loves-zig\r\n",
@@ -204,22 +927,341 @@ mod tests {
         )
         .as_bytes();
 
-        let mut consumed_bytes = 0;
-        let mut done = false;
-        let mut err = None;
+        let (headers, consumed) = Headers::parse_all(data).unwrap();
 
-        while !done && err.is_none() {
-            let (n, d, e) = headers.parse(&data[consumed_bytes..]);
-            consumed_bytes += n;
-            done = d;
-            err = e;
-        }
-
-        assert!(err.is_none());
         assert_eq!(
             headers.get("set-person"),
             Some(&"lane-loves-go, prime-loves-zig, tj-loves-ocaml".to_string())
         );
+        assert_eq!(consumed, data.len());
+    }
+
+    #[test]
+    fn test_cache_control() {
+        let (headers, _) =
+            Headers::parse_all(b"Cache-Control: max-age=60, must-revalidate\r\n\r\n").unwrap();
+        assert_eq!(
+            headers.cache_control(),
+            CacheControl {
+                max_age: Some(60),
+                must_revalidate: true,
+                ..CacheControl::default()
+            }
+        );
+
+        let cc = CacheControl::parse("Private=\"Set-Cookie, X-Id\", no-cache, max-age=\"30\"");
+        assert!(cc.private && cc.no_cache);
+        assert_eq!(cc.max_age, Some(30));
+
+        assert_eq!(CacheControl::parse("max-age=soon").max_age, None);
+        assert_eq!(Headers::new().cache_control(), CacheControl::default());
+    }
+
+    #[test]
+    fn test_parse_all_in_one_call() {
+        let data = b"Host: localhost\r\nAccept: a\r\nAccept: b\r\nX-Id: 7\r\n\r\nbody";
+        let (headers, consumed) = Headers::parse_all(data).unwrap();
+
+        assert_eq!(consumed, data.len() - 4);
+        assert_eq!(headers.get("host"), Some(&"localhost".to_string()));
+        assert_eq!(headers.get("accept"), Some(&"a, b".to_string()));
+        assert_eq!(headers.get("x-id"), Some(&"7".to_string()));
+
+        let err = Headers::parse_all(b"Host: localhost\r\nBad Header\r\n\r\n").unwrap_err();
+        assert!(matches!(
+            err,
+            RequestError::MalformedHeader { offset: 17, .. }
+        ));
+        assert!(matches!(
+            Headers::parse_all(b"Host: localhost\r\n"),
+            Err(RequestError::UnexpectedEof)
+        ));
+    }
+
+    #[test]
+    fn test_content_type_without_params() {
+        let mut headers = Headers::new();
+        headers.parse(b"Content-Type: application/json\r\n");
+
+        let ct = headers.content_type().expect("content type should parse");
+        assert_eq!(ct.media_type, "application");
+        assert_eq!(ct.subtype, "json");
+        assert!(ct.params.is_empty());
+    }
+
+    #[test]
+    fn test_content_type_with_charset() {
+        let mut headers = Headers::new();
+        headers.parse(b"Content-Type: Text/HTML; Charset=UTF-8\r\n");
+
+        let ct = headers.content_type().expect("content type should parse");
+        assert_eq!(ct.essence(), "text/html");
+        assert_eq!(ct.params.get("charset"), Some(&"UTF-8".to_string()));
+    }
+
+    #[test]
+    fn test_content_type_with_quoted_boundary() {
+        let mut headers = Headers::new();
+        headers.parse(b"Content-Type: multipart/form-data; boundary=\"a;bc\"\r\n");
+
+        let ct = headers.content_type().expect("content type should parse");
+        assert_eq!(ct.essence(), "multipart/form-data");
+        assert_eq!(ct.params.get("boundary"), Some(&"a;bc".to_string()));
+    }
+
+    #[test]
+    fn test_bare_lf_header_in_lenient_mode() {
+        let mut headers = Headers::new();
+        let config = RequestParserConfig::builder().strict(false).build();
+        let (n, done, err) = headers.parse_with_config(b"Host: localhost\n\n", &config);
+
+        assert!(err.is_none());
+        assert_eq!(headers.get("Host"), Some(&"localhost".to_string()));
+        assert_eq!(n, 16);
+        assert!(!done);
+
+        let (n, done, err) = headers.parse_with_config(b"\n", &config);
+        assert!(err.is_none());
+        assert_eq!(n, 1);
         assert!(done);
     }
+
+    #[test]
+    fn test_bare_lf_header_in_strict_mode() {
+        let mut headers = Headers::new();
+        let config = RequestParserConfig::builder().strict(true).build();
+        let (n, done, err) = headers.parse_with_config(b"Host: localhost\n\n", &config);
+
+        assert!(err.is_some());
+        assert_eq!(n, 0);
+        assert!(!done);
+    }
+
+    #[test]
+    fn test_merge_policy_comma_list() {
+        let config = RequestParserConfig::default();
+        let (headers, _) =
+            Headers::parse_all_with_config(b"Accept: a\r\nAccept: b\r\n\r\n", &config).unwrap();
+
+        assert_eq!(headers.get("Accept"), Some(&"a, b".to_string()));
+    }
+
+    #[test]
+    fn test_merge_policy_replace() {
+        let config = RequestParserConfig::builder()
+            .merge_policy("X-Mode", MergePolicy::Replace)
+            .build();
+        let (headers, _) =
+            Headers::parse_all_with_config(b"X-Mode: a\r\nX-Mode: b\r\n\r\n", &config).unwrap();
+
+        assert_eq!(headers.get("X-Mode"), Some(&"b".to_string()));
+    }
+
+    #[test]
+    fn test_merge_policy_reject() {
+        let config = RequestParserConfig::default();
+        let err = Headers::parse_all_with_config(b"Host: a\r\nHost: b\r\n\r\n", &config);
+
+        assert!(matches!(
+            err,
+            Err(RequestError::MalformedHeader { offset: 9, .. })
+        ));
+    }
+
+    #[test]
+    fn test_merge_policy_keep_all() {
+        let config = RequestParserConfig::default();
+        let (headers, _) = Headers::parse_all_with_config(
+            b"Set-Cookie: a=1; Expires=Wed, 21 Oct 2015 07:28:00 GMT\r\nSet-Cookie: b=2\r\n\r\n",
+            &config,
+        )
+        .unwrap();
+
+        assert_eq!(
+            headers.get_all("Set-Cookie"),
+            vec!["a=1; Expires=Wed, 21 Oct 2015 07:28:00 GMT", "b=2"]
+        );
+    }
+
+    #[test]
+    fn test_common_names_look_up_in_any_case() {
+        let data = "GET / HTTP/1.1\r\nHOST: a\r\nUser-Agent: b\r\nX-Custom: c\r\n\r\n";
+        let req = request_from_reader_with_config(data.as_bytes(), RequestParserConfig::default())
+            .unwrap();
+
+        for (name, value) in [("Host", "a"), ("user-agent", "b"), ("x-CUSTOM", "c")] {
+            assert_eq!(req.headers.get(name).map(String::as_str), Some(value));
+        }
+        assert_eq!(req.headers.names(), vec!["host", "user-agent", "x-custom"]);
+
+        let mut fields: Vec<_> = req.headers.iter().collect();
+        fields.sort_unstable();
+        assert_eq!(
+            fields,
+            [("host", "a"), ("user-agent", "b"), ("x-custom", "c")]
+        );
+    }
+
+    #[test]
+    fn test_allowed_header_names() {
+        let data = "GET / HTTP/1.1\r\nHost: localhost\r\nX-Evil: 1\r\n\r\n";
+        let builder = RequestParserConfig::builder().allowed_header_names(["HOST", "accept"]);
+
+        let err =
+            request_from_reader_with_config(data.as_bytes(), builder.clone().build()).unwrap_err();
+        assert!(matches!(
+            err,
+            RequestError::MalformedHeader { ref reason, .. } if reason == "Header not allowed: X-Evil"
+        ));
+
+        let config = builder.unknown_headers(UnknownHeaderPolicy::Drop).build();
+        let req = request_from_reader_with_config(data.as_bytes(), config).unwrap();
+        assert_eq!(req.headers.names(), vec!["host"]);
+    }
+
+    #[test]
+    fn test_allowed_header_names_keep_framing() {
+        let smuggled = "GET /admin HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let data = format!(
+            "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{smuggled}",
+            smuggled.len()
+        );
+        let config = RequestParserConfig::builder()
+            .allowed_header_names(["host"])
+            .unknown_headers(UnknownHeaderPolicy::Drop)
+            .build();
+
+        let mut reader = RequestReader::with_config(data.as_bytes(), config);
+        let req = reader.next_request().unwrap().unwrap();
+        assert_eq!(req.body, smuggled.as_bytes());
+        assert!(reader.next_request().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_merged_value_cap() {
+        let config = RequestParserConfig::builder()
+            .max_merged_value_len(64)
+            .build();
+        let mut headers = Headers::new();
+        let line = b"Accept: text/html\r\n";
+
+        let mut err = None;
+        for _ in 0..10 {
+            let (n, _, e) = headers.parse_with_config(line, &config);
+            if e.is_some() {
+                assert_eq!(n, 0);
+                err = e;
+                break;
+            }
+        }
+
+        assert_eq!(
+            err.as_deref(),
+            Some("Header value too long after merging: Accept")
+        );
+        assert!(headers.get("accept").unwrap().len() <= 64);
+    }
+
+    #[test]
+    fn test_if_range_etag_match_serves_range() {
+        let mut headers = Headers::new();
+        headers.set("If-Range", "\"v2\"");
+
+        assert_eq!(
+            headers.if_range(),
+            Some(IfRange::ETag("\"v2\"".to_string()))
+        );
+        assert!(headers.range_applies(Some("\"v2\""), None));
+        assert!(!headers.range_applies(Some("\"v3\""), None));
+
+        headers.set("If-Range", "W/\"v2\"");
+        assert!(!headers.range_applies(Some("W/\"v2\""), None));
+
+        headers.set("If-Range", "yesterday");
+        assert!(!headers.range_applies(Some("\"v2\""), None));
+
+        headers.remove("If-Range");
+        assert!(headers.range_applies(None, None));
+    }
+
+    #[test]
+    fn test_if_range_date_mismatch_serves_full_resource() {
+        let mut headers = Headers::new();
+        headers.set("If-Range", "Sun, 06 Nov 1994 08:49:37 GMT");
+        let date = UNIX_EPOCH + Duration::from_secs(784_111_777);
+
+        assert_eq!(headers.if_range(), Some(IfRange::Date(date)));
+        assert!(!headers.range_applies(None, Some(date + Duration::from_secs(60))));
+        assert!(headers.range_applies(None, Some(date + Duration::from_millis(250))));
+    }
+
+    #[test]
+    fn test_byte_range() {
+        let resolve = |value: &str| ByteRange::parse(value).and_then(|range| range.resolve(10));
+        assert_eq!(resolve("bytes=2-5"), Some((2, 5)));
+        assert_eq!(resolve("bytes=2-"), Some((2, 9)));
+        assert_eq!(resolve("bytes=8-20"), Some((8, 9)));
+        assert_eq!(resolve("bytes=-3"), Some((7, 9)));
+        assert_eq!(resolve("bytes=-30"), Some((0, 9)));
+        assert_eq!(resolve("bytes=10-"), None);
+        assert_eq!(resolve("bytes=-0"), None);
+
+        for invalid in [
+            "bytes=5-1",
+            "bytes=0-1,4-5",
+            "items=0-1",
+            "bytes=-",
+            "bytes=+1-2",
+        ] {
+            assert_eq!(ByteRange::parse(invalid), None, "{invalid}");
+        }
+        assert_eq!(
+            ContentRange::Unsatisfiable { total: 10 }.to_string(),
+            "bytes */10"
+        );
+    }
+
+    #[test]
+    fn test_content_range() {
+        let mut headers = Headers::new();
+        headers.set("Content-Range", "bytes 0-499/1234");
+        assert_eq!(
+            headers.content_range(),
+            Some(ContentRange::Bytes {
+                start: 0,
+                end: 499,
+                total: Some(1234)
+            })
+        );
+
+        headers.set("Content-Range", "bytes 500-999/*");
+        assert_eq!(
+            headers.content_range(),
+            Some(ContentRange::Bytes {
+                start: 500,
+                end: 999,
+                total: None
+            })
+        );
+    }
+
+    #[test]
+    fn test_unsatisfiable_content_range() {
+        let mut headers = Headers::new();
+        headers.set("Content-Range", "bytes */1234");
+        assert_eq!(
+            headers.content_range(),
+            Some(ContentRange::Unsatisfiable { total: 1234 })
+        );
+
+        for invalid in [
+            "bytes */*",
+            "bytes 5-1/10",
+            "bytes 0-10/10",
+            "items 0-1/2",
+            "bytes +0-1/2",
+        ] {
+            assert_eq!(ContentRange::parse(invalid), None, "{invalid}");
+        }
+    }
 }
@@ -1,6 +1,68 @@
+mod auth;
+pub mod authority;
+pub mod cache;
 pub mod chunk_reader;
+mod chunked;
+mod coding;
+pub mod config;
+pub mod cors;
+pub mod duplex_stream;
+pub mod error;
+pub mod file;
+mod forwarded;
 pub mod headers;
+pub mod httpdate;
+mod line;
+pub mod method;
+pub mod metrics;
+pub mod multipart;
+mod negotiate;
+pub mod origin;
+mod path;
+pub mod percent;
+pub mod pool;
+mod query;
 pub mod request;
+pub mod request_builder;
+pub mod response;
+pub mod router;
+mod security;
+pub mod server;
+pub mod sse;
+mod upgrade;
+mod websocket;
 
-pub use headers::Headers;
-pub use request::{Request, RequestLine, request_from_reader};
+pub use authority::Authority;
+pub use cache::ResponseCache;
+pub use chunked::ChunkExtension;
+pub use config::{
+    ChunkExtensionPolicy, InvalidUtf8Policy, MergePolicy, RequestParserConfig,
+    RequestParserConfigBuilder, UnknownHeaderPolicy,
+};
+pub use cors::Cors;
+pub use error::RequestError;
+pub use file::{FileResponse, send_file, serve_static};
+pub use headers::{ByteRange, CacheControl, ContentRange, ContentType, Headers, IfRange};
+pub use httpdate::{format_http_date, parse_http_date};
+pub use line::{LineError, LineReader};
+pub use method::Method;
+pub use metrics::BodyMetrics;
+pub use multipart::{MultipartReader, Part};
+pub use origin::Origin;
+pub use path::canonical_request_path;
+pub use percent::{EncodeSet, percent_encode};
+pub use pool::{ConnectionPool, PooledConnection};
+pub use request::{
+    BodyReader, Request, RequestHead, RequestLine, RequestReader, parse_all_requests,
+    parse_request, request_from_reader, request_from_reader_with_config, request_head_from_lines,
+    request_head_from_reader,
+};
+pub use request_builder::RequestBuilder;
+pub use response::{LineEnding, Response, ResponseLimits, StatusCode};
+pub use router::{BoxError, ErrorHandler, FallibleHandler, Handler, Router, VirtualHosts};
+pub use security::{ReferrerPolicy, XFrameOptions};
+#[cfg(feature = "socket-options")]
+pub use server::SocketOptions;
+pub use server::{Server, ServerConfig, serve_connection};
+pub use sse::SseEvent;
+pub use upgrade::Upgraded;
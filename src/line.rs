@@ -0,0 +1,259 @@
+use std::{
+    fmt,
+    io::{self, Read},
+};
+
+const READ_CHUNK_SIZE: usize = 1024;
+
+/// Finds the end of the first line in `data`.
+///
+/// Returns `(line_len, consumed)` where `consumed` includes the terminator,
+/// or `None` if no complete line is buffered yet. Lenient mode also accepts a
+/// bare `\n`; strict mode requires `\r\n` and rejects a bare `\n` outright
+/// rather than waiting for a CRLF that will never come. Strict mode also
+/// rejects a CR not followed by LF, which other parsers may take as a line
+/// break of its own.
+pub(crate) fn find_line_end(
+    data: &[u8],
+    strict: bool,
+) -> Result<Option<(usize, usize)>, &'static str> {
+    let lf = data.iter().position(|&b| b == b'\n');
+
+    if strict {
+        let line = &data[..lf.unwrap_or(data.len())];
+        let bare_cr = line
+            .iter()
+            .enumerate()
+            .any(|(i, &b)| b == b'\r' && data.get(i + 1).is_some_and(|&next| next != b'\n'));
+        if bare_cr {
+            return Err("bare CR in line");
+        }
+    }
+
+    let Some(lf) = lf else {
+        return Ok(None);
+    };
+
+    let has_cr = lf > 0 && data[lf - 1] == b'\r';
+    if has_cr {
+        return Ok(Some((lf - 1, lf + 1)));
+    }
+
+    if strict {
+        return Err("bare LF line ending");
+    }
+
+    Ok(Some((lf, lf + 1)))
+}
+
+/// Why [`LineReader`] couldn't produce another line.
+#[derive(Debug)]
+pub enum LineError {
+    TooLong { limit: usize },
+    Io(io::Error),
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LineError::TooLong { limit } => write!(f, "line exceeds {limit} bytes"),
+            LineError::Io(err) => write!(f, "error reading lines: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for LineError {}
+
+/// Reads `\r\n`- or `\n`-terminated lines from a stream as they arrive.
+///
+/// A line longer than `max_line_len` (excluding the terminator) is an error
+/// as soon as that many bytes are buffered, so a line that never ends can't
+/// grow the buffer forever. Bytes read past the last line stay buffered and
+/// are served first by the [`Read`] impl, e.g. for a body after a header
+/// section.
+pub struct LineReader<R> {
+    reader: R,
+    buf: Vec<u8>,
+    max_line_len: usize,
+}
+
+impl<R: Read> LineReader<R> {
+    pub fn new(reader: R, max_line_len: usize) -> Self {
+        Self {
+            reader,
+            buf: Vec::with_capacity(READ_CHUNK_SIZE),
+            max_line_len,
+        }
+    }
+
+    /// The next line without its terminator, or `None` at EOF. A final line
+    /// without a terminator is still returned.
+    pub fn next_line(&mut self) -> Result<Option<Vec<u8>>, LineError> {
+        let Some(mut line) = self.next_raw_line()? else {
+            return Ok(None);
+        };
+
+        if line.last() == Some(&b'\n') {
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+        }
+
+        Ok(Some(line))
+    }
+
+    /// Like [`LineReader::next_line`], but keeps the terminator exactly as
+    /// received so a strict parser can still tell `\r\n` from a bare `\n`.
+    pub(crate) fn next_raw_line(&mut self) -> Result<Option<Vec<u8>>, LineError> {
+        let mut searched = 0;
+
+        loop {
+            if let Some(i) = self.buf[searched..].iter().position(|&b| b == b'\n') {
+                let end = searched + i;
+                let len = if end > 0 && self.buf[end - 1] == b'\r' {
+                    end - 1
+                } else {
+                    end
+                };
+                if len > self.max_line_len {
+                    return Err(self.too_long());
+                }
+
+                return Ok(Some(self.buf.drain(..=end).collect()));
+            }
+
+            // A trailing CR may still turn out to be part of the terminator.
+            let pending = self.buf.len() - usize::from(self.buf.last() == Some(&b'\r'));
+            if pending > self.max_line_len {
+                return Err(self.too_long());
+            }
+            searched = self.buf.len();
+
+            if self.read_more()? == 0 {
+                if self.buf.is_empty() {
+                    return Ok(None);
+                }
+
+                return Ok(Some(std::mem::take(&mut self.buf)));
+            }
+        }
+    }
+
+    /// The underlying stream and the bytes read from it but not yet returned.
+    pub fn into_parts(self) -> (R, Vec<u8>) {
+        (self.reader, self.buf)
+    }
+
+    fn too_long(&self) -> LineError {
+        LineError::TooLong {
+            limit: self.max_line_len,
+        }
+    }
+
+    fn read_more(&mut self) -> Result<usize, LineError> {
+        let mut tmp = [0u8; READ_CHUNK_SIZE];
+
+        loop {
+            match self.reader.read(&mut tmp) {
+                Ok(n) => {
+                    self.buf.extend_from_slice(&tmp[..n]);
+                    return Ok(n);
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(LineError::Io(err)),
+            }
+        }
+    }
+}
+
+/// Serves bytes already buffered past the last line before reading from the
+/// underlying stream.
+impl<R: Read> Read for LineReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.buf.is_empty() {
+            return self.reader.read(buf);
+        }
+
+        let n = self.buf.len().min(buf.len());
+        buf[..n].copy_from_slice(&self.buf[..n]);
+        self.buf.drain(..n);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use crate::{
+        chunk_reader::ChunkReader,
+        line::{LineError, LineReader, find_line_end},
+    };
+
+    fn lines(input: &str, chunk_size: usize) -> Vec<Vec<u8>> {
+        let mut reader = LineReader::new(ChunkReader::new(input, chunk_size), 64);
+        std::iter::from_fn(|| reader.next_line().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_line_reader_multiple_lines() {
+        assert_eq!(
+            lines("first\r\nsecond\nthird", 64),
+            vec![b"first".to_vec(), b"second".to_vec(), b"third".to_vec()]
+        );
+    }
+
+    #[test]
+    fn test_line_reader_line_split_across_reads() {
+        assert_eq!(
+            lines("a longer line\r\nnext\r\n", 3),
+            vec![b"a longer line".to_vec(), b"next".to_vec()]
+        );
+    }
+
+    #[test]
+    fn test_line_reader_max_line_len() {
+        let mut reader = LineReader::new(ChunkReader::new("12345678\r\n123456789\r\n", 2), 8);
+
+        assert_eq!(reader.next_line().unwrap().unwrap(), b"12345678");
+        assert!(matches!(
+            reader.next_line(),
+            Err(LineError::TooLong { limit: 8 })
+        ));
+    }
+
+    #[test]
+    fn test_line_reader_keeps_rest_for_read() {
+        let mut reader = LineReader::new(ChunkReader::new("head\r\nbody bytes", 64), 64);
+        assert_eq!(reader.next_raw_line().unwrap().unwrap(), b"head\r\n");
+
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "body bytes");
+    }
+
+    #[test]
+    fn test_crlf_in_both_modes() {
+        assert_eq!(find_line_end(b"abc\r\nrest", true), Ok(Some((3, 5))));
+        assert_eq!(find_line_end(b"abc\r\nrest", false), Ok(Some((3, 5))));
+    }
+
+    #[test]
+    fn test_bare_lf() {
+        assert_eq!(find_line_end(b"abc\nrest", false), Ok(Some((3, 4))));
+        assert!(find_line_end(b"abc\nrest", true).is_err());
+    }
+
+    #[test]
+    fn test_incomplete_line() {
+        assert_eq!(find_line_end(b"abc\r", true), Ok(None));
+    }
+
+    #[test]
+    fn test_bare_cr() {
+        assert_eq!(find_line_end(b"a\rb\r\n", true), Err("bare CR in line"));
+        assert_eq!(find_line_end(b"a\rb", true), Err("bare CR in line"));
+        assert_eq!(find_line_end(b"a\rb\r\n", false), Ok(Some((3, 5))));
+    }
+}
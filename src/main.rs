@@ -1,3 +1,95 @@
-fn main() {
-    println!("Hello World!")
+use std::{
+    fs::File,
+    io::{self, Read},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use rust_http::{LineError, LineReader};
+
+const MAX_LINE_LEN: usize = 1024;
+
+fn main() -> io::Result<()> {
+    let file = File::open("messages.txt")?;
+
+    for line in get_lines_channel(file, MAX_LINE_LEN) {
+        match line {
+            Ok(line) => println!("read: {line}"),
+            Err(err) => eprintln!("{err}"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Streams `r` line by line over a channel.
+///
+/// A final line without a trailing newline is still delivered at EOF. A line
+/// longer than `max_line_len` (excluding the newline) is reported as
+/// [`LineError::TooLong`] and ends the stream, as do read errors. The reader
+/// thread stops quietly if the receiver is dropped.
+fn get_lines_channel<R: Read + Send + 'static>(
+    r: R,
+    max_line_len: usize,
+) -> Receiver<Result<String, LineError>> {
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let mut lines = LineReader::new(r, max_line_len);
+
+        loop {
+            let line = match lines.next_line() {
+                Ok(Some(line)) => Ok(String::from_utf8_lossy(&line).into_owned()),
+                Ok(None) => return,
+                Err(err) => Err(err),
+            };
+            let failed = line.is_err();
+
+            if tx.send(line).is_err() || failed {
+                return;
+            }
+        }
+    });
+
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_http::{LineError, chunk_reader::ChunkReader};
+
+    use crate::get_lines_channel;
+
+    fn collect_lines(input: &str, chunk_size: usize, max_line_len: usize) -> Vec<String> {
+        get_lines_channel(ChunkReader::new(input, chunk_size), max_line_len)
+            .iter()
+            .map(|line| line.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_lines_split_across_reads() {
+        let lines = collect_lines("first line\nsecond\nthird one\n", 3, 64);
+
+        assert_eq!(lines, vec!["first line", "second", "third one"]);
+    }
+
+    #[test]
+    fn test_last_line_without_newline_is_delivered() {
+        let lines = collect_lines("one\ntwo", 8, 64);
+
+        assert_eq!(lines, vec!["one", "two"]);
+    }
+
+    #[test]
+    fn test_over_long_line_is_reported() {
+        let input = format!("short\n{}\nnever read\n", "x".repeat(100));
+        let results: Vec<_> = get_lines_channel(ChunkReader::new(&input, 8), 16)
+            .iter()
+            .collect();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_deref().unwrap(), "short");
+        assert!(matches!(results[1], Err(LineError::TooLong { limit: 16 })));
+    }
 }
@@ -0,0 +1,113 @@
+use std::{
+    iter,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use crate::{request::Request, response::Response};
+
+/// Counts request and response body bytes, applied as a middleware around a
+/// handler with [`BodyMetrics::wrap`].
+///
+/// Request bodies are counted as the handler sees them, after transfer
+/// decoding. Streamed response bodies are counted chunk by chunk as they are
+/// sent, so the counter catches up once the stream ends.
+#[derive(Debug, Clone)]
+pub struct BodyMetrics {
+    request_bytes: Arc<AtomicU64>,
+    response_bytes: Arc<AtomicU64>,
+}
+
+impl BodyMetrics {
+    pub fn new(request_bytes: Arc<AtomicU64>, response_bytes: Arc<AtomicU64>) -> Self {
+        Self {
+            request_bytes,
+            response_bytes,
+        }
+    }
+
+    pub fn wrap<H>(self, handler: H) -> impl Fn(&Request) -> Response + Send + Sync
+    where
+        H: Fn(&Request) -> Response + Send + Sync,
+    {
+        move |req| self.handle(req, &handler)
+    }
+
+    fn handle<H>(&self, req: &Request, handler: &H) -> Response
+    where
+        H: Fn(&Request) -> Response,
+    {
+        self.request_bytes
+            .fetch_add(req.body.len() as u64, Ordering::Relaxed);
+
+        let mut res = handler(req);
+        let Some(stream) = res.take_body_stream() else {
+            self.response_bytes
+                .fetch_add(res.body_len(), Ordering::Relaxed);
+            return res;
+        };
+
+        let response_bytes = Arc::clone(&self.response_bytes);
+        let chunks = iter::from_fn(move || {
+            let chunk = stream
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .next()?;
+            response_bytes.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+            Some(chunk)
+        });
+
+        res.with_body_iter(chunks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+        mpsc,
+    };
+
+    use crate::{
+        metrics::BodyMetrics,
+        request::parse_request,
+        response::{Response, StatusCode},
+    };
+
+    #[test]
+    fn test_counts_request_and_response_bodies() {
+        let request_bytes = Arc::new(AtomicU64::new(0));
+        let response_bytes = Arc::new(AtomicU64::new(0));
+        let handler = BodyMetrics::new(Arc::clone(&request_bytes), Arc::clone(&response_bytes))
+            .wrap(|req| Response::new(StatusCode::Ok).with_body(req.body.repeat(2)));
+
+        let req = parse_request(b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello").unwrap();
+        let res = handler(&req);
+        res.write_to(&mut Vec::new()).unwrap();
+
+        assert_eq!(request_bytes.load(Ordering::Relaxed), 5);
+        assert_eq!(response_bytes.load(Ordering::Relaxed), 10);
+    }
+
+    #[test]
+    fn test_counts_streamed_response_body() {
+        let response_bytes = Arc::new(AtomicU64::new(0));
+        let handler = BodyMetrics::new(Arc::default(), Arc::clone(&response_bytes)).wrap(|_| {
+            let (tx, rx) = mpsc::channel();
+            for chunk in ["abc", "defg"] {
+                tx.send(chunk.as_bytes().to_vec()).unwrap();
+            }
+            Response::new(StatusCode::Ok).with_body_stream(rx)
+        });
+
+        let req = parse_request(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        let mut out = Vec::new();
+        handler(&req).write_to(&mut out).unwrap();
+
+        assert!(out.ends_with(b"3\r\nabc\r\n4\r\ndefg\r\n0\r\n\r\n"));
+        assert_eq!(response_bytes.load(Ordering::Relaxed), 7);
+    }
+}
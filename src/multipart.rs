@@ -0,0 +1,367 @@
+use std::io::{self, Read};
+
+use crate::{
+    headers::{ContentType, Headers, split_unquoted, unquote},
+    request::Request,
+};
+
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+const MAX_PART_HEADER_BYTES: usize = 16 * 1024;
+
+impl Request {
+    /// The `boundary` parameter of a `multipart/*` Content-Type.
+    pub fn multipart_boundary(&self) -> Option<String> {
+        let content_type = self.headers.content_type()?;
+        if content_type.media_type != "multipart" {
+            return None;
+        }
+
+        content_type.params.get("boundary").cloned()
+    }
+}
+
+enum State {
+    Preamble,
+    Body,
+    PartEnd,
+    Done,
+}
+
+/// Reads a `multipart/*` body part by part as bytes arrive.
+///
+/// Only the part headers and a window about the size of one read are held in
+/// memory, so part contents of any size can be streamed through. Pair it with
+/// [`RequestReader::next_request_head`](crate::RequestReader::next_request_head)
+/// to read an upload straight off the connection.
+pub struct MultipartReader<R> {
+    reader: R,
+    /// `CRLF--boundary`, the CRLF belonging to the delimiter rather than to
+    /// the preceding part's content.
+    delimiter: Vec<u8>,
+    buf: Vec<u8>,
+    state: State,
+}
+
+/// One part of a multipart body. Reading it yields the part's content.
+pub struct Part<'a, R> {
+    pub headers: Headers,
+    reader: &'a mut MultipartReader<R>,
+}
+
+impl<R: Read> MultipartReader<R> {
+    pub fn new(reader: R, boundary: &str) -> Self {
+        Self {
+            reader,
+            delimiter: format!("\r\n--{boundary}").into_bytes(),
+            // Lets the first boundary match the delimiter like every other.
+            buf: b"\r\n".to_vec(),
+            state: State::Preamble,
+        }
+    }
+
+    /// Advances to the next part, skipping any unread content of the
+    /// current one. Returns `None` after the closing boundary.
+    pub fn next_part(&mut self) -> io::Result<Option<Part<'_, R>>> {
+        let mut scratch = [0u8; 1024];
+        while matches!(self.state, State::Body) {
+            self.read_body(&mut scratch)?;
+        }
+
+        if matches!(self.state, State::Preamble) {
+            self.skip_preamble()?;
+        }
+
+        if matches!(self.state, State::Done) {
+            return Ok(None);
+        }
+
+        let end = self.delimiter.len();
+        self.fill_to(end + 2)?;
+        match &self.buf[end..end + 2] {
+            b"--" => {
+                self.state = State::Done;
+                return Ok(None);
+            }
+            b"\r\n" => {}
+            _ => return Err(invalid_data("malformed multipart boundary")),
+        }
+        self.buf.drain(..end + 2);
+
+        let headers = self.read_part_headers()?;
+        self.state = State::Body;
+
+        Ok(Some(Part {
+            headers,
+            reader: self,
+        }))
+    }
+
+    fn skip_preamble(&mut self) -> io::Result<()> {
+        loop {
+            if let Some(i) = find(&self.buf, &self.delimiter) {
+                self.buf.drain(..i);
+                self.state = State::PartEnd;
+                return Ok(());
+            }
+
+            let keep = self.delimiter.len() - 1;
+            if self.buf.len() > keep {
+                self.buf.drain(..self.buf.len() - keep);
+            }
+            self.fill_or_eof()?;
+        }
+    }
+
+    fn read_part_headers(&mut self) -> io::Result<Headers> {
+        let mut headers = Headers::new();
+        let mut parsed = 0;
+
+        loop {
+            let (n, done, err) = headers.parse(&self.buf);
+            if let Some(err) = err {
+                return Err(invalid_data(err));
+            }
+
+            self.buf.drain(..n);
+            parsed += n;
+            if done {
+                return Ok(headers);
+            }
+
+            if n == 0 {
+                if parsed + self.buf.len() > MAX_PART_HEADER_BYTES {
+                    return Err(invalid_data("multipart part headers too large"));
+                }
+                self.fill_or_eof()?;
+            }
+        }
+    }
+
+    fn read_body(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if !matches!(self.state, State::Body) || out.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            // Bytes that might begin the delimiter stay buffered until the
+            // next read settles whether they do.
+            let available = match find(&self.buf, &self.delimiter) {
+                Some(0) => {
+                    self.state = State::PartEnd;
+                    return Ok(0);
+                }
+                Some(i) => i,
+                None => self.buf.len().saturating_sub(self.delimiter.len() - 1),
+            };
+
+            if available > 0 {
+                let n = available.min(out.len());
+                out[..n].copy_from_slice(&self.buf[..n]);
+                self.buf.drain(..n);
+                return Ok(n);
+            }
+
+            self.fill_or_eof()?;
+        }
+    }
+
+    fn fill_to(&mut self, len: usize) -> io::Result<()> {
+        while self.buf.len() < len {
+            self.fill_or_eof()?;
+        }
+        Ok(())
+    }
+
+    fn fill_or_eof(&mut self) -> io::Result<()> {
+        let mut chunk = [0u8; READ_CHUNK_SIZE];
+        let n = loop {
+            match self.reader.read(&mut chunk) {
+                Ok(n) => break n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        };
+
+        if n == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "multipart body ended before the closing boundary",
+            ));
+        }
+
+        self.buf.extend_from_slice(&chunk[..n]);
+        Ok(())
+    }
+}
+
+impl<R> Part<'_, R> {
+    /// The `name` parameter of the part's Content-Disposition.
+    pub fn name(&self) -> Option<String> {
+        self.disposition_param("name")
+    }
+
+    /// The `filename` parameter of the part's Content-Disposition.
+    pub fn filename(&self) -> Option<String> {
+        self.disposition_param("filename")
+    }
+
+    pub fn content_type(&self) -> Option<ContentType> {
+        self.headers.content_type()
+    }
+
+    fn disposition_param(&self, name: &str) -> Option<String> {
+        let value = self.headers.get("content-disposition")?;
+
+        split_unquoted(value, ';')
+            .into_iter()
+            .skip(1)
+            .find_map(|param| {
+                let (key, value) = param.split_once('=')?;
+                key.trim()
+                    .eq_ignore_ascii_case(name)
+                    .then(|| unquote(value.trim()))
+            })
+    }
+}
+
+impl<R: Read> Read for Part<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read_body(buf)
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn invalid_data(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor, Read};
+
+    use crate::{chunk_reader::ChunkReader, multipart::MultipartReader, request::RequestReader};
+
+    fn fnv1a(hash: u64, data: &[u8]) -> u64 {
+        data.iter()
+            .fold(hash, |h, &b| (h ^ b as u64).wrapping_mul(0x100000001b3))
+    }
+
+    const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+
+    #[test]
+    fn test_parts_split_across_small_reads() {
+        let body = concat!(
+            "preamble to ignore\r\n",
+            "--XyZ\r\n",
+            "Content-Disposition: form-data; name=\"title\"\r\n",
+            "\r\n",
+            "hello\r\n--XyQ is not the boundary\r\n",
+            "--XyZ\r\n",
+            "Content-Disposition: form-data; name=\"file\"; filename=\"a;b.txt\"\r\n",
+            "Content-Type: text/plain\r\n",
+            "\r\n",
+            "file contents\r\n",
+            "--XyZ--\r\n",
+        );
+        let mut multipart = MultipartReader::new(ChunkReader::new(body, 3), "XyZ");
+
+        let mut part = multipart.next_part().unwrap().expect("first part");
+        assert_eq!(part.name().as_deref(), Some("title"));
+        let mut content = String::new();
+        part.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "hello\r\n--XyQ is not the boundary");
+
+        let mut part = multipart.next_part().unwrap().expect("second part");
+        assert_eq!(part.name().as_deref(), Some("file"));
+        assert_eq!(part.filename().as_deref(), Some("a;b.txt"));
+        assert_eq!(part.content_type().unwrap().essence(), "text/plain");
+        let mut content = String::new();
+        part.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "file contents");
+
+        assert!(multipart.next_part().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_unread_part_is_skipped() {
+        let body = "--b\r\n\r\nskipped\r\n--b\r\nX-Id: 2\r\n\r\nkept\r\n--b--";
+        let mut multipart = MultipartReader::new(body.as_bytes(), "b");
+
+        multipart.next_part().unwrap().expect("first part");
+        let mut part = multipart.next_part().unwrap().expect("second part");
+        assert_eq!(part.headers.get("x-id"), Some(&"2".to_string()));
+        let mut content = String::new();
+        part.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "kept");
+    }
+
+    #[test]
+    fn test_missing_closing_boundary_is_an_error() {
+        let mut multipart = MultipartReader::new(&b"--b\r\n\r\ncut short"[..], "b");
+
+        let mut part = multipart.next_part().unwrap().expect("part");
+        let err = part.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_streams_large_upload_without_buffering_it() {
+        const SIZE: u64 = 4 * 1024 * 1024;
+
+        let part_head = concat!(
+            "--upload\r\n",
+            "Content-Disposition: form-data; name=\"blob\"\r\n",
+            "\r\n",
+        );
+        let tail = "\r\n--upload--\r\n";
+        let head = format!(
+            concat!(
+                "POST /upload HTTP/1.1\r\n",
+                "Host: localhost\r\n",
+                "Content-Type: multipart/form-data; boundary=upload\r\n",
+                "Content-Length: {}\r\n",
+                "\r\n",
+                "{}",
+            ),
+            part_head.len() as u64 + SIZE + tail.len() as u64,
+            part_head
+        );
+        let content = io::repeat(b'\r')
+            .take(SIZE / 2)
+            .chain(io::repeat(b'-').take(SIZE / 2));
+        let stream = Cursor::new(head).chain(content).chain(Cursor::new(tail));
+
+        let mut reader = RequestReader::new(stream);
+        let req = reader.next_request_head().unwrap().expect("request");
+        let boundary = req.multipart_boundary().expect("boundary");
+        let body_len = req.headers.get("content-length").unwrap().parse().unwrap();
+
+        let mut multipart = MultipartReader::new(reader.take(body_len), &boundary);
+        let mut part = multipart.next_part().unwrap().expect("part");
+        assert_eq!(part.name().as_deref(), Some("blob"));
+
+        let mut hash = FNV_OFFSET;
+        let mut len = 0;
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = part.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            hash = fnv1a(hash, &buf[..n]);
+            len += n as u64;
+        }
+
+        let expected = fnv1a(
+            fnv1a(FNV_OFFSET, &vec![b'\r'; SIZE as usize / 2]),
+            &vec![b'-'; SIZE as usize / 2],
+        );
+        assert_eq!(len, SIZE);
+        assert_eq!(hash, expected);
+        assert!(multipart.buf.capacity() < 64 * 1024);
+        assert!(multipart.next_part().unwrap().is_none());
+    }
+}
@@ -0,0 +1,138 @@
+use crate::{headers::split_unquoted, request::Request};
+
+/// One entry of an `Accept` header, e.g. `text/*;q=0.5`.
+struct MediaRange {
+    media_type: String,
+    subtype: String,
+    q: f32,
+}
+
+impl MediaRange {
+    fn parse(s: &str) -> Option<Self> {
+        let mut parts = split_unquoted(s, ';').into_iter();
+        let (media_type, subtype) = parts.next()?.trim().split_once('/')?;
+
+        let mut q = 1.0;
+        for param in parts {
+            if let Some((name, value)) = param.split_once('=')
+                && name.trim().eq_ignore_ascii_case("q")
+            {
+                q = value
+                    .trim()
+                    .parse()
+                    .ok()
+                    .filter(|q| (0.0..=1.0).contains(q))?;
+            }
+        }
+
+        Some(Self {
+            media_type: media_type.trim().to_lowercase(),
+            subtype: subtype.trim().to_lowercase(),
+            q,
+        })
+    }
+
+    /// How specifically this range matches `type/subtype`: 2 for an exact
+    /// match, 1 for `type/*`, 0 for `*/*`, `None` for no match.
+    fn specificity(&self, media_type: &str, subtype: &str) -> Option<u8> {
+        match (self.media_type.as_str(), self.subtype.as_str()) {
+            ("*", "*") => Some(0),
+            (t, "*") if t == media_type => Some(1),
+            (t, s) if t == media_type && s == subtype => Some(2),
+            _ => None,
+        }
+    }
+}
+
+impl Request {
+    /// Whether the client accepts `media_type`, per the most specific
+    /// matching range of its `Accept` header. A range with `q=0` is an
+    /// explicit refusal. Without an `Accept` header everything is accepted.
+    pub fn accepts(&self, media_type: &str) -> bool {
+        self.quality(media_type) > 0.0
+    }
+
+    /// Picks the offered media type the client prefers most, favouring the
+    /// earlier offer on ties. Returns `None` when every offer is refused.
+    pub fn preferred_media_type<'a>(&self, offered: &[&'a str]) -> Option<&'a str> {
+        let mut best: Option<(&str, f32)> = None;
+
+        for &media_type in offered {
+            let q = self.quality(media_type);
+            if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+                best = Some((media_type, q));
+            }
+        }
+
+        best.map(|(media_type, _)| media_type)
+    }
+
+    fn quality(&self, media_type: &str) -> f32 {
+        let Some(accept) = self.headers.get("accept") else {
+            return 1.0;
+        };
+
+        let essence = media_type.split(';').next().unwrap_or_default().trim();
+        let Some((t, s)) = essence.split_once('/') else {
+            return 0.0;
+        };
+        let (t, s) = (t.to_lowercase(), s.to_lowercase());
+
+        split_unquoted(accept, ',')
+            .into_iter()
+            .filter_map(MediaRange::parse)
+            .filter_map(|range| Some((range.specificity(&t, &s)?, range.q)))
+            .max_by_key(|(specificity, _)| *specificity)
+            .map_or(0.0, |(_, q)| q)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chunk_reader::ChunkReader,
+        request::{Request, request_from_reader},
+    };
+
+    fn request_accepting(accept: &str) -> Request {
+        let data = format!("GET / HTTP/1.1\r\nHost: localhost\r\nAccept: {accept}\r\n\r\n");
+        request_from_reader(ChunkReader::new(&data, 16)).unwrap()
+    }
+
+    #[test]
+    fn test_prefers_higher_quality() {
+        let req = request_accepting("text/html, application/json;q=0.9");
+
+        assert_eq!(
+            req.preferred_media_type(&["application/json", "text/html"]),
+            Some("text/html")
+        );
+        assert!(req.accepts("application/json"));
+        assert!(!req.accepts("image/png"));
+    }
+
+    #[test]
+    fn test_wildcards() {
+        let req = request_accepting("*/*");
+        assert!(req.accepts("image/png"));
+        assert_eq!(
+            req.preferred_media_type(&["application/json", "text/html"]),
+            Some("application/json")
+        );
+
+        let req = request_accepting("text/*;q=0.5, text/plain;q=1");
+        assert_eq!(
+            req.preferred_media_type(&["text/html", "text/plain"]),
+            Some("text/plain")
+        );
+    }
+
+    #[test]
+    fn test_zero_quality_refuses() {
+        let req = request_accepting("*/*, application/xml;q=0");
+
+        assert!(!req.accepts("application/xml"));
+        assert!(req.accepts("application/json"));
+        assert_eq!(req.preferred_media_type(&["application/xml"]), None);
+    }
+}
@@ -0,0 +1,154 @@
+use crate::{authority::Authority, request::Request};
+
+/// A parsed `Origin` header (RFC 6454).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    /// `Origin: null`, sent for opaque origins such as sandboxed iframes,
+    /// `file:` pages and some redirects. Anyone can produce it, so it should
+    /// never be trusted.
+    Null,
+    /// A scheme, host and optional port, e.g. `https://example.com:8443`. The
+    /// scheme and host are lowercased.
+    Tuple {
+        scheme: String,
+        authority: Authority,
+    },
+}
+
+impl Origin {
+    /// Parses `null` or `scheme://host[:port]`. Anything more, such as a
+    /// path or userinfo, isn't a valid origin.
+    pub fn parse(s: &str) -> Option<Self> {
+        if s == "null" {
+            return Some(Origin::Null);
+        }
+
+        let (scheme, rest) = s.split_once("://")?;
+        let valid_scheme = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'-' | b'.'));
+        if !valid_scheme || rest.contains(['/', '?', '#', '@']) {
+            return None;
+        }
+
+        Some(Origin::Tuple {
+            scheme: scheme.to_ascii_lowercase(),
+            authority: Authority::parse(rest)?,
+        })
+    }
+
+    /// Whether both are the same tuple origin, counting an omitted port as
+    /// the scheme's default. `null` is never the same as anything.
+    pub fn same_as(&self, other: &Origin) -> bool {
+        match (self, other) {
+            (
+                Origin::Tuple { scheme, authority },
+                Origin::Tuple {
+                    scheme: other_scheme,
+                    authority: other_authority,
+                },
+            ) => {
+                scheme == other_scheme
+                    && authority.host == other_authority.host
+                    && authority.port.or(default_port(scheme))
+                        == other_authority.port.or(default_port(other_scheme))
+            }
+            _ => false,
+        }
+    }
+}
+
+fn default_port(scheme: &str) -> Option<u16> {
+    match scheme {
+        "http" | "ws" => Some(80),
+        "https" | "wss" => Some(443),
+        _ => None,
+    }
+}
+
+impl Request {
+    /// The parsed `Origin` header; `None` when it is missing or malformed.
+    pub fn origin(&self) -> Option<Origin> {
+        Origin::parse(self.headers.get("origin")?.trim())
+    }
+
+    /// Whether `Origin` names one of `allowed` (e.g. `https://example.com`),
+    /// for rejecting cross-site state-changing requests. A missing,
+    /// malformed or `null` origin never matches, even if `allowed` lists
+    /// `null`.
+    pub fn origin_matches(&self, allowed: &[&str]) -> bool {
+        let Some(origin) = self.origin() else {
+            return false;
+        };
+
+        allowed
+            .iter()
+            .filter_map(|allowed| Origin::parse(allowed))
+            .any(|allowed| origin.same_as(&allowed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        authority::Authority,
+        chunk_reader::ChunkReader,
+        origin::Origin,
+        request::{Request, request_from_reader},
+    };
+
+    fn request(origin: &str) -> Request {
+        let data = format!("POST / HTTP/1.1\r\nHost: example.com\r\nOrigin: {origin}\r\n\r\n");
+        request_from_reader(ChunkReader::new(&data, 16)).unwrap()
+    }
+
+    #[test]
+    fn test_origin() {
+        let req = request("HTTPS://Example.com:8443");
+        assert_eq!(
+            req.origin(),
+            Some(Origin::Tuple {
+                scheme: "https".to_string(),
+                authority: Authority {
+                    host: "example.com".to_string(),
+                    port: Some(8443),
+                },
+            })
+        );
+        assert!(req.origin_matches(&["https://other.example", "https://example.com:8443"]));
+
+        let req = request("https://example.com");
+        assert!(req.origin_matches(&["https://example.com:443"]));
+    }
+
+    #[test]
+    fn test_null_origin() {
+        let req = request("null");
+
+        assert_eq!(req.origin(), Some(Origin::Null));
+        assert!(!req.origin_matches(&["null", "https://example.com"]));
+    }
+
+    #[test]
+    fn test_origin_mismatch() {
+        let allowed = ["https://example.com"];
+
+        for origin in [
+            "http://example.com",
+            "https://example.com:8443",
+            "https://evil.example",
+            "https://example.com.evil.example",
+        ] {
+            assert!(!request(origin).origin_matches(&allowed), "{origin}");
+        }
+
+        for invalid in [
+            "https://example.com/",
+            "https://user@example.com",
+            "example.com",
+        ] {
+            assert_eq!(request(invalid).origin(), None, "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{error::RequestError, percent::percent_decode, request::RequestLine};
+
+/// The request's path, decoded and normalized for matching against routes.
+///
+/// - The query and fragment are dropped, as is the scheme and authority of
+///   an absolute-form target.
+/// - Each segment is percent-decoded and must be valid UTF-8 without control
+///   characters (NUL included).
+/// - `.` segments and empty segments from `//` are removed; `..` removes the
+///   segment before it but never climbs above `/`.
+/// - A trailing `/` is kept, since `/dir/` and `/dir` can be different routes.
+///
+/// An encoded slash (`%2F`) is rejected rather than decoded: decoding it
+/// would let `/a%2Fb` reach the `/a/b` route, and keeping it would give one
+/// path two spellings.
+pub fn canonical_request_path(line: &RequestLine) -> Result<String, RequestError> {
+    let target = &line.request_target;
+    let path = target.split(['?', '#']).next().unwrap_or_default();
+    let path = match path.split_once("://") {
+        Some((scheme, rest))
+            if scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https") =>
+        {
+            rest.find('/').map_or("/", |i| &rest[i..])
+        }
+        _ => path,
+    };
+
+    if !path.starts_with('/') {
+        return Err(RequestError::InvalidPath("path must start with /"));
+    }
+
+    let mut segments: Vec<String> = Vec::new();
+    for raw in path.split('/') {
+        let decoded =
+            percent_decode(raw).ok_or(RequestError::InvalidPath("malformed percent-encoding"))?;
+        if decoded.contains(&b'/') {
+            return Err(RequestError::InvalidPath("encoded slash"));
+        }
+        if decoded.iter().any(|b| b.is_ascii_control()) {
+            return Err(RequestError::InvalidPath("control character"));
+        }
+
+        let segment =
+            String::from_utf8(decoded).map_err(|_| RequestError::InvalidPath("invalid UTF-8"))?;
+        match segment.as_str() {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+
+    let mut canonical = format!("/{}", segments.join("/"));
+    if !segments.is_empty()
+        && (path.ends_with('/') || path.ends_with("/.") || path.ends_with("/.."))
+    {
+        canonical.push('/');
+    }
+
+    Ok(canonical)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chunk_reader::ChunkReader,
+        error::RequestError,
+        path::canonical_request_path,
+        request::{RequestLine, request_from_reader},
+    };
+
+    fn request_line(target: &str) -> RequestLine {
+        let data = format!("GET {target} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        request_from_reader(ChunkReader::new(&data, 16))
+            .unwrap()
+            .request_line
+            .unwrap()
+    }
+
+    fn canonical(target: &str) -> Result<String, RequestError> {
+        canonical_request_path(&request_line(target))
+    }
+
+    #[test]
+    fn test_normalizes_dot_segments_and_slashes() {
+        assert_eq!(canonical("/").unwrap(), "/");
+        assert_eq!(canonical("/a//b/./c?x=1#top").unwrap(), "/a/b/c");
+        assert_eq!(canonical("/a/b/../c/").unwrap(), "/a/c/");
+        assert_eq!(canonical("/a/%62%20c").unwrap(), "/a/b c");
+        assert_eq!(canonical("http://example.com/a/./b").unwrap(), "/a/b");
+        assert_eq!(canonical("http://example.com").unwrap(), "/");
+    }
+
+    #[test]
+    fn test_traversal_is_clamped_at_root() {
+        assert_eq!(canonical("/../../etc/passwd").unwrap(), "/etc/passwd");
+        assert_eq!(canonical("/a/%2e%2e/%2E%2E/b").unwrap(), "/b");
+        assert_eq!(canonical("/a/..").unwrap(), "/");
+    }
+
+    #[test]
+    fn test_encoded_slash_is_rejected() {
+        assert!(matches!(
+            canonical("/a%2Fb"),
+            Err(RequestError::InvalidPath("encoded slash"))
+        ));
+        assert!(matches!(
+            canonical("/a%2f..%2f..%2fb"),
+            Err(RequestError::InvalidPath("encoded slash"))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_control_bytes_and_bad_encoding() {
+        assert!(matches!(
+            canonical("/a%00b"),
+            Err(RequestError::InvalidPath("control character"))
+        ));
+        assert!(matches!(
+            canonical("/a%0Ab"),
+            Err(RequestError::InvalidPath("control character"))
+        ));
+        assert!(matches!(
+            canonical("/a%zz"),
+            Err(RequestError::InvalidPath("malformed percent-encoding"))
+        ));
+        assert!(matches!(
+            canonical("/%FF"),
+            Err(RequestError::InvalidPath("invalid UTF-8"))
+        ));
+        assert!(canonical("*").is_err());
+    }
+}
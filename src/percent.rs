@@ -0,0 +1,115 @@
+use std::fmt::Write;
+
+/// Which characters [`percent_encode`] may leave as they are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodeSet {
+    /// A single path segment: `/`, `?` and `#` are encoded so the input can't
+    /// add segments or end the path.
+    PathSegment,
+    /// A key or value inside a query string: `&`, `=`, `+` and `#` are
+    /// encoded so the input can't add parameters or end the query.
+    QueryComponent,
+}
+
+impl EncodeSet {
+    fn allows(self, b: u8) -> bool {
+        let unreserved = b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~');
+        let extra: &[u8] = match self {
+            EncodeSet::PathSegment => b"!$&'()*+,;=:@",
+            EncodeSet::QueryComponent => b"!$'()*,;:@/?",
+        };
+
+        unreserved || extra.contains(&b)
+    }
+}
+
+/// Percent-encodes the UTF-8 bytes of `input` that `set` doesn't allow, e.g.
+/// for building a `Location` header from user-supplied text. Control
+/// characters, spaces and `%` are always encoded, so the result can't break
+/// out of a header line or be decoded twice into something else.
+pub fn percent_encode(input: &str, set: EncodeSet) -> String {
+    let mut out = String::with_capacity(input.len());
+
+    for &b in input.as_bytes() {
+        if set.allows(b) {
+            out.push(b as char);
+        } else {
+            write!(out, "%{b:02X}").unwrap();
+        }
+    }
+
+    out
+}
+
+/// Decodes `%XX` escapes in `input`, leaving everything else as is. `None`
+/// if a `%` isn't followed by two hex digits.
+pub(crate) fn percent_decode(input: &str) -> Option<Vec<u8>> {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return None;
+            }
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::percent::{EncodeSet, percent_decode, percent_encode};
+
+    #[test]
+    fn test_encode_path_segment() {
+        let encode = |s| percent_encode(s, EncodeSet::PathSegment);
+
+        assert_eq!(encode("my file.txt"), "my%20file.txt");
+        assert_eq!(encode("café"), "caf%C3%A9");
+        assert_eq!(encode("../a/b?c#d"), "..%2Fa%2Fb%3Fc%23d");
+        assert_eq!(encode("a=b&c+d:e@f"), "a=b&c+d:e@f");
+        assert_eq!(encode("100%"), "100%25");
+    }
+
+    #[test]
+    fn test_encode_query_component() {
+        let encode = |s| percent_encode(s, EncodeSet::QueryComponent);
+
+        assert_eq!(encode("hello world"), "hello%20world");
+        assert_eq!(encode("日本"), "%E6%97%A5%E6%9C%AC");
+        assert_eq!(encode("a=b&c+d#e"), "a%3Db%26c%2Bd%23e");
+        assert_eq!(encode("/path?x"), "/path?x");
+    }
+
+    #[test]
+    fn test_encode_prevents_header_injection() {
+        for set in [EncodeSet::PathSegment, EncodeSet::QueryComponent] {
+            assert_eq!(
+                percent_encode("x\r\nSet-Cookie: a=1", set),
+                match set {
+                    EncodeSet::PathSegment => "x%0D%0ASet-Cookie:%20a=1",
+                    EncodeSet::QueryComponent => "x%0D%0ASet-Cookie:%20a%3D1",
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_round_trips() {
+        let input = "a b/ü?&=%";
+        let encoded = percent_encode(input, EncodeSet::QueryComponent);
+
+        assert_eq!(percent_decode(&encoded).unwrap(), input.as_bytes());
+        assert!(percent_decode("%zz").is_none());
+        assert!(percent_decode("%4").is_none());
+    }
+}
@@ -0,0 +1,253 @@
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+    net::TcpStream,
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Keep-alive connections for outbound requests, keyed by `host:port`.
+/// Clones share the same connections.
+#[derive(Debug, Clone)]
+pub struct ConnectionPool {
+    hosts: Arc<Mutex<HashMap<String, Host>>>,
+    max_per_host: usize,
+    idle_timeout: Duration,
+}
+
+#[derive(Debug, Default)]
+struct Host {
+    /// Connections waiting to be reused, most recently returned last.
+    idle: Vec<(TcpStream, Instant)>,
+    /// Connections open to the host, idle or handed out.
+    open: usize,
+}
+
+impl ConnectionPool {
+    /// A pool that keeps at most `max_per_host` connections open to each
+    /// host and closes those left idle for longer than `idle_timeout`.
+    pub fn new(max_per_host: usize, idle_timeout: Duration) -> Self {
+        Self {
+            hosts: Arc::default(),
+            max_per_host,
+            idle_timeout,
+        }
+    }
+
+    /// An idle connection to `addr` (`host:port`) if there is one, otherwise
+    /// a new one. Fails with `WouldBlock` when `max_per_host` connections to
+    /// `addr` are already handed out.
+    pub fn get(&self, addr: &str) -> io::Result<PooledConnection> {
+        {
+            let mut hosts = self.hosts.lock().unwrap_or_else(|err| err.into_inner());
+            let host = hosts.entry(addr.to_string()).or_default();
+
+            while let Some((stream, idle_since)) = host.idle.pop() {
+                if idle_since.elapsed() < self.idle_timeout {
+                    return Ok(self.hand_out(addr, stream));
+                }
+                host.open -= 1;
+            }
+
+            if host.open >= self.max_per_host {
+                return Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    format!("{} connections to {addr} already in use", host.open),
+                ));
+            }
+            host.open += 1;
+        }
+
+        match TcpStream::connect(addr) {
+            Ok(stream) => Ok(self.hand_out(addr, stream)),
+            Err(err) => {
+                self.release(addr);
+                Err(err)
+            }
+        }
+    }
+
+    /// Connections to `addr` waiting to be reused, expired ones included.
+    pub fn idle_count(&self, addr: &str) -> usize {
+        let hosts = self.hosts.lock().unwrap_or_else(|err| err.into_inner());
+        hosts.get(addr).map_or(0, |host| host.idle.len())
+    }
+
+    fn hand_out(&self, addr: &str, stream: TcpStream) -> PooledConnection {
+        PooledConnection {
+            stream: Some(stream),
+            addr: addr.to_string(),
+            pool: self.clone(),
+            reusable: true,
+        }
+    }
+
+    fn release(&self, addr: &str) {
+        let mut hosts = self.hosts.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(host) = hosts.get_mut(addr) {
+            host.open -= 1;
+        }
+    }
+}
+
+/// A connection handed out by a [`ConnectionPool`]. Goes back to the pool
+/// when dropped, unless [`PooledConnection::discard`] was called.
+#[derive(Debug)]
+pub struct PooledConnection {
+    stream: Option<TcpStream>,
+    addr: String,
+    pool: ConnectionPool,
+    reusable: bool,
+}
+
+impl PooledConnection {
+    /// Closes the connection instead of returning it to the pool, e.g. after
+    /// `Connection: close` or an incompletely read response.
+    pub fn discard(mut self) {
+        self.reusable = false;
+    }
+}
+
+impl Deref for PooledConnection {
+    type Target = TcpStream;
+
+    fn deref(&self) -> &TcpStream {
+        self.stream.as_ref().expect("stream taken before drop")
+    }
+}
+
+impl DerefMut for PooledConnection {
+    fn deref_mut(&mut self) -> &mut TcpStream {
+        self.stream.as_mut().expect("stream taken before drop")
+    }
+}
+
+impl Read for PooledConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.deref_mut().read(buf)
+    }
+}
+
+impl Write for PooledConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.deref_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.deref_mut().flush()
+    }
+}
+
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        let Some(stream) = self.stream.take() else {
+            return;
+        };
+
+        if !self.reusable {
+            self.pool.release(&self.addr);
+            return;
+        }
+
+        let mut hosts = self
+            .pool
+            .hosts
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let host = hosts.entry(self.addr.clone()).or_default();
+        host.idle.push((stream, Instant::now()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, BufRead, BufReader, Write},
+        net::TcpListener,
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        thread,
+        time::Duration,
+    };
+
+    use crate::pool::ConnectionPool;
+
+    /// A keep-alive server echoing each line back, counting the
+    /// connections it accepted.
+    fn echo_server() -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let accepted = Arc::new(AtomicUsize::new(0));
+
+        let counter = accepted.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = stream.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                thread::spawn(move || {
+                    let mut writer = stream.try_clone().unwrap();
+                    for line in BufReader::new(stream).lines() {
+                        let Ok(line) = line else { break };
+                        if writeln!(writer, "{line}").is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+
+        (addr, accepted)
+    }
+
+    fn round_trip(pool: &ConnectionPool, addr: &str, line: &str) -> String {
+        let mut conn = pool.get(addr).unwrap();
+        writeln!(conn, "{line}").unwrap();
+
+        let mut reply = String::new();
+        BufReader::new(&*conn).read_line(&mut reply).unwrap();
+        reply
+    }
+
+    #[test]
+    fn test_second_request_reuses_connection() {
+        let (addr, accepted) = echo_server();
+        let pool = ConnectionPool::new(4, Duration::from_secs(5));
+
+        assert_eq!(round_trip(&pool, &addr, "first"), "first\n");
+        assert_eq!(pool.idle_count(&addr), 1);
+        assert_eq!(round_trip(&pool, &addr, "second"), "second\n");
+
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+        assert_eq!(pool.idle_count(&addr), 1);
+    }
+
+    #[test]
+    fn test_idle_timeout_and_discard() {
+        let (addr, accepted) = echo_server();
+        let pool = ConnectionPool::new(4, Duration::from_millis(50));
+
+        round_trip(&pool, &addr, "a");
+        thread::sleep(Duration::from_millis(100));
+        round_trip(&pool, &addr, "b");
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+
+        pool.get(&addr).unwrap().discard();
+        assert_eq!(pool.idle_count(&addr), 0);
+    }
+
+    #[test]
+    fn test_max_per_host() {
+        let (addr, _) = echo_server();
+        let pool = ConnectionPool::new(1, Duration::from_secs(5));
+
+        let conn = pool.get(&addr).unwrap();
+        let err = pool.get(&addr).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+
+        drop(conn);
+        assert!(pool.get(&addr).is_ok());
+    }
+}
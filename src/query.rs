@@ -0,0 +1,93 @@
+use crate::request::RequestLine;
+
+#[cfg(feature = "query-serde")]
+use crate::error::RequestError;
+
+impl RequestLine {
+    /// The raw query string after `?`, without any fragment.
+    pub fn query(&self) -> Option<&str> {
+        let (_, query) = self.request_target.split_once('?')?;
+        Some(query.split('#').next().unwrap_or_default())
+    }
+
+    /// Deserializes the query string, e.g. `?page=2&size=10`, into `T`.
+    /// A missing query deserializes like an empty one.
+    #[cfg(feature = "query-serde")]
+    pub fn query_typed<T: serde::de::DeserializeOwned>(&self) -> Result<T, RequestError> {
+        serde_urlencoded::from_str(self.query().unwrap_or_default())
+            .map_err(|err| RequestError::InvalidQuery(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chunk_reader::ChunkReader,
+        request::{RequestLine, request_from_reader},
+    };
+
+    fn request_line(target: &str) -> RequestLine {
+        let data = format!("GET {target} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        request_from_reader(ChunkReader::new(&data, 16))
+            .unwrap()
+            .request_line
+            .unwrap()
+    }
+
+    #[test]
+    fn test_raw_query() {
+        assert_eq!(
+            request_line("/items?page=2&size=10").query(),
+            Some("page=2&size=10")
+        );
+        assert_eq!(request_line("/items?q=a#top").query(), Some("q=a"));
+        assert_eq!(request_line("/items").query(), None);
+    }
+
+    #[cfg(feature = "query-serde")]
+    #[test]
+    fn test_query_into_struct() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Paging {
+            page: u32,
+            #[serde(default = "default_size")]
+            size: u32,
+            sort: Option<String>,
+        }
+
+        fn default_size() -> u32 {
+            20
+        }
+
+        let paging: Paging = request_line("/items?page=2&size=10").query_typed().unwrap();
+        assert_eq!(
+            paging,
+            Paging {
+                page: 2,
+                size: 10,
+                sort: None
+            }
+        );
+
+        let paging: Paging = request_line("/items?page=3&sort=name%20asc")
+            .query_typed()
+            .unwrap();
+        assert_eq!(paging.size, 20);
+        assert_eq!(paging.sort.as_deref(), Some("name asc"));
+    }
+
+    #[cfg(feature = "query-serde")]
+    #[test]
+    fn test_invalid_query_is_an_error() {
+        #[derive(Debug, serde::Deserialize)]
+        struct Paging {
+            #[allow(dead_code)]
+            page: u32,
+        }
+
+        let err = request_line("/items?page=two")
+            .query_typed::<Paging>()
+            .unwrap_err();
+        assert!(matches!(err, crate::error::RequestError::InvalidQuery(_)));
+    }
+}
@@ -1,28 +1,97 @@
 #![allow(dead_code, unused_variables)]
 
-use std::io::{self, Error, Read};
+use std::{
+    collections::HashMap,
+    io::{self, Read},
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
 
-use crate::headers::Headers;
+use crate::{
+    chunked::{ChunkExtension, ChunkedDecoder},
+    coding,
+    config::{DEFAULT_MAX_BODY_SIZE, RequestParserConfig},
+    error::RequestError,
+    headers::{Headers, parse_digits},
+    line::{LineError, LineReader, find_line_end},
+    method::Method,
+};
 
-#[derive(Debug)]
+const READ_CHUNK_SIZE: usize = 1024;
+
+/// How many blank lines lenient parsing skips before a request line.
+const MAX_LEADING_EMPTY_LINES: usize = 4;
+
+#[derive(Debug, Clone)]
 pub struct RequestLine {
     pub http_version: String,
     pub request_target: String,
-    pub method: String,
+    pub method: Method,
+    raw: String,
+}
+
+impl RequestLine {
+    /// The request line exactly as the client sent it, without the CRLF.
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 enum RequestState {
     ParsingRequestLine,
     ParsingHeaders,
+    ParsingBody,
     Done,
 }
 
-#[derive(Debug)]
+/// How the end of the body is found, decided once the headers are in.
+#[derive(Debug, Clone)]
+enum BodyFraming {
+    ContentLength(usize),
+    Chunked(ChunkedDecoder),
+    /// Up to the configured `body_terminator`, which is consumed but not
+    /// kept.
+    Terminated {
+        terminator: Vec<u8>,
+        done: bool,
+    },
+}
+
+/// Cloning copies the headers and the whole body, so fanning one request out
+/// to several handlers costs one body-sized allocation per copy.
+#[derive(Debug, Clone)]
 pub struct Request {
     pub request_line: Option<RequestLine>,
     pub headers: Headers,
+    pub body: Vec<u8>,
+    /// The address of the socket the request arrived on, when known.
+    pub peer_addr: Option<SocketAddr>,
+    /// Whether the request arrived over TLS terminated by this server.
+    pub tls: bool,
+    /// Set by the [`Router`](crate::router::Router) before it calls the
+    /// handler.
+    params: HashMap<String, String>,
+    /// `max_body_size` of the config the request was parsed under, which
+    /// also caps [`Request::decoded_body`].
+    max_body_size: usize,
     state: RequestState,
+    offset: usize,
+    header_bytes: usize,
+    header_count: usize,
+    leading_empty_lines: usize,
+    framing: BodyFraming,
+    /// Transfer codings applied before `chunked`, in the order listed.
+    transfer_codings: Vec<String>,
+    /// When the parser first saw a byte of this request.
+    parse_started: Option<Instant>,
+    parse_duration: Option<Duration>,
+}
+
+impl Default for Request {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Request {
@@ -30,282 +99,1214 @@ impl Request {
         Self {
             request_line: None,
             headers: Headers::new(),
+            body: Vec::new(),
+            peer_addr: None,
+            tls: false,
+            params: HashMap::new(),
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
             state: RequestState::ParsingRequestLine,
+            offset: 0,
+            header_bytes: 0,
+            header_count: 0,
+            leading_empty_lines: 0,
+            framing: BodyFraming::ContentLength(0),
+            transfer_codings: Vec::new(),
+            parse_started: None,
+            parse_duration: None,
+        }
+    }
+
+    /// The path parameter `name` captured by the
+    /// [`Router`](crate::router::Router) route that matched, named without
+    /// its leading `:` or `*`.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map(String::as_str)
+    }
+
+    /// Every path parameter captured by the route that matched.
+    pub fn params(&self) -> &HashMap<String, String> {
+        &self.params
+    }
+
+    pub(crate) fn params_mut(&mut self) -> &mut HashMap<String, String> {
+        &mut self.params
+    }
+
+    /// Clears everything parsed so far, keeping the allocations of the
+    /// headers and body, so one `Request` can be reused with
+    /// [`RequestReader::next_request_into`] instead of allocating a new one
+    /// per request.
+    pub fn reset(&mut self) {
+        self.request_line = None;
+        self.headers.clear();
+        self.body.clear();
+        self.peer_addr = None;
+        self.tls = false;
+        self.params.clear();
+        self.max_body_size = DEFAULT_MAX_BODY_SIZE;
+        self.state = RequestState::ParsingRequestLine;
+        self.offset = 0;
+        self.header_bytes = 0;
+        self.header_count = 0;
+        self.leading_empty_lines = 0;
+        self.framing = BodyFraming::ContentLength(0);
+        self.transfer_codings.clear();
+        self.parse_started = None;
+        self.parse_duration = None;
+    }
+
+    /// Wall-clock time from the first byte of the request reaching the parser
+    /// until the request was complete, body included. Slow clients show up
+    /// here. `None` while the request is still being read.
+    pub fn parse_duration(&self) -> Option<Duration> {
+        self.parse_duration
+    }
+
+    fn record_parse_duration(&mut self) {
+        if let (RequestState::Done, Some(started), None) =
+            (&self.state, self.parse_started, self.parse_duration)
+        {
+            self.parse_duration = Some(started.elapsed());
+        }
+    }
+
+    /// Whether the client asked for the connection to be closed after this
+    /// request, either explicitly or by speaking HTTP/1.0.
+    pub fn wants_close(&self) -> bool {
+        let connection = self.headers.get("connection").map(|v| v.to_lowercase());
+        let http_version = self.request_line.as_ref().map(|l| l.http_version.as_str());
+
+        match connection.as_deref() {
+            Some("close") => true,
+            Some("keep-alive") => false,
+            _ => matches!(http_version, Some("1.0" | "0.9")),
+        }
+    }
+
+    /// Whether the whole body has been taken off the stream, buffered into
+    /// `body` or handed out by [`Request::read_body_with`] and
+    /// [`Request::drain_body`]. Until it has, the next request on the
+    /// connection can't be parsed, so it must be closed instead of reused.
+    pub fn body_fully_read(&self) -> bool {
+        matches!(self.state, RequestState::Done)
+    }
+
+    /// A lower bound on how many more bytes the request needs before it is
+    /// complete, for sizing reads when feeding the parser: what is left of a
+    /// `Content-Length` body or of the current chunk, `0` once complete.
+    /// `None` while parsing the head or between chunks, where it depends on
+    /// bytes not seen yet.
+    pub fn bytes_needed_hint(&self) -> Option<usize> {
+        match (&self.state, &self.framing) {
+            (RequestState::Done, _) => Some(0),
+            (RequestState::ParsingBody, BodyFraming::ContentLength(len)) => {
+                Some(len - self.body.len())
+            }
+            (RequestState::ParsingBody, BodyFraming::Chunked(decoder)) => decoder.data_remaining(),
+            _ => None,
+        }
+    }
+
+    /// The remaining proxy hop count from `Max-Forwards`, which limits how far
+    /// a `TRACE` or `OPTIONS` request is forwarded. A proxy forwarding the
+    /// request decrements it, and answers itself once it reaches zero. `None`
+    /// when absent, or when the value isn't a non-negative integer (an error
+    /// during parsing in strict mode).
+    pub fn max_forwards(&self) -> Option<u32> {
+        let value = self.headers.get("max-forwards")?.trim();
+        if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        value.parse().ok()
+    }
+
+    /// The headers as sorted `name:value` lines, each ending in `\n`, for
+    /// hashing or signing a request (as AWS SigV4 does). Names are lowercase;
+    /// values are trimmed with inner runs of whitespace collapsed to one
+    /// space, and values kept apart on separate lines are joined with `,` in
+    /// the order received. Header order and case don't change the result.
+    pub fn canonical_headers_string(&self) -> String {
+        let mut out = String::new();
+
+        for name in self.headers.names() {
+            let values: Vec<String> = self
+                .headers
+                .get_all(name)
+                .iter()
+                .map(|value| value.split_whitespace().collect::<Vec<_>>().join(" "))
+                .collect();
+
+            out.push_str(name);
+            out.push(':');
+            out.push_str(&values.join(","));
+            out.push('\n');
+        }
+
+        out
+    }
+
+    /// The request's `Host` authority. In lenient mode a value sent as a full
+    /// URL (`http://example.com/`) has already been normalized to
+    /// `example.com` during parsing.
+    pub fn host(&self) -> Option<&str> {
+        self.headers.get("host").map(String::as_str)
+    }
+
+    /// The body with the codings listed in `Content-Encoding` undone, last
+    /// one first; the body as is when there is none or it is `identity`.
+    /// `gzip` and `deflate` need the `gzip` feature, and anything else is
+    /// [`RequestError::UnsupportedCoding`]. Decoding past the parser's
+    /// `max_body_size` is [`RequestError::BodyTooLarge`].
+    pub fn decoded_body(&self) -> Result<Vec<u8>, RequestError> {
+        let Some(encoding) = self.headers.get("content-encoding") else {
+            return Ok(self.body.clone());
+        };
+
+        encoding
+            .rsplit(',')
+            .map(|coding| coding.trim().to_lowercase())
+            .filter(|coding| !coding.is_empty())
+            .try_fold(self.body.clone(), |body, coding| {
+                coding::decode(&coding, body, self.max_body_size)
+            })
+    }
+
+    /// The body decoded as text in the charset named by `Content-Type`,
+    /// UTF-8 if there is none. UTF-8, US-ASCII and ISO-8859-1 are supported;
+    /// bytes the charset can't represent are an error, not replaced.
+    pub fn text(&self) -> Result<String, RequestError> {
+        let charset = self
+            .headers
+            .content_type()
+            .and_then(|ct| ct.params.get("charset").map(|c| c.to_lowercase()))
+            .unwrap_or_else(|| "utf-8".to_string());
+
+        let invalid = || RequestError::InvalidBodyText(charset.clone());
+        match charset.as_str() {
+            "utf-8" | "utf8" => String::from_utf8(self.body.clone()).map_err(|_| invalid()),
+            "us-ascii" | "ascii" if !self.body.is_ascii() => Err(invalid()),
+            // Both map each byte to the code point of the same value.
+            "us-ascii" | "ascii" | "iso-8859-1" | "latin1" => {
+                Ok(self.body.iter().map(|&b| b as char).collect())
+            }
+            _ => Err(RequestError::UnsupportedCharset(charset)),
+        }
+    }
+
+    /// Chunk extensions from a chunked body, when the parser was configured
+    /// to preserve them.
+    pub fn chunk_extensions(&self) -> &[ChunkExtension] {
+        match &self.framing {
+            BodyFraming::Chunked(decoder) => decoder.extensions(),
+            BodyFraming::ContentLength(_) | BodyFraming::Terminated { .. } => &[],
+        }
+    }
+
+    /// Reads and discards whatever is left of the body so the next request on
+    /// the same stream can be parsed. A no-op if the body was already read.
+    pub fn drain_body<R: Read>(&mut self, reader: &mut R) -> io::Result<()> {
+        self.read_body_with(reader, |_| {})
+    }
+
+    /// Reads the rest of the body from `reader`, handing each piece to
+    /// `on_data` as it arrives instead of collecting it in `body`. Chunked
+    /// framing is removed; any other transfer codings are not. A no-op if the
+    /// body was already read.
+    pub fn read_body_with<R, F>(&mut self, reader: &mut R, mut on_data: F) -> io::Result<()>
+    where
+        R: Read,
+        F: FnMut(&[u8]),
+    {
+        if !matches!(self.state, RequestState::ParsingBody) {
+            return Ok(());
+        }
+
+        let mut buf = [0u8; READ_CHUNK_SIZE];
+
+        match &mut self.framing {
+            BodyFraming::ContentLength(len) => {
+                let mut remaining = *len - self.body.len();
+
+                while remaining > 0 {
+                    let n = reader.read(&mut buf[..remaining.min(READ_CHUNK_SIZE)])?;
+                    if n == 0 {
+                        return Err(RequestError::UnexpectedEof.into());
+                    }
+
+                    on_data(&buf[..n]);
+                    remaining -= n;
+                }
+            }
+            BodyFraming::Chunked(decoder) => {
+                let mut pending = Vec::new();
+                let mut decoded = Vec::new();
+
+                // Reads are capped by `max_safe_read`, so `pending` only ever
+                // holds part of the current line and nothing past the body.
+                while !decoder.is_done() {
+                    let want = decoder.max_safe_read().min(buf.len());
+                    let n = reader.read(&mut buf[..want])?;
+                    if n == 0 {
+                        return Err(RequestError::UnexpectedEof.into());
+                    }
+
+                    pending.extend_from_slice(&buf[..n]);
+                    let consumed = decoder.decode(&pending, &mut decoded)?;
+                    pending.drain(..consumed);
+
+                    if !decoded.is_empty() {
+                        on_data(&decoded);
+                        decoded.clear();
+                    }
+                }
+            }
+            BodyFraming::Terminated { terminator, done } => {
+                // Only as much is read as could complete the terminator, so
+                // nothing past it is taken from the stream.
+                let mut pending = Vec::new();
+
+                loop {
+                    let want = (terminator.len() - pending.len()).min(buf.len());
+                    let n = reader.read(&mut buf[..want])?;
+                    if n == 0 {
+                        return Err(RequestError::UnexpectedEof.into());
+                    }
+                    pending.extend_from_slice(&buf[..n]);
+
+                    if pending == *terminator {
+                        *done = true;
+                        break;
+                    }
+
+                    let keep = partial_terminator_len(&pending, terminator);
+                    on_data(&pending[..pending.len() - keep]);
+                    pending.drain(..pending.len() - keep);
+                }
+            }
         }
+
+        self.state = RequestState::Done;
+        self.record_parse_duration();
+        Ok(())
     }
 
-    fn parse(&mut self, data: &str) -> Result<usize, io::Error> {
+    fn parse(
+        &mut self,
+        data: &[u8],
+        config: &RequestParserConfig,
+        read_body: bool,
+    ) -> Result<usize, RequestError> {
         let mut total_bytes_parsed = 0;
+        self.max_body_size = config.max_body_size;
+        if self.parse_started.is_none() && !data.is_empty() {
+            self.parse_started = Some(Instant::now());
+        }
 
-        while !matches!(self.state, RequestState::Done) && total_bytes_parsed < data.len() {
-            let n = self.parse_single(&data[total_bytes_parsed..])?;
+        while !self.is_parsed(read_body) && total_bytes_parsed < data.len() {
+            let n = self.parse_single(&data[total_bytes_parsed..], config)?;
 
             if n == 0 {
                 break;
             }
 
             total_bytes_parsed += n;
+            self.offset += n;
         }
 
+        self.record_parse_duration();
         Ok(total_bytes_parsed)
     }
 
-    fn parse_single(&mut self, data: &str) -> Result<usize, io::Error> {
+    fn is_parsed(&self, read_body: bool) -> bool {
+        match self.state {
+            RequestState::Done => true,
+            RequestState::ParsingBody => !read_body,
+            _ => false,
+        }
+    }
+
+    fn parse_single(
+        &mut self,
+        data: &[u8],
+        config: &RequestParserConfig,
+    ) -> Result<usize, RequestError> {
         match self.state {
             RequestState::ParsingRequestLine => {
-                let (consumed, maybe_line) = parse_request_line(data)?;
+                // RFC 9112 section 2.2: servers should ignore at least one
+                // empty line received before the request line.
+                if let Ok(Some((0, consumed))) = find_line_end(data, config.strict) {
+                    if config.strict || self.leading_empty_lines == MAX_LEADING_EMPTY_LINES {
+                        return Err(RequestError::MalformedRequestLine {
+                            offset: self.offset,
+                            reason: "empty line before request line",
+                        });
+                    }
+
+                    self.leading_empty_lines += 1;
+                    return Ok(consumed);
+                }
+
+                let (consumed, maybe_line) = parse_request_line(data, self.offset, config)?;
 
                 if let Some(line) = maybe_line {
+                    // A simple (HTTP/0.9) request ends with its request line.
+                    self.state = match line.http_version.as_str() {
+                        "0.9" => RequestState::Done,
+                        _ => RequestState::ParsingHeaders,
+                    };
                     self.request_line = Some(line);
-                    self.state = RequestState::ParsingHeaders;
                 }
 
                 Ok(consumed)
             }
             RequestState::ParsingHeaders => {
-                let (consumed, done, err) = self.headers.parse(data.as_bytes());
+                let (consumed, done, err) = self.headers.parse_with_config(data, config);
 
-                if let Some(e) = err {
-                    return Err(io::Error::new(io::ErrorKind::Other, e));
+                if let Some(reason) = err {
+                    return Err(RequestError::MalformedHeader {
+                        offset: self.offset,
+                        reason,
+                    });
+                }
+
+                // An incomplete line counts against the limit too, so a
+                // header that never ends can't grow the buffer forever.
+                let pending = if consumed == 0 { data.len() } else { consumed };
+                if self.header_bytes + pending > config.max_header_bytes {
+                    return Err(RequestError::HeadersTooLarge);
+                }
+                self.header_bytes += consumed;
+
+                if consumed > 0 && !done {
+                    self.header_count += 1;
+                    if self.header_count > config.max_header_count {
+                        return Err(RequestError::TooManyHeaders);
+                    }
                 }
 
                 if done {
-                    self.state = RequestState::Done;
+                    self.normalize_host(config)?;
+                    self.framing = self.body_framing(config)?;
+                    if config.strict {
+                        self.check_body_allowed()?;
+                        if self.headers.get("max-forwards").is_some()
+                            && self.max_forwards().is_none()
+                        {
+                            return Err(RequestError::InvalidMaxForwards);
+                        }
+                    }
+
+                    self.state = match self.framing {
+                        BodyFraming::ContentLength(0) => RequestState::Done,
+                        _ => RequestState::ParsingBody,
+                    };
                 }
 
                 Ok(consumed)
             }
+            RequestState::ParsingBody => {
+                let n = match &mut self.framing {
+                    BodyFraming::ContentLength(len) => {
+                        let remaining = *len - self.body.len();
+                        let n = remaining.min(data.len());
+                        self.body.extend_from_slice(&data[..n]);
+                        n
+                    }
+                    BodyFraming::Chunked(decoder) => {
+                        let n = decoder.decode(data, &mut self.body)?;
+                        if self.body.len() > config.max_body_size {
+                            return Err(RequestError::BodyTooLarge);
+                        }
+                        n
+                    }
+                    BodyFraming::Terminated { terminator, done } => {
+                        // The terminator may have started in an earlier slice.
+                        let start = self.body.len().saturating_sub(terminator.len() - 1);
+                        self.body.extend_from_slice(data);
+
+                        match find(&self.body[start..], terminator) {
+                            Some(i) => {
+                                let end = start + i;
+                                let n = end + terminator.len() - (self.body.len() - data.len());
+                                self.body.truncate(end);
+                                *done = true;
+                                n
+                            }
+                            None => {
+                                if self.body.len() > config.max_body_size {
+                                    return Err(RequestError::BodyTooLarge);
+                                }
+                                data.len()
+                            }
+                        }
+                    }
+                };
+
+                if self.body_complete() {
+                    self.finish_body(config)?;
+                }
+
+                Ok(n)
+            }
             RequestState::Done => Ok(0),
         }
     }
-}
 
-pub fn request_from_reader<R: Read>(mut r: R) -> Result<Request, std::io::Error> {
-    let mut req = Request::new();
-    let mut buf = Vec::with_capacity(8);
-    let mut tmp = [0u8; 8];
+    fn normalize_host(&mut self, config: &RequestParserConfig) -> Result<(), RequestError> {
+        let Some(host) = self.headers.get("host") else {
+            return Ok(());
+        };
 
-    loop {
-        let n = r.read(&mut tmp)?;
-        if n == 0 {
-            return Err(io::Error::new(
-                io::ErrorKind::UnexpectedEof,
-                "EOF before request complete",
-            ));
+        let authority = host
+            .split_once("://")
+            .map_or(host.as_str(), |(_, rest)| rest);
+        let authority = authority.split(['/', '?', '#']).next().unwrap_or_default();
+
+        if authority == host {
+            return Ok(());
         }
 
-        buf.extend_from_slice(&tmp[..n]);
+        if config.strict {
+            return Err(RequestError::InvalidHost);
+        }
+
+        let authority = authority.to_string();
+        self.headers.set("host", &authority);
+        Ok(())
+    }
 
-        let s = std::str::from_utf8(&buf)
-            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid UTF-8"))?;
+    fn body_framing(&mut self, config: &RequestParserConfig) -> Result<BodyFraming, RequestError> {
+        let mut codings: Vec<String> = self
+            .headers
+            .get("transfer-encoding")
+            .map(|te| {
+                te.split(',')
+                    .map(|c| c.trim().to_lowercase())
+                    .filter(|c| !c.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
 
-        let consumed = req.parse(s)?;
-        if consumed > 0 {
-            buf.drain(..consumed);
+        // `identity` (RFC 2616) means no coding: alone it is the same as no
+        // Transfer-Encoding at all, but next to other codings it contradicts
+        // them.
+        if codings == ["identity"] {
+            codings.clear();
+        } else if codings.iter().any(|c| c == "identity") {
+            return Err(RequestError::InvalidTransferEncoding);
         }
 
-        if let RequestState::Done = req.state {
-            return Ok(req);
+        if codings.is_empty() {
+            if self.headers.get("content-length").is_none()
+                && let Some(terminator) = config.body_terminator.as_ref().filter(|t| !t.is_empty())
+            {
+                return Ok(BodyFraming::Terminated {
+                    terminator: terminator.clone(),
+                    done: false,
+                });
+            }
+
+            let content_length = self.content_length()?;
+            if content_length > config.max_body_size {
+                return Err(RequestError::BodyTooLarge);
+            }
+
+            return Ok(BodyFraming::ContentLength(content_length));
         }
-    }
-}
 
-fn parse_request_line(s: &str) -> Result<(usize, Option<RequestLine>), io::Error> {
-    if let Some(n) = s.find("\r\n") {
-        let data = &s[..n];
-        let mut parts = data.split_whitespace();
-
-        let method = parts
-            .next()
-            .ok_or_else(|| Error::new(io::ErrorKind::InvalidData, "missing method"))?;
-
-        let method = match method {
-            "GET" => "GET".to_string(),
-            "POST" => "POST".to_string(),
-            _ => {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "unsupported method",
-                ));
+        // A sender of both is either broken or trying to make a proxy and
+        // this server disagree on where the body ends (RFC 9112 section
+        // 6.3). Transfer-Encoding wins, and the connection can't be trusted
+        // with another request.
+        if self.headers.get("content-length").is_some() {
+            if config.strict {
+                return Err(RequestError::ConflictingFraming);
             }
-        };
+            self.headers.remove("content-length");
+            self.headers.set("connection", "close");
+        }
 
-        let request_target = parts
-            .next()
-            .ok_or_else(|| Error::new(io::ErrorKind::InvalidData, "missing request target"))?
-            .to_string();
-
-        let http_version = parts
-            .next()
-            .and_then(|s| s.strip_prefix("HTTP/"))
-            .ok_or_else(|| {
-                Error::new(
-                    io::ErrorKind::InvalidData,
-                    "missing or invalid http version",
-                )
-            })?
-            .to_string();
-
-        if let Some(_) = parts.next() {
-            return Err(Error::new(
-                io::ErrorKind::InvalidData,
-                "too many parts in request line",
-            ));
+        // `chunked` must be applied last, and only once; anything else leaves
+        // a request body with no way to find its end.
+        if codings.pop().as_deref() != Some("chunked") || codings.iter().any(|c| c == "chunked") {
+            return Err(RequestError::InvalidTransferEncoding);
         }
 
-        return Ok((
-            n + 2,
-            Some(RequestLine {
-                http_version,
-                request_target,
-                method,
-            }),
-        ));
+        self.transfer_codings = codings;
+        Ok(BodyFraming::Chunked(ChunkedDecoder::new(config)))
     }
 
-    Ok((0, None))
-}
-
-#[cfg(test)]
-mod tests {
-    use crate::{
-        chunk_reader::ChunkReader,
-        request::{RequestState, request_from_reader},
-    };
+    /// Rejects body framing on methods whose requests have no use for a
+    /// body. A proxy that drops such a body while this server reads it, or
+    /// the other way round, disagrees on where the next request starts.
+    fn check_body_allowed(&self) -> Result<(), RequestError> {
+        let Some(line) = &self.request_line else {
+            return Ok(());
+        };
 
-    #[test]
-    fn test_good_get_request_line() {
-        let reader = ChunkReader::new(
-            concat!(
-                "GET / HTTP/1.1\r\n",
-                "Host: localhost:42069\r\n",
-                "User-Agent: curl/7.81.0\r\n",
-                "Accept: */*\r\n",
-                "\r\n",
-            ),
-            50,
+        // A terminated body is the config's choice, not the client's.
+        let has_body = matches!(
+            self.framing,
+            BodyFraming::ContentLength(1..) | BodyFraming::Chunked(_)
+        );
+        let forbids_body = matches!(
+            line.method,
+            Method::Get | Method::Head | Method::Delete | Method::Options | Method::Trace
         );
 
-        let result = request_from_reader(reader);
-        assert!(result.is_ok());
+        if has_body && forbids_body {
+            return Err(RequestError::BodyNotAllowed(line.method));
+        }
+        Ok(())
+    }
 
-        let r = result.unwrap();
-        assert!(matches!(r.state, RequestState::Done));
+    fn body_complete(&self) -> bool {
+        match &self.framing {
+            BodyFraming::ContentLength(len) => self.body.len() == *len,
+            BodyFraming::Chunked(decoder) => decoder.is_done(),
+            BodyFraming::Terminated { done, .. } => *done,
+        }
+    }
 
-        let line = r.request_line.expect("request line should be parsed");
-        assert_eq!(line.method, "GET");
-        assert_eq!(line.request_target, "/");
-        assert_eq!(line.http_version, "1.1");
+    /// Undoes any transfer codings listed before `chunked`, last one first.
+    fn finish_body(&mut self, config: &RequestParserConfig) -> Result<(), RequestError> {
+        for coding in self.transfer_codings.iter().rev() {
+            let body = std::mem::take(&mut self.body);
+            self.body = coding::decode(coding, body, config.max_body_size)?;
+        }
+
+        self.state = RequestState::Done;
+        Ok(())
     }
 
-    #[test]
-    fn test_good_get_request_line_with_path() {
-        let reader = ChunkReader::new(
-            concat!(
-                "GET /coffee HTTP/1.1\r\n",
-                "Host: localhost:42069\r\n",
-                "User-Agent: curl/7.81.0\r\n",
-                "Accept: */*\r\n",
-                "\r\n",
-            ),
-            3,
-        );
+    /// `Content-Length` as `1*DIGIT`. A list of values (from repeated
+    /// headers merged under a lenient merge policy) is accepted only when
+    /// every value is the same (RFC 9110 section 8.6).
+    fn content_length(&self) -> Result<usize, RequestError> {
+        let values = self.headers.get_all("content-length");
+        if values.is_empty() {
+            return Ok(0);
+        }
 
-        let result = request_from_reader(reader);
-        assert!(result.is_ok());
+        let mut lengths = values
+            .iter()
+            .flat_map(|v| v.split(','))
+            .map(|v| parse_digits(v.trim()));
+        let first = lengths.next().flatten();
+        match first {
+            Some(len) if lengths.all(|other| other == first) => {
+                usize::try_from(len).map_err(|_| RequestError::InvalidContentLength)
+            }
+            _ => Err(RequestError::InvalidContentLength),
+        }
+    }
+}
 
-        let r = result.unwrap();
-        assert!(matches!(r.state, RequestState::Done));
+/// Reads consecutive requests from a single stream, keeping any bytes read
+/// past the end of one request for the next one (keep-alive / pipelining).
+pub struct RequestReader<R> {
+    reader: R,
+    buf: Vec<u8>,
+    config: RequestParserConfig,
+}
 
-        let line = r.request_line.expect("request line should be parsed");
-        assert_eq!(line.method, "GET");
-        assert_eq!(line.request_target, "/coffee");
-        assert_eq!(line.http_version, "1.1");
+impl<R: Read> RequestReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_config(reader, RequestParserConfig::default())
     }
 
-    #[test]
-    fn test_invalid_number_of_parts_in_request_line() {
-        let input = ChunkReader::new(
-            concat!(
-                "/coffee HTTP/1.1\r\n",
-                "Host: localhost:42069\r\n",
-                "User-Agent: curl/7.81.0\r\n",
-                "Accept: */*\r\n",
-                "\r\n",
-            ),
-            7,
-        );
+    pub fn with_config(reader: R, config: RequestParserConfig) -> Self {
+        Self {
+            reader,
+            buf: Vec::with_capacity(READ_CHUNK_SIZE),
+            config,
+        }
+    }
 
-        let result = request_from_reader(input);
-        assert!(result.is_err());
+    /// The underlying stream, e.g. to write a response back over it. Reading
+    /// from it directly skips any bytes already buffered here.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
     }
 
-    #[test]
-    fn test_good_post_request_line() {
-        let input = ChunkReader::new(
-            concat!(
-                "POST / HTTP/1.1\r\n",
-                "Host: localhost:42069\r\n",
-                "User-Agent: curl/7.81.0\r\n",
-                "Accept: */*\r\n",
-                "\r\n",
-            ),
-            4,
-        );
+    /// The underlying stream and the bytes read from it but not yet parsed,
+    /// e.g. to hand the connection over to another protocol.
+    pub fn into_parts(self) -> (R, Vec<u8>) {
+        (self.reader, self.buf)
+    }
 
-        let result = request_from_reader(input);
-        assert!(result.is_ok());
+    /// Blocks until at least one byte of the next request is buffered.
+    /// Returns `false` if the stream reached EOF first.
+    pub fn fill_buf(&mut self) -> Result<bool, io::Error> {
+        if !self.buf.is_empty() {
+            return Ok(true);
+        }
 
-        let r = result.unwrap();
-        assert!(matches!(r.state, RequestState::Done));
+        Ok(self.read_more()? > 0)
+    }
 
-        let line = r.request_line.expect("request line should be parsed");
-        assert_eq!(line.method, "POST");
-        assert_eq!(line.request_target, "/");
-        assert_eq!(line.http_version, "1.1");
+    /// Parses the next request. Returns `None` if the stream ended cleanly
+    /// before any byte of a new request arrived.
+    pub fn next_request(&mut self) -> Result<Option<Request>, RequestError> {
+        self.read_request(true)
     }
 
-    #[test]
-    fn test_invalid_out_of_order_request_line() {
-        let input = ChunkReader::new(
-            concat!(
-                "/ GET HTTP/1.1\r\n",
-                "Host: localhost:42069\r\n",
-                "User-Agent: curl/7.81.0\r\n",
-                "Accept: */*\r\n",
-                "\r\n",
-            ),
-            9,
-        );
+    /// Parses only the request line and headers, leaving the body unread on
+    /// the stream. Read it through this reader, or discard it with
+    /// [`Request::drain_body`] before asking for the next request.
+    pub fn next_request_head(&mut self) -> Result<Option<Request>, RequestError> {
+        self.read_request(false)
+    }
 
-        let result = request_from_reader(input);
-        assert!(result.is_err());
+    /// Like [`RequestReader::next_request`], but parses into `req` after
+    /// resetting it, reusing its allocations. Returns `false` if the stream
+    /// ended cleanly before any byte of a new request arrived.
+    pub fn next_request_into(&mut self, req: &mut Request) -> Result<bool, RequestError> {
+        req.reset();
+        self.read_into(req, true)
     }
 
-    #[test]
-    fn test_standard_headers() {
-        let reader = ChunkReader::new(
-            concat!(
-                "GET / HTTP/1.1\r\n",
-                "Host: localhost:42069\r\n",
-                "User-Agent: curl/7.81.0\r\n",
-                "Accept: */*\r\n",
-                "\r\n",
-            ),
-            3,
-        );
+    /// Like [`RequestReader::next_request_head`], but parses into `req` after
+    /// resetting it.
+    pub fn next_request_head_into(&mut self, req: &mut Request) -> Result<bool, RequestError> {
+        req.reset();
+        self.read_into(req, false)
+    }
 
-        let result = request_from_reader(reader);
-        assert!(result.is_ok());
+    /// Reads the body of a request whose head came from this reader into
+    /// `req.body`, with transfer codings undone. A body over `max_body_size`
+    /// is read and discarded, up to `max_discard` bytes past the limit, so
+    /// the next request can still be parsed; it then fails with
+    /// [`RequestError::BodyTooLarge`] while [`Request::body_fully_read`] is
+    /// true. A body too large to discard fails the same way but leaves it
+    /// false, as does any other failure.
+    pub(crate) fn read_body(
+        &mut self,
+        req: &mut Request,
+        max_discard: u64,
+    ) -> Result<(), RequestError> {
+        if req.body_fully_read() {
+            return Ok(());
+        }
 
-        let r = result.unwrap();
-        assert_eq!(r.headers.get("host"), Some(&"localhost:42069".to_string()));
-        assert_eq!(
-            r.headers.get("user-agent"),
-            Some(&"curl/7.81.0".to_string())
-        );
-        assert_eq!(r.headers.get("accept"), Some(&"*/*".to_string()));
-    }
+        let max = self.config.max_body_size;
+        let limit = (max as u64).saturating_add(max_discard);
+        let mut body = Vec::new();
+        let mut too_large = false;
+
+        let mut limited = self.by_ref().take(limit);
+        let result = req.read_body_with(&mut limited, |data| {
+            too_large |= body.len() + data.len() > max;
+            if too_large {
+                body.clear();
+            } else {
+                body.extend_from_slice(data);
+            }
+        });
+        if result.is_err() && limited.limit() == 0 {
+            return Err(RequestError::BodyTooLarge);
+        }
+        result.map_err(RequestError::from_io)?;
+
+        if too_large {
+            return Err(RequestError::BodyTooLarge);
+        }
+
+        req.body = body;
+        req.finish_body(&self.config)
+    }
+
+    fn read_request(&mut self, read_body: bool) -> Result<Option<Request>, RequestError> {
+        let mut req = Request::new();
+        Ok(self.read_into(&mut req, read_body)?.then_some(req))
+    }
+
+    fn read_into(&mut self, req: &mut Request, read_body: bool) -> Result<bool, RequestError> {
+        loop {
+            if !self.buf.is_empty() {
+                let consumed = req.parse(&self.buf, &self.config, read_body)?;
+                if consumed > 0 {
+                    self.buf.drain(..consumed);
+                }
+
+                if req.is_parsed(read_body) {
+                    return Ok(true);
+                }
+            }
+
+            if self.read_more()? == 0 {
+                if self.buf.is_empty() && matches!(req.state, RequestState::ParsingRequestLine) {
+                    return Ok(false);
+                }
+
+                return Err(RequestError::UnexpectedEof);
+            }
+        }
+    }
+
+    fn read_more(&mut self) -> Result<usize, io::Error> {
+        let mut tmp = [0u8; READ_CHUNK_SIZE];
+        let n = self.reader.read(&mut tmp)?;
+        self.buf.extend_from_slice(&tmp[..n]);
+        Ok(n)
+    }
+}
+
+/// Serves bytes already buffered past the last parsed request before reading
+/// from the underlying stream, so a body left unread by
+/// [`RequestReader::next_request_head`] can be consumed in order.
+impl<R: Read> Read for RequestReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.buf.is_empty() {
+            return self.reader.read(buf);
+        }
+
+        let n = self.buf.len().min(buf.len());
+        buf[..n].copy_from_slice(&self.buf[..n]);
+        self.buf.drain(..n);
+        Ok(n)
+    }
+}
+
+pub fn request_from_reader<R: Read>(r: R) -> Result<Request, RequestError> {
+    request_from_reader_with_config(r, RequestParserConfig::default())
+}
+
+pub fn request_from_reader_with_config<R: Read>(
+    r: R,
+    config: RequestParserConfig,
+) -> Result<Request, RequestError> {
+    RequestReader::with_config(r, config)
+        .next_request()?
+        .ok_or(RequestError::UnexpectedEof)
+}
+
+/// Parses the request line and headers from `lines`, one line at a time,
+/// leaving the body unread in `lines`. Lines over the reader's limit are
+/// reported as [`RequestError::RequestLineTooLong`] or
+/// [`RequestError::HeadersTooLarge`]. Returns `None` if the stream ended
+/// cleanly before the request began.
+pub fn request_head_from_lines<R: Read>(
+    lines: &mut LineReader<R>,
+    config: &RequestParserConfig,
+) -> Result<Option<Request>, RequestError> {
+    let mut req = Request::new();
+
+    while !req.is_parsed(false) {
+        let line = match lines.next_raw_line() {
+            Ok(Some(line)) => line,
+            Ok(None) if req.offset == 0 => return Ok(None),
+            Ok(None) => return Err(RequestError::UnexpectedEof),
+            Err(LineError::TooLong { .. }) => {
+                return Err(match req.state {
+                    RequestState::ParsingRequestLine => RequestError::RequestLineTooLong,
+                    _ => RequestError::HeadersTooLarge,
+                });
+            }
+            Err(LineError::Io(err)) => return Err(RequestError::Io(err)),
+        };
+
+        // Only an unterminated last line is left partly unparsed.
+        if req.parse(&line, config, false)? < line.len() {
+            return Err(RequestError::UnexpectedEof);
+        }
+    }
+
+    Ok(Some(req))
+}
+
+/// Parses one complete request from `data` with the default config.
+///
+/// Total over arbitrary input: malformed, truncated or oversized bytes are
+/// reported as a [`RequestError`], never a panic. Bytes past the end of the
+/// request are ignored. This is the entry point to drive from a fuzzer.
+pub fn parse_request(data: &[u8]) -> Result<Request, RequestError> {
+    request_from_reader(data)
+}
+
+/// The request line and headers of a request whose body is read separately
+/// through a [`BodyReader`].
+#[derive(Debug, Clone)]
+pub struct RequestHead {
+    pub request_line: RequestLine,
+    pub headers: Headers,
+}
+
+/// Parses the head of one request from `reader` and returns it along with a
+/// [`BodyReader`] that streams the body from the same stream as it is read,
+/// without buffering it, e.g. for a proxy.
+pub fn request_head_from_reader<R: Read>(
+    reader: R,
+) -> Result<(RequestHead, BodyReader<R>), RequestError> {
+    let mut reader = RequestReader::new(reader);
+    let mut req = reader
+        .next_request_head()?
+        .ok_or(RequestError::UnexpectedEof)?;
+
+    let head = RequestHead {
+        request_line: req.request_line.take().ok_or(RequestError::UnexpectedEof)?,
+        headers: std::mem::take(&mut req.headers),
+    };
+    let body = BodyReader {
+        reader,
+        req,
+        read: 0,
+        pending: Vec::new(),
+        raw: Vec::new(),
+    };
+
+    Ok((head, body))
+}
+
+/// A request body read lazily from the underlying stream, with chunked
+/// framing removed. Other transfer codings are not undone.
+pub struct BodyReader<R> {
+    reader: RequestReader<R>,
+    /// The parse state the head left behind, for its body framing.
+    req: Request,
+    /// Bytes of a `Content-Length` body read so far.
+    read: usize,
+    /// Body bytes decoded but not yet returned.
+    pending: Vec<u8>,
+    /// Bytes read but not yet decoded: part of a chunked line, or what may
+    /// be the start of a body terminator.
+    raw: Vec<u8>,
+}
+
+impl<R: Read> BodyReader<R> {
+    /// The reader the body came from, positioned after the body if it was
+    /// read to the end, to parse the next request on the connection.
+    pub fn into_inner(self) -> RequestReader<R> {
+        self.reader
+    }
+
+    /// Reads at most as far as the end of the body into `pending`.
+    fn fill_pending(&mut self) -> io::Result<()> {
+        let mut buf = [0u8; READ_CHUNK_SIZE];
+
+        while self.pending.is_empty() && !matches!(self.req.state, RequestState::Done) {
+            match &mut self.req.framing {
+                BodyFraming::ContentLength(len) => {
+                    let remaining = *len - self.read;
+                    if remaining == 0 {
+                        self.req.state = RequestState::Done;
+                        break;
+                    }
+
+                    let n = self
+                        .reader
+                        .read(&mut buf[..remaining.min(READ_CHUNK_SIZE)])?;
+                    if n == 0 {
+                        return Err(RequestError::UnexpectedEof.into());
+                    }
+                    self.read += n;
+                    self.pending.extend_from_slice(&buf[..n]);
+                }
+                BodyFraming::Chunked(decoder) => {
+                    if decoder.is_done() {
+                        self.req.state = RequestState::Done;
+                        break;
+                    }
+
+                    let want = decoder.max_safe_read().min(buf.len());
+                    let n = self.reader.read(&mut buf[..want])?;
+                    if n == 0 {
+                        return Err(RequestError::UnexpectedEof.into());
+                    }
+                    self.raw.extend_from_slice(&buf[..n]);
+                    let consumed = decoder.decode(&self.raw, &mut self.pending)?;
+                    self.raw.drain(..consumed);
+                }
+                BodyFraming::Terminated { terminator, done } => {
+                    let want = (terminator.len() - self.raw.len()).min(buf.len());
+                    let n = self.reader.read(&mut buf[..want])?;
+                    if n == 0 {
+                        return Err(RequestError::UnexpectedEof.into());
+                    }
+                    self.raw.extend_from_slice(&buf[..n]);
+
+                    if self.raw == *terminator {
+                        *done = true;
+                        self.raw.clear();
+                        self.req.state = RequestState::Done;
+                        break;
+                    }
+
+                    let keep = partial_terminator_len(&self.raw, terminator);
+                    let data = self.raw.len() - keep;
+                    self.pending.extend(self.raw.drain(..data));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for BodyReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.fill_pending()?;
+
+        let n = self.pending.len().min(buf.len());
+        buf[..n].copy_from_slice(&self.pending[..n]);
+        self.pending.drain(..n);
+        Ok(n)
+    }
+}
+
+/// Parses every request in `data` back to back with the default config,
+/// e.g. from a captured stream. Stops after the first error; incomplete
+/// trailing data is reported as [`RequestError::UnexpectedEof`].
+pub fn parse_all_requests(data: &[u8]) -> Vec<Result<Request, RequestError>> {
+    let mut reader = RequestReader::new(data);
+    let mut requests = Vec::new();
+
+    loop {
+        match reader.next_request() {
+            Ok(Some(req)) => requests.push(Ok(req)),
+            Ok(None) => break,
+            Err(err) => {
+                requests.push(Err(err));
+                break;
+            }
+        }
+    }
+
+    requests
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// How many bytes at the end of `data` could be the start of `terminator`.
+fn partial_terminator_len(data: &[u8], terminator: &[u8]) -> usize {
+    (1..terminator.len())
+        .rev()
+        .find(|&k| data.ends_with(&terminator[..k]))
+        .unwrap_or(0)
+}
+
+fn parse_request_line(
+    data: &[u8],
+    offset: usize,
+    config: &RequestParserConfig,
+) -> Result<(usize, Option<RequestLine>), RequestError> {
+    let malformed = |reason| RequestError::MalformedRequestLine { offset, reason };
+
+    let Some((n, consumed)) = find_line_end(data, config.strict).map_err(malformed)? else {
+        if data.len() > config.max_request_line_len {
+            return Err(RequestError::RequestLineTooLong);
+        }
+
+        return Ok((0, None));
+    };
+
+    if n > config.max_request_line_len {
+        return Err(RequestError::RequestLineTooLong);
+    }
+
+    let s = std::str::from_utf8(&data[..n]).map_err(|_| RequestError::InvalidUtf8 { offset })?;
+    let mut parts = s.split_whitespace();
+
+    let method = parts.next().ok_or_else(|| malformed("missing method"))?;
+
+    let method = method
+        .parse::<Method>()
+        .map_err(|_| malformed("unsupported method"))?;
+
+    let request_target = parts
+        .next()
+        .ok_or_else(|| malformed("missing request target"))?
+        .to_string();
+
+    let http_version = match parts.next() {
+        // HTTP/0.9 only had `GET`, and no version on the request line.
+        None if config.http09 && method == Method::Get => "0.9".to_string(),
+        version => {
+            let version = version
+                .and_then(|s| s.strip_prefix("HTTP/"))
+                .filter(|v| {
+                    let v = v.as_bytes();
+                    v.len() == 3 && v[0].is_ascii_digit() && v[1] == b'.' && v[2].is_ascii_digit()
+                })
+                .ok_or_else(|| malformed("missing or invalid http version"))?;
+
+            if !version.starts_with("1.") {
+                return Err(RequestError::UnsupportedVersion(version.to_string()));
+            }
+            version.to_string()
+        }
+    };
+
+    if parts.next().is_some() {
+        return Err(malformed("too many parts in request line"));
+    }
+
+    Ok((
+        consumed,
+        Some(RequestLine {
+            http_version,
+            request_target,
+            method,
+            raw: s.to_string(),
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Read, time::Duration};
+
+    use crate::{
+        chunk_reader::ChunkReader,
+        config::{MergePolicy, RequestParserConfig},
+        error::RequestError,
+        line::LineReader,
+        method::Method,
+        request::{
+            Request, RequestReader, RequestState, parse_all_requests, parse_request,
+            request_from_reader, request_from_reader_with_config, request_head_from_lines,
+            request_head_from_reader,
+        },
+    };
+
+    #[test]
+    fn test_good_get_request_line() {
+        let reader = ChunkReader::new(
+            concat!(
+                "GET / HTTP/1.1\r\n",
+                "Host: localhost:42069\r\n",
+                "User-Agent: curl/7.81.0\r\n",
+                "Accept: */*\r\n",
+                "\r\n",
+            ),
+            50,
+        );
+
+        let result = request_from_reader(reader);
+        assert!(result.is_ok());
+
+        let r = result.unwrap();
+        assert!(matches!(r.state, RequestState::Done));
+
+        let line = r.request_line.expect("request line should be parsed");
+        assert_eq!(line.method, "GET");
+        assert_eq!(line.request_target, "/");
+        assert_eq!(line.http_version, "1.1");
+    }
+
+    #[test]
+    fn test_good_get_request_line_with_path() {
+        let reader = ChunkReader::new(
+            concat!(
+                "GET /coffee HTTP/1.1\r\n",
+                "Host: localhost:42069\r\n",
+                "User-Agent: curl/7.81.0\r\n",
+                "Accept: */*\r\n",
+                "\r\n",
+            ),
+            3,
+        );
+
+        let result = request_from_reader(reader);
+        assert!(result.is_ok());
+
+        let r = result.unwrap();
+        assert!(matches!(r.state, RequestState::Done));
+
+        let line = r.request_line.expect("request line should be parsed");
+        assert_eq!(line.method, "GET");
+        assert_eq!(line.request_target, "/coffee");
+        assert_eq!(line.http_version, "1.1");
+    }
+
+    #[test]
+    fn test_invalid_number_of_parts_in_request_line() {
+        let input = ChunkReader::new(
+            concat!(
+                "/coffee HTTP/1.1\r\n",
+                "Host: localhost:42069\r\n",
+                "User-Agent: curl/7.81.0\r\n",
+                "Accept: */*\r\n",
+                "\r\n",
+            ),
+            7,
+        );
+
+        let result = request_from_reader(input);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_good_post_request_line() {
+        let input = ChunkReader::new(
+            concat!(
+                "POST / HTTP/1.1\r\n",
+                "Host: localhost:42069\r\n",
+                "User-Agent: curl/7.81.0\r\n",
+                "Accept: */*\r\n",
+                "\r\n",
+            ),
+            4,
+        );
+
+        let result = request_from_reader(input);
+        assert!(result.is_ok());
+
+        let r = result.unwrap();
+        assert!(matches!(r.state, RequestState::Done));
+
+        let line = r.request_line.expect("request line should be parsed");
+        assert_eq!(line.method, "POST");
+        assert_eq!(line.request_target, "/");
+        assert_eq!(line.http_version, "1.1");
+    }
+
+    #[test]
+    fn test_invalid_out_of_order_request_line() {
+        let input = ChunkReader::new(
+            concat!(
+                "/ GET HTTP/1.1\r\n",
+                "Host: localhost:42069\r\n",
+                "User-Agent: curl/7.81.0\r\n",
+                "Accept: */*\r\n",
+                "\r\n",
+            ),
+            9,
+        );
+
+        let result = request_from_reader(input);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_standard_headers() {
+        let reader = ChunkReader::new(
+            concat!(
+                "GET / HTTP/1.1\r\n",
+                "Host: localhost:42069\r\n",
+                "User-Agent: curl/7.81.0\r\n",
+                "Accept: */*\r\n",
+                "\r\n",
+            ),
+            3,
+        );
+
+        let result = request_from_reader(reader);
+        assert!(result.is_ok());
+
+        let r = result.unwrap();
+        assert_eq!(r.headers.get("host"), Some(&"localhost:42069".to_string()));
+        assert_eq!(
+            r.headers.get("user-agent"),
+            Some(&"curl/7.81.0".to_string())
+        );
+        assert_eq!(r.headers.get("accept"), Some(&"*/*".to_string()));
+    }
 
     #[test]
     fn test_malformed_header() {
@@ -321,4 +1322,1072 @@ mod tests {
         let result = request_from_reader(reader);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_pipelined_requests_with_body() {
+        let reader = ChunkReader::new(
+            concat!(
+                "POST /submit HTTP/1.1\r\n",
+                "Host: localhost:42069\r\n",
+                "Content-Length: 13\r\n",
+                "\r\n",
+                "hello world!\n",
+                "GET /next HTTP/1.1\r\n",
+                "Host: localhost:42069\r\n",
+                "\r\n",
+            ),
+            5,
+        );
+        let mut reader = RequestReader::new(reader);
+
+        let first = reader.next_request().unwrap().expect("first request");
+        assert_eq!(first.body, b"hello world!\n");
+
+        let second = reader.next_request().unwrap().expect("second request");
+        let line = second.request_line.expect("request line should be parsed");
+        assert_eq!(line.request_target, "/next");
+        assert!(second.body.is_empty());
+
+        assert!(reader.next_request().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_well_formed_host_in_both_modes() {
+        for strict in [false, true] {
+            let reader = ChunkReader::new("GET / HTTP/1.1\r\nHost: example.com:8080\r\n\r\n", 4);
+            let config = RequestParserConfig::builder().strict(strict).build();
+
+            let r = request_from_reader_with_config(reader, config).unwrap();
+            assert_eq!(r.host(), Some("example.com:8080"));
+        }
+    }
+
+    #[test]
+    fn test_url_in_host_is_normalized_in_lenient_mode() {
+        let reader = ChunkReader::new(
+            "GET / HTTP/1.1\r\nHost: http://example.com/index.html\r\n\r\n",
+            4,
+        );
+
+        let r = request_from_reader(reader).unwrap();
+        assert_eq!(r.host(), Some("example.com"));
+    }
+
+    #[test]
+    fn test_url_in_host_is_rejected_in_strict_mode() {
+        let reader = ChunkReader::new("GET / HTTP/1.1\r\nHost: http://example.com/\r\n\r\n", 4);
+        let config = RequestParserConfig::builder().strict(true).build();
+
+        let result = request_from_reader_with_config(reader, config);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_get_with_body_rejected_in_strict_mode() {
+        let data = "GET / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nhello";
+
+        let strict = RequestParserConfig::builder().strict(true).build();
+        let err = request_from_reader_with_config(ChunkReader::new(data, 8), strict).unwrap_err();
+        assert!(matches!(err, RequestError::BodyNotAllowed(Method::Get)));
+
+        let r = request_from_reader(ChunkReader::new(data, 8)).unwrap();
+        assert_eq!(r.body, b"hello");
+
+        let chunked = "DELETE / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n";
+        let strict = RequestParserConfig::builder().strict(true).build();
+        assert!(request_from_reader_with_config(ChunkReader::new(chunked, 8), strict).is_err());
+    }
+
+    #[test]
+    fn test_http09_simple_request() {
+        let config = RequestParserConfig::builder().http09(true).build();
+        let r = request_from_reader_with_config(ChunkReader::new("GET /old.html\r\n", 4), config)
+            .unwrap();
+
+        let line = r.request_line.as_ref().unwrap();
+        assert_eq!(line.method, Method::Get);
+        assert_eq!(line.request_target, "/old.html");
+        assert_eq!(line.http_version, "0.9");
+        assert!(r.headers.is_empty());
+        assert!(r.wants_close());
+
+        assert!(matches!(
+            request_from_reader(ChunkReader::new("GET /old.html\r\n", 4)),
+            Err(RequestError::MalformedRequestLine { .. })
+        ));
+
+        let config = RequestParserConfig::builder().http09(true).build();
+        assert!(
+            request_from_reader_with_config(ChunkReader::new("POST /form\r\n", 4), config).is_err()
+        );
+    }
+
+    #[test]
+    fn test_body_text() {
+        let request = |content_type: &str, body: &[u8]| {
+            let mut data = format!(
+                "POST / HTTP/1.1\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\r\n",
+                body.len()
+            )
+            .into_bytes();
+            data.extend_from_slice(body);
+            parse_request(&data).unwrap()
+        };
+
+        let utf8 = "grüße".as_bytes();
+        assert_eq!(request("text/plain", utf8).text().unwrap(), "grüße");
+        assert_eq!(
+            request("text/plain; charset=UTF-8", utf8).text().unwrap(),
+            "grüße"
+        );
+        assert_eq!(
+            request("text/plain; charset=iso-8859-1", b"gr\xfc\xdfe")
+                .text()
+                .unwrap(),
+            "grüße"
+        );
+        assert!(matches!(
+            request("text/plain; charset=utf-8", b"gr\xfc\xdfe").text(),
+            Err(RequestError::InvalidBodyText(_))
+        ));
+        assert!(matches!(
+            request("text/plain; charset=us-ascii", utf8).text(),
+            Err(RequestError::InvalidBodyText(_))
+        ));
+        assert!(matches!(
+            request("text/plain; charset=koi8-r", b"abc").text(),
+            Err(RequestError::UnsupportedCharset(_))
+        ));
+    }
+
+    #[test]
+    fn test_transfer_encoding_identity() {
+        let r = parse_request(
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: identity\r\nContent-Length: 2\r\n\r\nhi",
+        )
+        .unwrap();
+        assert_eq!(r.body, b"hi");
+
+        let r = parse_request(b"POST / HTTP/1.1\r\nTransfer-Encoding: Identity\r\n\r\n").unwrap();
+        assert!(r.body.is_empty());
+
+        for te in [
+            "identity, chunked",
+            "chunked, identity",
+            "gzip, identity, chunked",
+        ] {
+            let data = format!("POST / HTTP/1.1\r\nTransfer-Encoding: {te}\r\n\r\n0\r\n\r\n");
+            assert!(
+                matches!(
+                    parse_request(data.as_bytes()),
+                    Err(RequestError::InvalidTransferEncoding)
+                ),
+                "{te}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_bare_cr_in_header_rejected_in_strict_mode() {
+        let data = "GET / HTTP/1.1\r\nHost: a\rX-Smuggled: 1\r\n\r\n";
+        let strict = RequestParserConfig::builder().strict(true).build();
+
+        let err = request_from_reader_with_config(ChunkReader::new(data, 4), strict).unwrap_err();
+        assert!(matches!(
+            err,
+            RequestError::MalformedHeader { offset: 16, .. }
+        ));
+
+        let strict = RequestParserConfig::builder().strict(true).build();
+        let data = "GET /a\rb HTTP/1.1\r\n\r\n";
+        assert!(matches!(
+            request_from_reader_with_config(ChunkReader::new(data, 4), strict),
+            Err(RequestError::MalformedRequestLine { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_whole_request_in_one_slice() {
+        let config = RequestParserConfig::default();
+        let mut data = String::from("POST /upload HTTP/1.1\r\nHost: localhost\r\n");
+        for i in 0..40 {
+            data.push_str(&format!("X-Header-{i}: value {i}\r\n"));
+        }
+        data.push_str("Content-Length: 11\r\n\r\nhello world");
+        let request_len = data.len();
+        data.push_str("GET /next HTTP/1.1\r\n");
+
+        let mut req = Request::new();
+        let consumed = req.parse(data.as_bytes(), &config, true).unwrap();
+
+        assert_eq!(consumed, request_len);
+        assert!(matches!(req.state, RequestState::Done));
+        assert_eq!(
+            req.headers.get("x-header-39"),
+            Some(&"value 39".to_string())
+        );
+        assert_eq!(req.body, b"hello world");
+    }
+
+    #[test]
+    fn test_request_head_from_lines() {
+        let config = RequestParserConfig::default();
+        let data = "POST /a HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nhelloGET";
+        let mut lines = LineReader::new(ChunkReader::new(data, 3), 64);
+
+        let mut req = request_head_from_lines(&mut lines, &config)
+            .unwrap()
+            .expect("request");
+        assert_eq!(req.request_line.as_ref().unwrap().request_target, "/a");
+        assert_eq!(req.headers.get("host"), Some(&"localhost".to_string()));
+
+        let mut body = Vec::new();
+        req.read_body_with(&mut lines, |data| body.extend_from_slice(data))
+            .unwrap();
+        assert_eq!(body, b"hello");
+        assert_eq!(lines.next_line().unwrap().unwrap(), b"GET");
+
+        let mut lines = LineReader::new(ChunkReader::new("GET / HTTP/1.1\r\nX: y\r\n", 4), 64);
+        assert!(matches!(
+            request_head_from_lines(&mut lines, &config),
+            Err(RequestError::UnexpectedEof)
+        ));
+
+        let mut lines = LineReader::new(ChunkReader::new("GET /very/long HTTP/1.1\r\n", 4), 8);
+        assert!(matches!(
+            request_head_from_lines(&mut lines, &config),
+            Err(RequestError::RequestLineTooLong)
+        ));
+    }
+
+    #[test]
+    fn test_max_forwards() {
+        let request = |value: &str, strict| {
+            let data =
+                format!("TRACE / HTTP/1.1\r\nHost: localhost\r\nMax-Forwards: {value}\r\n\r\n");
+            request_from_reader_with_config(
+                ChunkReader::new(&data, 8),
+                RequestParserConfig::builder().strict(strict).build(),
+            )
+        };
+
+        assert_eq!(request("5", true).unwrap().max_forwards(), Some(5));
+        assert_eq!(request("0", true).unwrap().max_forwards(), Some(0));
+        assert_eq!(
+            parse_request(b"OPTIONS * HTTP/1.1\r\n\r\n")
+                .unwrap()
+                .max_forwards(),
+            None
+        );
+
+        for invalid in ["-1", "ten", "+3"] {
+            assert_eq!(request(invalid, false).unwrap().max_forwards(), None);
+            assert!(matches!(
+                request(invalid, true),
+                Err(RequestError::InvalidMaxForwards)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_parse_all_requests() {
+        let data = concat!(
+            "GET /a HTTP/1.1\r\nHost: localhost\r\n\r\n",
+            "POST /b HTTP/1.1\r\nHost: localhost\r\nContent-Length: 3\r\n\r\nabc",
+            "GET /c HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        );
+
+        let requests = parse_all_requests(data.as_bytes());
+        let targets: Vec<_> = requests
+            .iter()
+            .map(|req| {
+                req.as_ref()
+                    .unwrap()
+                    .request_line
+                    .as_ref()
+                    .unwrap()
+                    .request_target
+                    .as_str()
+            })
+            .collect();
+        assert_eq!(targets, ["/a", "/b", "/c"]);
+        assert_eq!(requests[1].as_ref().unwrap().body, b"abc");
+
+        let truncated = parse_all_requests(&data.as_bytes()[..data.len() - 2]);
+        assert_eq!(truncated.len(), 3);
+        assert!(matches!(truncated[2], Err(RequestError::UnexpectedEof)));
+
+        let malformed =
+            parse_all_requests(b"GET /a HTTP/1.1\r\n\r\nBOGUS\r\n\r\nGET /b HTTP/1.1\r\n\r\n");
+        assert_eq!(malformed.len(), 2);
+        assert!(matches!(
+            malformed[1],
+            Err(RequestError::MalformedRequestLine { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        let data = "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nhello";
+        let reader = ChunkReader::new(data, 16).with_delay(Duration::from_millis(5));
+
+        let req = request_from_reader(reader).unwrap();
+        assert!(req.parse_duration().unwrap() >= Duration::from_millis(10));
+
+        let mut req = Request::new();
+        req.parse(b"GET / HTTP/1.1\r\n", &RequestParserConfig::default(), true)
+            .unwrap();
+        assert_eq!(req.parse_duration(), None);
+    }
+
+    #[test]
+    fn test_body_terminator() {
+        let config = RequestParserConfig::builder()
+            .body_terminator(*b"\r\n.\r\n")
+            .build();
+        let data = concat!(
+            "POST /a HTTP/1.1\r\nHost: localhost\r\n\r\n",
+            "line one\r\n.line two\r\n.\r\n",
+            "POST /b HTTP/1.1\r\nHost: localhost\r\nContent-Length: 2\r\n\r\nok",
+        );
+
+        let mut reader = RequestReader::with_config(ChunkReader::new(data, 3), config.clone());
+        let req = reader.next_request().unwrap().unwrap();
+        assert_eq!(req.body, b"line one\r\n.line two");
+        let req = reader.next_request().unwrap().unwrap();
+        assert_eq!(req.body, b"ok");
+
+        let mut reader = RequestReader::with_config(ChunkReader::new(data, 3), config);
+        let mut req = reader.next_request_head().unwrap().unwrap();
+        let mut body = Vec::new();
+        req.read_body_with(&mut reader, |data| body.extend_from_slice(data))
+            .unwrap();
+        assert_eq!(body, b"line one\r\n.line two");
+        let req = reader.next_request().unwrap().unwrap();
+        assert_eq!(req.request_line.unwrap().request_target, "/b");
+    }
+
+    #[test]
+    fn test_reset_and_reuse() {
+        let data = concat!(
+            "POST /first?a=1 HTTP/1.1\r\nHost: one\r\nX-Only-First: yes\r\n",
+            "Transfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n",
+            "GET /second HTTP/1.0\r\nHost: two\r\n\r\n",
+        );
+        let mut reader = RequestReader::new(ChunkReader::new(data, 7));
+        let mut req = Request::default();
+
+        assert!(reader.next_request_into(&mut req).unwrap());
+        assert_eq!(req.body, b"hello");
+        assert_eq!(req.headers.get("x-only-first"), Some(&"yes".to_string()));
+        let capacity = req.body.capacity();
+
+        assert!(reader.next_request_into(&mut req).unwrap());
+        let line = req.request_line.as_ref().unwrap();
+        assert_eq!(line.method, Method::Get);
+        assert_eq!(line.request_target, "/second");
+        assert_eq!(line.http_version, "1.0");
+        assert_eq!(req.headers.names(), vec!["host"]);
+        assert_eq!(req.host(), Some("two"));
+        assert!(req.body.is_empty());
+        assert_eq!(req.body.capacity(), capacity);
+
+        assert!(!reader.next_request_into(&mut req).unwrap());
+    }
+
+    #[test]
+    fn test_reads_split_at_awkward_points() {
+        let data = "GET / HTTP/1.1\r\nHost: localhost\r\nX-Long: value\r\n\r\n";
+        let colon = data.find(": localhost").unwrap();
+        let before_crlf = data.find("\r\nX-Long").unwrap();
+        let end = data.len();
+
+        for splits in [
+            // At the colon, and just after it.
+            vec![colon, 1, 1],
+            // Just before CRLF, and between CR and LF.
+            vec![before_crlf, 1, 1],
+            // Whitespace-only reads inside the header section.
+            vec![colon + 1, 1],
+            // Before the final CRLF and between its CR and LF.
+            vec![end - 2, 1, 1],
+        ] {
+            let req = request_from_reader(ChunkReader::scripted(data, &splits)).unwrap();
+            assert_eq!(req.host(), Some("localhost"), "{splits:?}");
+            assert_eq!(
+                req.headers.get("x-long"),
+                Some(&"value".to_string()),
+                "{splits:?}"
+            );
+            assert_eq!(req.headers.names(), vec!["host", "x-long"]);
+        }
+    }
+
+    #[test]
+    fn test_partial_line_consumes_nothing() {
+        let config = RequestParserConfig::default();
+        let mut req = Request::new();
+
+        assert_eq!(
+            req.parse(b"GET / HTTP/1.1\r\nHost", &config, true).unwrap(),
+            16
+        );
+        assert_eq!(req.parse(b"Host", &config, true).unwrap(), 0);
+        assert_eq!(req.parse(b"Host:", &config, true).unwrap(), 0);
+        assert_eq!(req.parse(b"Host: x\r", &config, true).unwrap(), 0);
+        assert_eq!(req.parse(b"Host: x\r\n\r", &config, true).unwrap(), 9);
+        assert_eq!(req.parse(b"\r", &config, true).unwrap(), 0);
+        assert_eq!(req.parse(b"\r\n", &config, true).unwrap(), 2);
+        assert!(matches!(req.state, RequestState::Done));
+        assert_eq!(req.host(), Some("x"));
+    }
+
+    #[test]
+    fn test_body_fully_read() {
+        let data = "POST / HTTP/1.1\r\nContent-Length: 11\r\n\r\nhello world";
+
+        let req = request_from_reader(ChunkReader::new(data, 4)).unwrap();
+        assert!(req.body_fully_read());
+
+        let mut reader = RequestReader::new(ChunkReader::new(data, 4));
+        let mut req = reader.next_request_head().unwrap().unwrap();
+        assert!(!req.body_fully_read());
+        req.drain_body(&mut reader).unwrap();
+        assert!(req.body_fully_read());
+
+        // The stream ends partway through the body.
+        let mut reader = RequestReader::new(ChunkReader::new(&data[..data.len() - 3], 4));
+        let mut req = reader.next_request_head().unwrap().unwrap();
+        assert!(req.read_body_with(&mut reader, |_| {}).is_err());
+        assert!(!req.body_fully_read());
+    }
+
+    #[test]
+    fn test_canonical_headers_string() {
+        let a = parse_request(
+            b"GET / HTTP/1.1\r\nHost: example.com\r\nX-Amz-Date: 20260101T000000Z\r\nSet-Cookie: a=1\r\nContent-Type:  text/plain;   charset=utf-8 \r\nSet-Cookie: b=2\r\n\r\n",
+        )
+        .unwrap();
+        let b = parse_request(
+            b"GET / HTTP/1.1\r\ncontent-type: text/plain; charset=utf-8\r\nset-cookie: a=1\r\nx-amz-date: 20260101T000000Z\r\nset-cookie: b=2\r\nHOST: example.com\r\n\r\n",
+        )
+        .unwrap();
+
+        let expected = concat!(
+            "content-type:text/plain; charset=utf-8\n",
+            "host:example.com\n",
+            "set-cookie:a=1,b=2\n",
+            "x-amz-date:20260101T000000Z\n",
+        );
+        assert_eq!(a.canonical_headers_string(), expected);
+        assert_eq!(b.canonical_headers_string(), expected);
+    }
+
+    #[test]
+    fn test_head_then_lazy_body() {
+        let data = concat!(
+            "POST /upload HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n",
+            "6\r\nhello \r\n5\r\nworld\r\n0\r\n\r\n",
+            "GET /next HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        );
+
+        let (head, mut body) = request_head_from_reader(ChunkReader::new(data, 5)).unwrap();
+        assert_eq!(head.request_line.request_target, "/upload");
+        assert_eq!(head.headers.get("host"), Some(&"localhost".to_string()));
+
+        let mut buf = [0u8; 4];
+        let n = body.read(&mut buf).unwrap();
+        assert_eq!(&buf[..n], &b"hello world"[..n]);
+
+        let mut rest = Vec::new();
+        body.read_to_end(&mut rest).unwrap();
+        assert_eq!([&buf[..n], &rest[..]].concat(), b"hello world");
+
+        let next = body.into_inner().next_request().unwrap().unwrap();
+        assert_eq!(next.request_line.unwrap().request_target, "/next");
+
+        let data = "PUT / HTTP/1.1\r\nContent-Length: 5\r\n\r\nabc";
+        let (_, mut body) = request_head_from_reader(ChunkReader::new(data, 2)).unwrap();
+        let mut out = Vec::new();
+        assert!(body.read_to_end(&mut out).is_err());
+        assert_eq!(out, b"abc");
+    }
+
+    #[test]
+    fn test_bytes_needed_hint() {
+        let config = RequestParserConfig::default();
+        let mut req = Request::new();
+
+        let head = b"POST / HTTP/1.1\r\nContent-Length: 10\r\n";
+        req.parse(head, &config, true).unwrap();
+        assert_eq!(req.bytes_needed_hint(), None);
+
+        req.parse(b"\r\nhel", &config, true).unwrap();
+        assert_eq!(req.bytes_needed_hint(), Some(7));
+        req.parse(b"lo wor", &config, true).unwrap();
+        assert_eq!(req.bytes_needed_hint(), Some(1));
+        req.parse(b"d", &config, true).unwrap();
+        assert_eq!(req.bytes_needed_hint(), Some(0));
+
+        let mut req = Request::new();
+        let data = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\na\r\nabc";
+        req.parse(data, &config, true).unwrap();
+        assert_eq!(req.bytes_needed_hint(), Some(7));
+    }
+
+    #[test]
+    fn test_malformed_header_reports_offset() {
+        let reader = ChunkReader::new(
+            concat!(
+                "GET / HTTP/1.1\r\n",
+                "Host: localhost:42069\r\n",
+                "Bad Header\r\n",
+                "\r\n",
+            ),
+            5,
+        );
+
+        let err = request_from_reader(reader).unwrap_err();
+        assert!(matches!(
+            err,
+            RequestError::MalformedHeader { offset: 39, .. }
+        ));
+        assert_eq!(err.offset(), Some(39));
+        assert!(err.to_string().starts_with("malformed header at offset 39"));
+    }
+
+    #[test]
+    fn test_malformed_request_line_reports_offset() {
+        let reader = ChunkReader::new("BREW /pot HTTP/1.1\r\n\r\n", 3);
+
+        let err = request_from_reader(reader).unwrap_err();
+        assert!(matches!(
+            err,
+            RequestError::MalformedRequestLine {
+                offset: 0,
+                reason: "unsupported method"
+            }
+        ));
+    }
+
+    #[test]
+    fn test_raw_request_line() {
+        let reader = ChunkReader::new("GET /coffee HTTP/1.1\r\nHost: localhost\r\n\r\n", 6);
+
+        let r = request_from_reader(reader).unwrap();
+        let line = r.request_line.expect("request line should be parsed");
+        let rebuilt = format!(
+            "{} {} HTTP/{}",
+            line.method, line.request_target, line.http_version
+        );
+        assert_eq!(line.raw(), rebuilt);
+    }
+
+    #[test]
+    fn test_raw_request_line_keeps_unusual_spacing() {
+        let reader = ChunkReader::new("GET   /coffee\tHTTP/1.1\r\nHost: localhost\r\n\r\n", 6);
+
+        let r = request_from_reader(reader).unwrap();
+        let line = r.request_line.expect("request line should be parsed");
+        assert_eq!(line.request_target, "/coffee");
+        assert_eq!(line.raw(), "GET   /coffee\tHTTP/1.1");
+    }
+
+    #[test]
+    fn test_bare_lf_request_in_lenient_mode() {
+        let reader = ChunkReader::new("GET /coffee HTTP/1.1\nHost: localhost\n\n", 4);
+
+        let r = request_from_reader(reader).unwrap();
+        assert!(matches!(r.state, RequestState::Done));
+        assert_eq!(r.host(), Some("localhost"));
+
+        let line = r.request_line.expect("request line should be parsed");
+        assert_eq!(line.request_target, "/coffee");
+    }
+
+    #[test]
+    fn test_bare_lf_request_in_strict_mode() {
+        let reader = ChunkReader::new("GET /coffee HTTP/1.1\nHost: localhost\n\n", 4);
+        let config = RequestParserConfig::builder().strict(true).build();
+
+        let err = request_from_reader_with_config(reader, config).unwrap_err();
+        assert!(matches!(
+            err,
+            RequestError::MalformedRequestLine {
+                reason: "bare LF line ending",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_drain_body_then_parse_pipelined_request() {
+        let reader = ChunkReader::new(
+            concat!(
+                "POST /upload HTTP/1.1\r\n",
+                "Host: localhost:42069\r\n",
+                "Content-Length: 26\r\n",
+                "\r\n",
+                "abcdefghijklmnopqrstuvwxyz",
+                "GET /after HTTP/1.1\r\n",
+                "Host: localhost:42069\r\n",
+                "\r\n",
+            ),
+            7,
+        );
+        let mut reader = RequestReader::new(reader);
+
+        let mut first = reader.next_request_head().unwrap().expect("first request");
+        assert!(matches!(first.state, RequestState::ParsingBody));
+        assert!(first.body.is_empty());
+
+        first.drain_body(&mut reader).unwrap();
+        assert!(matches!(first.state, RequestState::Done));
+
+        let second = reader.next_request().unwrap().expect("second request");
+        let line = second.request_line.expect("request line should be parsed");
+        assert_eq!(line.request_target, "/after");
+    }
+
+    #[test]
+    fn test_drain_body_fails_on_truncated_body() {
+        let reader = ChunkReader::new("POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nabc", 64);
+        let mut reader = RequestReader::new(reader);
+
+        let mut req = reader.next_request_head().unwrap().expect("request");
+        assert!(req.drain_body(&mut reader).is_err());
+    }
+
+    #[test]
+    fn test_too_many_headers() {
+        let reader = ChunkReader::new("GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n", 8);
+        let config = RequestParserConfig::builder().max_header_count(2).build();
+
+        let err = request_from_reader_with_config(reader, config).unwrap_err();
+        assert!(matches!(err, RequestError::TooManyHeaders));
+    }
+
+    #[test]
+    fn test_unterminated_header_hits_size_limit() {
+        let data = format!("GET / HTTP/1.1\r\nX-Big: {}", "a".repeat(4096));
+        let reader = ChunkReader::new(&data, 512);
+        let config = RequestParserConfig::builder()
+            .max_header_bytes(1024)
+            .build();
+
+        let err = request_from_reader_with_config(reader, config).unwrap_err();
+        assert!(matches!(err, RequestError::HeadersTooLarge));
+    }
+
+    #[test]
+    fn test_request_line_too_long() {
+        let data = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(64));
+        let reader = ChunkReader::new(&data, 8);
+        let config = RequestParserConfig::builder()
+            .max_request_line_len(32)
+            .build();
+
+        let err = request_from_reader_with_config(reader, config).unwrap_err();
+        assert!(matches!(err, RequestError::RequestLineTooLong));
+    }
+
+    #[test]
+    fn test_content_length_digits_only() {
+        for value in ["+3", "-3", "3 3", "0x3", "", "3, 4"] {
+            let data = format!("POST / HTTP/1.1\r\nContent-Length: {value}\r\n\r\nabc");
+            let err = parse_request(data.as_bytes()).unwrap_err();
+            assert!(
+                matches!(err, RequestError::InvalidContentLength),
+                "{value:?}"
+            );
+        }
+
+        let r = parse_request(b"POST / HTTP/1.1\r\nContent-Length: 3, 3\r\n\r\nabc").unwrap();
+        assert_eq!(r.body, b"abc");
+
+        let config = RequestParserConfig::builder()
+            .merge_policy("content-length", MergePolicy::CommaList)
+            .build();
+        let data = "POST / HTTP/1.1\r\nContent-Length: 3\r\nContent-Length: 4\r\n\r\nabcd";
+        let err = request_from_reader_with_config(data.as_bytes(), config).unwrap_err();
+        assert!(matches!(err, RequestError::InvalidContentLength));
+    }
+
+    #[test]
+    fn test_body_over_max_size() {
+        let reader = ChunkReader::new(
+            "POST / HTTP/1.1\r\nContent-Length: 11\r\n\r\nhello world",
+            8,
+        );
+        let config = RequestParserConfig::builder().max_body_size(10).build();
+
+        let err = request_from_reader_with_config(reader, config).unwrap_err();
+        assert!(matches!(err, RequestError::BodyTooLarge));
+    }
+
+    #[test]
+    fn test_chunked_body() {
+        let reader = ChunkReader::new(
+            concat!(
+                "POST /upload HTTP/1.1\r\n",
+                "Host: localhost:42069\r\n",
+                "Transfer-Encoding: chunked\r\n",
+                "\r\n",
+                "8\r\n",
+                "Hello, W\r\n",
+                "5\r\n",
+                "orld!\r\n",
+                "0\r\n",
+                "\r\n",
+            ),
+            3,
+        );
+
+        let r = request_from_reader(reader).unwrap();
+        assert!(matches!(r.state, RequestState::Done));
+        assert_eq!(r.body, b"Hello, World!");
+    }
+
+    #[test]
+    fn test_zero_length_chunked_body() {
+        let head = "POST /empty HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n";
+        let data = format!("{head}0\r\n\r\n");
+
+        // The reader hits EOF right after the last chunk, so this only
+        // passes if the body completes without asking for more.
+        for chunk_size in [1, 5, data.len()] {
+            let r = request_from_reader(ChunkReader::new(&data, chunk_size)).unwrap();
+            assert!(matches!(r.state, RequestState::Done), "{chunk_size}");
+            assert!(r.body.is_empty(), "{chunk_size}");
+        }
+
+        let pipelined = format!("{data}GET /next HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let mut reader = RequestReader::new(ChunkReader::new(&pipelined, 7));
+        let r = reader.next_request().unwrap().unwrap();
+        assert!(r.body.is_empty());
+        let next = reader.next_request().unwrap().unwrap();
+        assert_eq!(next.request_line.unwrap().request_target, "/next");
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip_then_chunked_body() {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"compressed payload").unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        let mut data = b"POST / HTTP/1.1\r\nTransfer-Encoding: gzip, chunked\r\n\r\n".to_vec();
+        data.extend_from_slice(format!("{:x}\r\n", gzipped.len()).as_bytes());
+        data.extend_from_slice(&gzipped);
+        data.extend_from_slice(b"\r\n0\r\n\r\n");
+
+        let r = request_from_reader(&data[..]).unwrap();
+        assert_eq!(r.body, b"compressed payload");
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip_bomb_is_capped() {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(&vec![0; 1024 * 1024]).unwrap();
+        let gzipped = encoder.finish().unwrap();
+        assert!(gzipped.len() < 4096);
+
+        let mut data = b"POST / HTTP/1.1\r\nTransfer-Encoding: gzip, chunked\r\n\r\n".to_vec();
+        data.extend_from_slice(format!("{:x}\r\n", gzipped.len()).as_bytes());
+        data.extend_from_slice(&gzipped);
+        data.extend_from_slice(b"\r\n0\r\n\r\n");
+
+        let config = RequestParserConfig::builder()
+            .max_body_size(64 * 1024)
+            .build();
+        let err = request_from_reader_with_config(&data[..], config).unwrap_err();
+        assert!(matches!(err, RequestError::BodyTooLarge));
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_decoded_body_bomb_is_capped() {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(&vec![0; 1024 * 1024]).unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        let mut data = format!(
+            "POST / HTTP/1.1\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n",
+            gzipped.len()
+        )
+        .into_bytes();
+        data.extend_from_slice(&gzipped);
+
+        let config = RequestParserConfig::builder()
+            .max_body_size(64 * 1024)
+            .build();
+        let r = request_from_reader_with_config(&data[..], config).unwrap();
+        assert!(matches!(r.decoded_body(), Err(RequestError::BodyTooLarge)));
+
+        let r = request_from_reader(&data[..]).unwrap();
+        assert_eq!(r.decoded_body().unwrap().len(), 1024 * 1024);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_decoded_body_gzip() {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"compressed payload").unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        let mut data = format!(
+            "POST / HTTP/1.1\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n",
+            gzipped.len()
+        )
+        .into_bytes();
+        data.extend_from_slice(&gzipped);
+
+        let r = request_from_reader(&data[..]).unwrap();
+        assert_eq!(r.body, gzipped);
+        assert_eq!(r.decoded_body().unwrap(), b"compressed payload");
+    }
+
+    #[test]
+    fn test_decoded_body_identity_and_unknown() {
+        let r = parse_request(
+            b"POST / HTTP/1.1\r\nContent-Encoding: identity\r\nContent-Length: 2\r\n\r\nhi",
+        )
+        .unwrap();
+        assert_eq!(r.decoded_body().unwrap(), b"hi");
+
+        let r = parse_request(
+            b"POST / HTTP/1.1\r\nContent-Encoding: br\r\nContent-Length: 2\r\n\r\nhi",
+        )
+        .unwrap();
+        assert!(matches!(
+            r.decoded_body(),
+            Err(RequestError::UnsupportedCoding(c)) if c == "br"
+        ));
+    }
+
+    #[cfg(not(feature = "gzip"))]
+    #[test]
+    fn test_gzip_then_chunked_body_needs_feature() {
+        let reader = ChunkReader::new(
+            "POST / HTTP/1.1\r\nTransfer-Encoding: gzip, chunked\r\n\r\n0\r\n\r\n",
+            8,
+        );
+
+        let err = request_from_reader(reader).unwrap_err();
+        assert!(matches!(err, RequestError::UnsupportedCoding(c) if c == "gzip"));
+    }
+
+    #[test]
+    fn test_chunked_must_be_last() {
+        let reader = ChunkReader::new(
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked, gzip\r\n\r\n0\r\n\r\n",
+            8,
+        );
+
+        let err = request_from_reader(reader).unwrap_err();
+        assert!(matches!(err, RequestError::InvalidTransferEncoding));
+    }
+
+    #[test]
+    fn test_transfer_encoding_with_content_length() {
+        let data = concat!(
+            "POST / HTTP/1.1\r\nHost: localhost\r\n",
+            "Content-Length: 3\r\nTransfer-Encoding: chunked\r\n\r\n",
+            "5\r\nhello\r\n0\r\n\r\n",
+        );
+
+        let r = request_from_reader(ChunkReader::new(data, 8)).unwrap();
+        assert_eq!(r.body, b"hello");
+        assert_eq!(r.headers.get("content-length"), None);
+        assert!(r.wants_close());
+
+        let config = RequestParserConfig::builder().strict(true).build();
+        let err = request_from_reader_with_config(ChunkReader::new(data, 8), config).unwrap_err();
+        assert!(matches!(err, RequestError::ConflictingFraming));
+    }
+
+    #[test]
+    fn test_drain_chunked_body_then_parse_pipelined_request() {
+        let reader = ChunkReader::new(
+            concat!(
+                "POST / HTTP/1.1\r\n",
+                "Transfer-Encoding: chunked\r\n",
+                "\r\n",
+                "4\r\nWiki\r\n0\r\n\r\n",
+                "GET /after HTTP/1.1\r\n",
+                "\r\n",
+            ),
+            5,
+        );
+        let mut reader = RequestReader::new(reader);
+
+        let mut first = reader.next_request_head().unwrap().expect("first request");
+        first.drain_body(&mut reader).unwrap();
+
+        let second = reader.next_request().unwrap().expect("second request");
+        assert_eq!(second.request_line.unwrap().request_target, "/after");
+    }
+
+    #[test]
+    fn test_cloned_request_is_independent() {
+        let reader = ChunkReader::new(
+            "POST /items HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nhello",
+            4,
+        );
+        let original = request_from_reader(reader).unwrap();
+
+        let mut copy = original.clone();
+        copy.headers.set("X-Mirror", "1");
+        copy.body.extend_from_slice(b" world");
+        copy.request_line.as_mut().unwrap().request_target = "/mirror".to_string();
+
+        assert_eq!(original.body, b"hello");
+        assert!(original.headers.get("x-mirror").is_none());
+        assert_eq!(original.request_line.unwrap().request_target, "/items");
+        assert_eq!(copy.body, b"hello world");
+    }
+
+    #[test]
+    fn test_leading_empty_line_skipped_when_lenient() {
+        let reader = ChunkReader::new("\r\nGET / HTTP/1.1\r\nHost: localhost\r\n\r\n", 3);
+
+        let req = request_from_reader(reader).unwrap();
+        assert_eq!(req.request_line.unwrap().request_target, "/");
+    }
+
+    #[test]
+    fn test_leading_empty_line_rejected_when_strict() {
+        let reader = ChunkReader::new("\r\nGET / HTTP/1.1\r\nHost: localhost\r\n\r\n", 3);
+        let config = RequestParserConfig::builder().strict(true).build();
+
+        let err = request_from_reader_with_config(reader, config).unwrap_err();
+        assert!(matches!(
+            err,
+            RequestError::MalformedRequestLine {
+                offset: 0,
+                reason: "empty line before request line"
+            }
+        ));
+    }
+
+    #[test]
+    fn test_too_many_leading_empty_lines_rejected() {
+        let data = format!("{}GET / HTTP/1.1\r\n\r\n", "\r\n".repeat(5));
+
+        let err = request_from_reader(ChunkReader::new(&data, 8)).unwrap_err();
+        assert!(matches!(err, RequestError::MalformedRequestLine { .. }));
+    }
+
+    #[test]
+    fn test_parse_request_rejects_adversarial_input() {
+        let huge_header = format!("GET / HTTP/1.1\r\nX: {}\r\n\r\n", "a".repeat(1 << 20));
+        let inputs: Vec<&[u8]> = vec![
+            b"",
+            b"GET / HTTP/1.1\r\nHost: local",
+            b"\x00\xff\xfe\r\n\r\n",
+            b"GET /\xff HTTP/1.1\r\n\r\n",
+            b"POST / HTTP/1.1\r\nContent-Length: 18446744073709551616\r\n\r\n",
+            b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nab",
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nffffffffffffffffff\r\n",
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhelloXX",
+            b"\r\n\r\n\r\n\r\n\r\n\r\n",
+            huge_header.as_bytes(),
+        ];
+
+        for input in inputs {
+            assert!(
+                parse_request(input).is_err(),
+                "{:?}",
+                &input[..input.len().min(64)]
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_request_survives_mutated_input() {
+        let seed: &[u8] = concat!(
+            "POST /upload?x=1 HTTP/1.1\r\n",
+            "Host: localhost\r\n",
+            "Transfer-Encoding: gzip, chunked\r\n",
+            "\r\n",
+            "5;a=b\r\nhello\r\n0\r\nTrailer: x\r\n\r\n",
+        )
+        .as_bytes();
+
+        // Deterministic xorshift, so a failure is reproducible.
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..2000 {
+            let mut input = seed.to_vec();
+            for _ in 0..next() % 8 {
+                let i = next() as usize % input.len();
+                match next() % 3 {
+                    0 => input[i] = next() as u8,
+                    1 => input.truncate(i),
+                    _ => input.insert(i, b"\r\n:;0 "[next() as usize % 6]),
+                }
+                if input.is_empty() {
+                    break;
+                }
+            }
+
+            let _ = parse_request(&input);
+        }
+    }
+
+    #[test]
+    fn test_read_body_with_callback() {
+        let reader = ChunkReader::new(
+            concat!(
+                "POST /a HTTP/1.1\r\nContent-Length: 11\r\n\r\n",
+                "hello world",
+                "POST /b HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n",
+                "6\r\nhello \r\n5;x=y\r\nworld\r\n0\r\n\r\n",
+            ),
+            3,
+        );
+        let mut reader = RequestReader::new(reader);
+
+        for target in ["/a", "/b"] {
+            let mut req = reader.next_request_head().unwrap().expect("request");
+            assert_eq!(req.request_line.as_ref().unwrap().request_target, target);
+
+            let mut segments = 0;
+            let mut checksum = 0u32;
+            let mut streamed = Vec::new();
+            req.read_body_with(&mut reader, |data| {
+                segments += 1;
+                checksum = data
+                    .iter()
+                    .fold(checksum, |sum, &b| sum.rotate_left(5) ^ b as u32);
+                streamed.extend_from_slice(data);
+            })
+            .unwrap();
+
+            assert!(segments > 1, "body arrived in one piece");
+            assert_eq!(streamed, b"hello world");
+            assert_eq!(
+                checksum,
+                b"hello world"
+                    .iter()
+                    .fold(0u32, |sum, &b| sum.rotate_left(5) ^ b as u32)
+            );
+            assert!(req.body.is_empty());
+        }
+    }
 }
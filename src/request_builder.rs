@@ -0,0 +1,108 @@
+use crate::method::Method;
+
+/// Assembles a wire-format request, for tests that would otherwise spell
+/// out raw request bytes.
+///
+/// Headers are written in the order they were added. A `Content-Length` is
+/// added for a non-empty body unless the request already sets
+/// `Content-Length` or `Transfer-Encoding`.
+#[derive(Debug, Clone)]
+pub struct RequestBuilder {
+    method: Method,
+    target: String,
+    version: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl RequestBuilder {
+    pub fn new(method: Method, target: &str) -> Self {
+        Self {
+            method,
+            target: target.to_string(),
+            version: "1.1".to_string(),
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// The version after `HTTP/`, `1.1` by default.
+    pub fn version(mut self, version: &str) -> Self {
+        self.version = version.to_string();
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let mut out = format!("{} {} HTTP/{}\r\n", self.method, self.target, self.version);
+
+        for (name, value) in &self.headers {
+            out.push_str(&format!("{name}: {value}\r\n"));
+        }
+
+        let has_framing = self.headers.iter().any(|(name, _)| {
+            name.eq_ignore_ascii_case("content-length")
+                || name.eq_ignore_ascii_case("transfer-encoding")
+        });
+        if !self.body.is_empty() && !has_framing {
+            out.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        }
+
+        out.push_str("\r\n");
+
+        let mut out = out.into_bytes();
+        out.extend_from_slice(&self.body);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chunk_reader::ChunkReader,
+        method::Method,
+        request::{parse_request, request_from_reader},
+        request_builder::RequestBuilder,
+    };
+
+    #[test]
+    fn test_built_request_parses_back() {
+        let data = RequestBuilder::new(Method::Post, "/items?x=1")
+            .header("Host", "localhost")
+            .header("Content-Type", "application/json")
+            .body(r#"{"a":1}"#)
+            .build();
+
+        let req = request_from_reader(ChunkReader::from_bytes(&data, 3)).unwrap();
+        let line = req.request_line.as_ref().unwrap();
+        assert_eq!(line.method, Method::Post);
+        assert_eq!(line.request_target, "/items?x=1");
+        assert_eq!(line.http_version, "1.1");
+        assert_eq!(req.host(), Some("localhost"));
+        assert_eq!(req.headers.get("content-length"), Some(&"7".to_string()));
+        assert_eq!(req.body, br#"{"a":1}"#);
+    }
+
+    #[test]
+    fn test_explicit_framing_is_kept() {
+        let data = RequestBuilder::new(Method::Put, "/")
+            .version("1.0")
+            .header("Transfer-Encoding", "chunked")
+            .body("3\r\nabc\r\n0\r\n\r\n")
+            .build();
+
+        let req = parse_request(&data).unwrap();
+        assert_eq!(req.request_line.as_ref().unwrap().http_version, "1.0");
+        assert!(req.headers.get("content-length").is_none());
+        assert_eq!(req.body, b"abc");
+    }
+}
@@ -0,0 +1,799 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    net::TcpStream,
+    sync::{Arc, Mutex, mpsc::Receiver},
+};
+
+use crate::{
+    file::send_file_range,
+    headers::{Headers, is_header_safe},
+    method::Method,
+    upgrade::{OnUpgrade, Upgraded},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusCode {
+    SwitchingProtocols,
+    EarlyHints,
+    Ok,
+    NoContent,
+    PartialContent,
+    NotModified,
+    BadRequest,
+    NotFound,
+    MethodNotAllowed,
+    PayloadTooLarge,
+    UriTooLong,
+    UnsupportedMediaType,
+    RangeNotSatisfiable,
+    RequestHeaderFieldsTooLarge,
+    InternalServerError,
+    NotImplemented,
+    HttpVersionNotSupported,
+}
+
+impl StatusCode {
+    pub fn code(self) -> u16 {
+        match self {
+            StatusCode::SwitchingProtocols => 101,
+            StatusCode::EarlyHints => 103,
+            StatusCode::Ok => 200,
+            StatusCode::NoContent => 204,
+            StatusCode::PartialContent => 206,
+            StatusCode::NotModified => 304,
+            StatusCode::BadRequest => 400,
+            StatusCode::NotFound => 404,
+            StatusCode::MethodNotAllowed => 405,
+            StatusCode::PayloadTooLarge => 413,
+            StatusCode::UriTooLong => 414,
+            StatusCode::UnsupportedMediaType => 415,
+            StatusCode::RangeNotSatisfiable => 416,
+            StatusCode::RequestHeaderFieldsTooLarge => 431,
+            StatusCode::InternalServerError => 500,
+            StatusCode::NotImplemented => 501,
+            StatusCode::HttpVersionNotSupported => 505,
+        }
+    }
+
+    /// Whether a response with this status may carry a body. Informational
+    /// responses, `204` and `304` never do.
+    pub fn allows_body(self) -> bool {
+        !matches!(self.code(), 100..=199 | 204 | 304)
+    }
+
+    pub fn reason(self) -> &'static str {
+        match self {
+            StatusCode::SwitchingProtocols => "Switching Protocols",
+            StatusCode::EarlyHints => "Early Hints",
+            StatusCode::Ok => "OK",
+            StatusCode::NoContent => "No Content",
+            StatusCode::PartialContent => "Partial Content",
+            StatusCode::NotModified => "Not Modified",
+            StatusCode::BadRequest => "Bad Request",
+            StatusCode::NotFound => "Not Found",
+            StatusCode::MethodNotAllowed => "Method Not Allowed",
+            StatusCode::PayloadTooLarge => "Payload Too Large",
+            StatusCode::UriTooLong => "URI Too Long",
+            StatusCode::UnsupportedMediaType => "Unsupported Media Type",
+            StatusCode::RangeNotSatisfiable => "Range Not Satisfiable",
+            StatusCode::RequestHeaderFieldsTooLarge => "Request Header Fields Too Large",
+            StatusCode::InternalServerError => "Internal Server Error",
+            StatusCode::NotImplemented => "Not Implemented",
+            StatusCode::HttpVersionNotSupported => "HTTP Version Not Supported",
+        }
+    }
+}
+
+/// Upper bounds a response must stay within to be written, to catch a
+/// handler gone wrong before it floods the connection. `None` means no
+/// limit, the default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResponseLimits {
+    /// Header lines, counting each value kept apart separately.
+    pub max_header_count: Option<usize>,
+    /// Bytes of the header section as written, line endings included.
+    pub max_header_bytes: Option<usize>,
+    /// Bytes of an inline body. Streamed bodies aren't checked.
+    pub max_body_size: Option<usize>,
+}
+
+/// The line ending [`Response::write_to`] and friends emit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\r\n`, as HTTP requires.
+    #[default]
+    CrLf,
+    /// A bare `\n`. Not valid HTTP and most clients will reject it; only for
+    /// readable output when debugging or in tests.
+    Lf,
+}
+
+impl LineEnding {
+    fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Lf => "\n",
+        }
+    }
+}
+
+/// The chunks of a streamed body, produced on the thread writing the
+/// response.
+pub(crate) struct BodyStream(Box<dyn Iterator<Item = Vec<u8>> + Send>);
+
+impl Iterator for BodyStream {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        self.0.next()
+    }
+}
+
+impl fmt::Debug for BodyStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BodyStream")
+    }
+}
+
+/// A region of a file sent as the body, see [`Response::with_file_body`].
+#[derive(Debug)]
+struct FileBody {
+    file: File,
+    offset: u64,
+    len: u64,
+}
+
+/// Where the server writes a response. Sockets take file bodies with
+/// [`send_file`](crate::file::send_file); anything else gets a copy.
+pub(crate) trait BodyWriter: Write {
+    fn write_file(&mut self, file: &mut File, len: u64) -> io::Result<u64> {
+        io::copy(&mut file.take(len), self)
+    }
+}
+
+impl<W: Write + ?Sized> BodyWriter for &mut W {}
+
+impl BodyWriter for &TcpStream {
+    fn write_file(&mut self, file: &mut File, len: u64) -> io::Result<u64> {
+        send_file_range(file, len, self)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: StatusCode,
+    pub headers: Headers,
+    pub body: Vec<u8>,
+    /// Interim responses sent ahead of this one, see [`Response::with_early_hints`].
+    early_hints: Vec<Response>,
+    /// Body chunks still to come, see [`Response::with_body_iter`]. Shared so
+    /// the response stays `Clone`; whichever copy is written drains it.
+    stream: Option<Arc<Mutex<BodyStream>>>,
+    /// A file sent in place of `body`, see [`Response::with_file_body`].
+    /// Shared like `stream`; each write sends the whole region again.
+    file: Option<Arc<Mutex<FileBody>>>,
+    /// Takes over the connection after a `101`, see [`Response::on_upgrade`].
+    on_upgrade: Option<OnUpgrade>,
+    limits: ResponseLimits,
+    line_ending: LineEnding,
+}
+
+impl Response {
+    pub fn new(status: StatusCode) -> Self {
+        let mut headers = Headers::new();
+        headers.set("Content-Length", "0");
+
+        Self {
+            status,
+            headers,
+            body: Vec::new(),
+            early_hints: Vec::new(),
+            stream: None,
+            file: None,
+            on_upgrade: None,
+            limits: ResponseLimits::default(),
+            line_ending: LineEnding::default(),
+        }
+    }
+
+    /// A `103 Early Hints` interim response carrying one `Link` header per
+    /// entry of `links`, e.g. `</style.css>; rel=preload; as=style`.
+    pub fn early_hints(links: &[&str]) -> Self {
+        let mut res = Response::new(StatusCode::EarlyHints);
+        res.headers.remove("Content-Length");
+        for link in links {
+            res.headers.append("Link", link);
+        }
+        res
+    }
+
+    /// Queues `hints` to be written before this response. The server only
+    /// sends them to HTTP/1.1 clients, since HTTP/1.0 has no interim
+    /// responses.
+    pub fn with_early_hints(mut self, hints: Response) -> Self {
+        self.early_hints.push(hints);
+        self
+    }
+
+    /// Takes the queued early hints out of the response.
+    pub fn take_early_hints(&mut self) -> Vec<Response> {
+        std::mem::take(&mut self.early_hints)
+    }
+
+    /// Runs `f` with the connection once this response, normally a `101`,
+    /// has been written, so a handler can speak the protocol it switched
+    /// to. The server stops serving HTTP on that connection and closes it
+    /// when `f` returns.
+    pub fn on_upgrade<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(Upgraded) + Send + 'static,
+    {
+        self.on_upgrade = Some(OnUpgrade::new(f));
+        self
+    }
+
+    pub(crate) fn take_on_upgrade(&mut self) -> Option<OnUpgrade> {
+        self.on_upgrade.take()
+    }
+
+    /// A `405` listing the methods the resource does support in `Allow`.
+    pub fn method_not_allowed(allowed: &[Method]) -> Self {
+        let allow = allowed
+            .iter()
+            .map(|m| m.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        Response::new(StatusCode::MethodNotAllowed)
+            .with_header("Allow", &allow)
+            .with_body("Method Not Allowed")
+    }
+
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.set_body(body.into());
+        self
+    }
+
+    /// Sends the body as it arrives on `chunks`, with chunked encoding, until
+    /// every sender is dropped. Lets another thread produce the body while
+    /// the response is being written. Empty chunks are skipped, since an
+    /// empty chunk would end the body.
+    pub fn with_body_stream(self, chunks: Receiver<Vec<u8>>) -> Self {
+        self.with_body_iter(chunks)
+    }
+
+    /// Sends each chunk `chunks` yields, with chunked encoding. The iterator
+    /// runs on the thread writing the response, as it is written, so
+    /// adapting a stream needs no thread of its own. Empty chunks are
+    /// skipped.
+    pub fn with_body_iter<I>(mut self, chunks: I) -> Self
+    where
+        I: IntoIterator<Item = Vec<u8>>,
+        I::IntoIter: Send + 'static,
+    {
+        self.body.clear();
+        self.file = None;
+        self.headers.remove("Content-Length");
+        self.headers.set("Transfer-Encoding", "chunked");
+        let stream = BodyStream(Box::new(chunks.into_iter()));
+        self.stream = Some(Arc::new(Mutex::new(stream)));
+        self
+    }
+
+    /// Sends `len` bytes of `file`, from `offset` on, as the body, with
+    /// `Content-Length` set to match. Nothing is read into memory up front:
+    /// the server hands the file to the socket with
+    /// [`send_file`](crate::file::send_file).
+    pub fn with_file_body(mut self, file: File, offset: u64, len: u64) -> Self {
+        self.set_body(Vec::new());
+        self.headers.set("Content-Length", &len.to_string());
+        let file = FileBody { file, offset, len };
+        self.file = Some(Arc::new(Mutex::new(file)));
+        self
+    }
+
+    /// The length of the body, whether held in memory or sent from a file.
+    /// `0` for a streamed body, whose length isn't known up front.
+    pub(crate) fn body_len(&self) -> u64 {
+        match &self.file {
+            Some(file) => file.lock().unwrap_or_else(|err| err.into_inner()).len,
+            None => self.body.len() as u64,
+        }
+    }
+
+    /// Takes the body stream out, leaving the response with an empty body
+    /// still framed as chunked. Lets a middleware put a stream of its own in
+    /// its place.
+    pub(crate) fn take_body_stream(&mut self) -> Option<Arc<Mutex<BodyStream>>> {
+        self.stream.take()
+    }
+
+    /// Sends a streamed body unframed, ended by closing the connection, for
+    /// clients that can't parse chunked encoding (HTTP/1.0). Adds
+    /// `Connection: close`; the connection must be closed once it is written.
+    pub fn set_close_delimited(&mut self) {
+        if self.is_streaming() {
+            self.headers.remove("Transfer-Encoding");
+            self.headers.set("Connection", "close");
+        }
+    }
+
+    /// Whether the body comes from [`Response::with_body_stream`] or
+    /// [`Response::with_body_iter`].
+    pub fn is_streaming(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Replaces the body and updates `Content-Length` to match. Any
+    /// `Transfer-Encoding` is dropped so the two framings never coexist.
+    pub fn set_body(&mut self, body: Vec<u8>) {
+        self.body = body;
+        self.stream = None;
+        self.file = None;
+        self.headers.remove("Transfer-Encoding");
+        self.headers
+            .set("Content-Length", &self.body.len().to_string());
+    }
+
+    /// Takes the body out of the response, leaving it empty with a
+    /// `Content-Length` of zero.
+    pub fn take_body(&mut self) -> Vec<u8> {
+        let body = std::mem::take(&mut self.body);
+        self.set_body(Vec::new());
+        body
+    }
+
+    /// Adds `headers` to `Vary`, keeping names already listed (compared
+    /// case-insensitively) only once. A `Vary: *` is left alone since it
+    /// already covers everything.
+    pub fn vary(&mut self, headers: &[&str]) {
+        let mut names: Vec<String> = self
+            .headers
+            .get("Vary")
+            .map(|v| {
+                v.split(',')
+                    .map(|name| name.trim().to_string())
+                    .filter(|name| !name.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if names.iter().any(|name| name == "*") {
+            return;
+        }
+
+        for header in headers {
+            if !names.iter().any(|name| name.eq_ignore_ascii_case(header)) {
+                names.push(header.to_string());
+            }
+        }
+
+        self.headers.set("Vary", &names.join(", "));
+    }
+
+    pub fn with_header(mut self, key: &str, value: &str) -> Self {
+        self.headers.set(key, value);
+        self
+    }
+
+    /// Sets the limits [`Response::write_to`] and friends enforce.
+    pub fn set_limits(&mut self, limits: ResponseLimits) {
+        self.limits = limits;
+    }
+
+    /// Sets the line ending the response is written with. Anything but the
+    /// default [`LineEnding::CrLf`] is non-conformant, for debugging only.
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.line_ending = line_ending;
+    }
+
+    /// Checks the response against its limits, failing with
+    /// `InvalidData` on the first one exceeded.
+    pub fn check_limits(&self) -> io::Result<()> {
+        let too_large = |what: &str, len: usize, max: usize| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("response {what} {len} exceeds the limit of {max}"),
+            )
+        };
+
+        let lines = self.headers.iter();
+        let eol = self.line_ending.as_str().len();
+        let (count, bytes) = lines.fold((0, eol), |(count, bytes), (key, value)| {
+            (count + 1, bytes + key.len() + value.len() + 2 + eol)
+        });
+
+        if let Some(max) = self.limits.max_header_count
+            && count > max
+        {
+            return Err(too_large("header count", count, max));
+        }
+        if let Some(max) = self.limits.max_header_bytes
+            && bytes > max
+        {
+            return Err(too_large("header size", bytes, max));
+        }
+        if let Some(max) = self.limits.max_body_size
+            && self.body.len() > max
+        {
+            return Err(too_large("body size", self.body.len(), max));
+        }
+        Ok(())
+    }
+
+    /// Writes the whole response. For statuses that can't carry a body the
+    /// body and its framing headers are left out, whatever they were set to.
+    pub fn write_to<W: Write>(&self, mut w: &mut W) -> io::Result<()> {
+        self.write(&mut w, false)
+    }
+
+    /// Like [`Response::write_to`], with the headers in name order so the
+    /// same response always serializes to the same bytes, e.g. for snapshot
+    /// tests or cache keys.
+    pub fn write_to_sorted<W: Write>(&self, mut w: &mut W) -> io::Result<()> {
+        self.write(&mut w, true)
+    }
+
+    /// Writes the status line and headers only. Used to answer `HEAD`
+    /// requests, where the headers (including `Content-Length`) must match
+    /// the `GET` response but no body is sent.
+    pub fn write_head_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.write_head(w, false)
+    }
+
+    pub(crate) fn write<W: BodyWriter>(&self, w: &mut W, sorted: bool) -> io::Result<()> {
+        self.check_limits()?;
+        self.write_head(w, sorted)?;
+        if !self.status.allows_body() {
+            return w.flush();
+        }
+        self.write_body(w)
+    }
+
+    /// Writes the body alone, framed as the headers say.
+    pub(crate) fn write_body<W: BodyWriter>(&self, w: &mut W) -> io::Result<()> {
+        let eol = self.line_ending.as_str();
+        match &self.stream {
+            Some(stream) if self.headers.get("transfer-encoding").is_none() => {
+                let mut stream = stream.lock().unwrap_or_else(|err| err.into_inner());
+                for chunk in stream.by_ref() {
+                    w.write_all(&chunk)?;
+                    w.flush()?;
+                }
+            }
+            Some(stream) => {
+                let mut stream = stream.lock().unwrap_or_else(|err| err.into_inner());
+                for chunk in stream.by_ref().filter(|chunk| !chunk.is_empty()) {
+                    write!(w, "{:x}{eol}", chunk.len())?;
+                    w.write_all(&chunk)?;
+                    w.write_all(eol.as_bytes())?;
+                    w.flush()?;
+                }
+                write!(w, "0{eol}{eol}")?;
+            }
+            None => match &self.file {
+                Some(file) => {
+                    let mut body = file.lock().unwrap_or_else(|err| err.into_inner());
+                    let FileBody { file, offset, len } = &mut *body;
+                    file.seek(SeekFrom::Start(*offset))?;
+                    if w.write_file(file, *len)? < *len {
+                        // `Content-Length` has promised bytes that are gone.
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "file shrank while being sent",
+                        ));
+                    }
+                }
+                None => w.write_all(&self.body)?,
+            },
+        }
+        w.flush()
+    }
+
+    fn write_head<W: Write>(&self, w: &mut W, sorted: bool) -> io::Result<()> {
+        self.check_limits()?;
+        let mut headers: Vec<_> = self.headers.iter().collect();
+        if sorted {
+            headers.sort_by_key(|&(key, _)| key);
+        }
+
+        // The map is public, so values can get in without `Headers::set`'s
+        // check.
+        if let Some((key, _)) = headers
+            .iter()
+            .find(|(key, value)| !is_header_safe(key) || !is_header_safe(value))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("header {key:?} has CR, LF or NUL in its name or value"),
+            ));
+        }
+
+        let eol = self.line_ending.as_str();
+        write!(
+            w,
+            "HTTP/1.1 {} {}{eol}",
+            self.status.code(),
+            self.status.reason()
+        )?;
+
+        for (key, value) in headers {
+            let is_framing = key == "content-length" || key == "transfer-encoding";
+            if is_framing && !self.status.allows_body() {
+                continue;
+            }
+
+            write!(w, "{key}: {value}{eol}")?;
+        }
+
+        w.write_all(eol.as_bytes())?;
+        w.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, sync::mpsc, thread};
+
+    use crate::{
+        method::Method,
+        response::{LineEnding, Response, ResponseLimits, StatusCode},
+    };
+
+    #[test]
+    fn test_write_response_with_body() {
+        let res = Response::new(StatusCode::Ok).with_body("hello");
+        let mut out = Vec::new();
+        res.write_to(&mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(out.contains("content-length: 5\r\n"));
+        assert!(out.ends_with("\r\n\r\nhello"));
+    }
+
+    #[test]
+    fn test_empty_response_has_zero_content_length() {
+        let res = Response::new(StatusCode::NotFound);
+
+        assert_eq!(res.headers.get("Content-Length"), Some(&"0".to_string()));
+        assert!(res.body.is_empty());
+    }
+
+    #[test]
+    fn test_set_body_updates_content_length() {
+        let mut res = Response::new(StatusCode::Ok).with_body("hello");
+        res.set_body(b"hello, world".to_vec());
+
+        assert_eq!(res.headers.get("Content-Length"), Some(&"12".to_string()));
+        assert_eq!(res.body, b"hello, world");
+    }
+
+    #[test]
+    fn test_take_body_resets_content_length() {
+        let mut res = Response::new(StatusCode::Ok).with_body("hello");
+        let body = res.take_body();
+
+        assert_eq!(body, b"hello");
+        assert!(res.body.is_empty());
+        assert_eq!(res.headers.get("Content-Length"), Some(&"0".to_string()));
+    }
+
+    #[test]
+    fn test_set_body_drops_transfer_encoding() {
+        let mut res = Response::new(StatusCode::Ok).with_header("Transfer-Encoding", "chunked");
+        res.set_body(b"abc".to_vec());
+
+        assert!(res.headers.get("Transfer-Encoding").is_none());
+        assert_eq!(res.headers.get("Content-Length"), Some(&"3".to_string()));
+    }
+
+    #[test]
+    fn test_method_not_allowed_lists_allowed_methods() {
+        let res = Response::method_not_allowed(&[Method::Get, Method::Head, Method::Post]);
+
+        assert_eq!(res.status, StatusCode::MethodNotAllowed);
+        assert_eq!(res.status.code(), 405);
+        assert_eq!(
+            res.headers.get("Allow"),
+            Some(&"GET, HEAD, POST".to_string())
+        );
+    }
+
+    #[test]
+    fn test_kept_apart_values_are_written_as_separate_lines() {
+        let mut res = Response::new(StatusCode::Ok);
+        res.headers.append("Set-Cookie", "a=1");
+        res.headers.append("Set-Cookie", "b=2");
+        let mut out = Vec::new();
+        res.write_to(&mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("set-cookie: a=1\r\nset-cookie: b=2\r\n"));
+    }
+
+    #[test]
+    fn test_header_with_line_break_is_rejected() {
+        for value in ["/a\nSet-Cookie: evil=1", "/a\rSet-Cookie: evil=1", "/a\0"] {
+            let result = std::panic::catch_unwind(|| {
+                Response::new(StatusCode::Ok).with_header("Location", value)
+            });
+            assert!(result.is_err(), "{value:?}");
+        }
+
+        let mut res = Response::new(StatusCode::Ok);
+        res.headers.0.insert(
+            "location".to_string(),
+            "/a\r\nSet-Cookie: evil=1".to_string(),
+        );
+        let mut out = Vec::new();
+        let err = res.write_to(&mut out).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(!String::from_utf8(out).unwrap().contains("evil"));
+    }
+
+    #[test]
+    fn test_not_modified_writes_no_body_or_framing() {
+        let res = Response::new(StatusCode::NotModified)
+            .with_header("ETag", "\"v1\"")
+            .with_body("accidental");
+        let mut out = Vec::new();
+        res.write_to(&mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("HTTP/1.1 304 Not Modified\r\n"));
+        assert!(out.contains("etag: \"v1\"\r\n"));
+        assert!(!out.contains("content-length"));
+        assert!(out.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_no_content_drops_transfer_encoding() {
+        let res = Response::new(StatusCode::NoContent).with_header("Transfer-Encoding", "chunked");
+        let mut out = Vec::new();
+        res.write_to(&mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(!out.contains("transfer-encoding"));
+        assert!(!out.contains("content-length"));
+    }
+
+    #[test]
+    fn test_vary_merges_without_duplicates() {
+        let mut res = Response::new(StatusCode::Ok);
+        res.vary(&["Accept-Encoding", "Accept"]);
+        res.vary(&["accept", "Origin"]);
+
+        assert_eq!(
+            res.headers.get("Vary"),
+            Some(&"Accept-Encoding, Accept, Origin".to_string())
+        );
+    }
+
+    #[test]
+    fn test_sorted_output_is_stable() {
+        let serialize = |names: &[&str]| {
+            let mut res = Response::new(StatusCode::Ok).with_body("hi");
+            for name in names {
+                res.headers.set(name, "1");
+            }
+            let mut out = Vec::new();
+            res.write_to_sorted(&mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let out = serialize(&["X-B", "X-A", "Date", "X-C"]);
+        assert_eq!(out, serialize(&["X-C", "Date", "X-A", "X-B"]));
+        assert_eq!(
+            out,
+            concat!(
+                "HTTP/1.1 200 OK\r\n",
+                "content-length: 2\r\n",
+                "date: 1\r\n",
+                "x-a: 1\r\n",
+                "x-b: 1\r\n",
+                "x-c: 1\r\n",
+                "\r\n",
+                "hi",
+            )
+        );
+    }
+
+    #[test]
+    fn test_body_stream_is_written_chunked() {
+        let (tx, rx) = mpsc::channel();
+        let producer = thread::spawn(move || {
+            for chunk in ["hello", ", ", "streaming world"] {
+                tx.send(chunk.as_bytes().to_vec()).unwrap();
+            }
+        });
+
+        let res = Response::new(StatusCode::Ok).with_body_stream(rx);
+        let mut out = Vec::new();
+        res.write_to_sorted(&mut out).unwrap();
+        producer.join().unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                "HTTP/1.1 200 OK\r\n",
+                "transfer-encoding: chunked\r\n",
+                "\r\n",
+                "5\r\nhello\r\n",
+                "2\r\n, \r\n",
+                "f\r\nstreaming world\r\n",
+                "0\r\n\r\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_body_iter_runs_on_writing_thread() {
+        let writer = thread::current().id();
+        let chunks = ["ab", "", "c"].into_iter().map(move |chunk| {
+            assert_eq!(thread::current().id(), writer);
+            chunk.as_bytes().to_vec()
+        });
+
+        let mut out = Vec::new();
+        Response::new(StatusCode::Ok)
+            .with_body_iter(chunks)
+            .write_to(&mut out)
+            .unwrap();
+        assert!(out.ends_with(b"\r\n\r\n2\r\nab\r\n1\r\nc\r\n0\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_limits_reject_before_writing() {
+        let limits = ResponseLimits {
+            max_header_count: Some(3),
+            max_body_size: Some(8),
+            ..ResponseLimits::default()
+        };
+
+        let mut res = Response::new(StatusCode::Ok).with_body("ok");
+        for i in 0..3 {
+            res.headers.set(&format!("X-{i}"), "1");
+        }
+        res.set_limits(limits);
+        let mut out = Vec::new();
+        let err = res.write_to(&mut out).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("header count 4"));
+        assert!(out.is_empty());
+
+        let mut res = Response::new(StatusCode::Ok).with_body("much too long");
+        res.set_limits(limits);
+        let err = res.write_to(&mut out).unwrap_err();
+        assert!(err.to_string().contains("body size 13"));
+        assert!(out.is_empty());
+
+        let mut res = Response::new(StatusCode::Ok).with_body("fits");
+        res.set_limits(limits);
+        res.write_to(&mut out).unwrap();
+        assert!(out.ends_with(b"fits"));
+    }
+
+    #[test]
+    fn test_lf_line_endings() {
+        let mut res = Response::new(StatusCode::Ok).with_body("hi");
+        res.headers.set("X-A", "1");
+
+        let mut crlf = Vec::new();
+        res.write_to_sorted(&mut crlf).unwrap();
+        assert_eq!(
+            String::from_utf8(crlf).unwrap(),
+            "HTTP/1.1 200 OK\r\ncontent-length: 2\r\nx-a: 1\r\n\r\nhi"
+        );
+
+        res.set_line_ending(LineEnding::Lf);
+        let mut lf = Vec::new();
+        res.write_to_sorted(&mut lf).unwrap();
+        assert_eq!(
+            String::from_utf8(lf).unwrap(),
+            "HTTP/1.1 200 OK\ncontent-length: 2\nx-a: 1\n\nhi"
+        );
+    }
+}
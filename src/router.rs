@@ -0,0 +1,575 @@
+use std::{collections::HashMap, error::Error, sync::Arc};
+
+use crate::{
+    method::Method,
+    path::canonical_request_path,
+    request::Request,
+    response::{Response, StatusCode},
+};
+
+pub type Handler = Arc<dyn Fn(&Request) -> Response + Send + Sync>;
+
+pub type BoxError = Box<dyn Error + Send + Sync>;
+
+/// A handler that may fail; see [`Router::add_fallible_route`].
+pub type FallibleHandler = Arc<dyn Fn(&Request) -> Result<Response, BoxError> + Send + Sync>;
+
+pub type ErrorHandler = Box<dyn Fn(StatusCode, &Request) -> Response + Send + Sync>;
+
+/// Dispatches requests to handlers by path, then by method.
+///
+/// Route paths are split into segments, each of which is one of:
+///
+/// - a literal, matched exactly (`/users`);
+/// - `:name`, matching any one non-empty segment and capturing it into
+///   [`Request::param`] under `name` (`/users/:id`);
+/// - `*`, matching any one non-empty segment without capturing it;
+/// - `*name` as the last segment, matching the rest of the path (at least
+///   one non-empty segment) and capturing it, slashes included
+///   (`/files/*path`).
+///
+/// Literals win over `:name`, which wins over `*`, which wins over `*name`,
+/// whatever order the routes were added in. Routes sharing a `:name` or
+/// `*name` position must use the same name there; adding one that doesn't
+/// panics. Paths are matched in the form
+/// [`canonical_request_path`] gives them, so routes should be registered
+/// decoded and normalized. Requests whose path can't be canonicalized get a
+/// `400`.
+///
+/// A path that exists but has no handler for the request's method gets a
+/// `405` listing the registered methods; an unknown path gets a `404`.
+/// `HEAD` falls back to the `GET` handler when it has none of its own.
+///
+/// The bodies of these generated error responses come from the error
+/// handler, which defaults to the status's reason phrase as plain text. So
+/// does the `500` sent when a fallible handler returns an error.
+#[derive(Default)]
+pub struct Router {
+    routes: Node,
+    error_handler: Option<ErrorHandler>,
+}
+
+/// One path segment in the route trie. A node is a route once it has a
+/// handler for at least one method.
+#[derive(Default)]
+struct Node {
+    handlers: HashMap<Method, FallibleHandler>,
+    literals: HashMap<String, Node>,
+    /// The `:name` child, with its name.
+    param: Option<(String, Box<Node>)>,
+    wildcard: Option<Box<Node>>,
+    /// The trailing `*name` route, with its name.
+    catch_all: Option<(String, Box<Node>)>,
+}
+
+impl Node {
+    fn insert(&mut self, segments: &[&str]) -> &mut Node {
+        let Some((segment, rest)) = segments.split_first() else {
+            return self;
+        };
+
+        let child = if let Some(name) = segment.strip_prefix(':') {
+            let (existing, child) = self
+                .param
+                .get_or_insert_with(|| (name.to_string(), Box::default()));
+            // One name per position, or a route's handler would find its
+            // param under another route's name.
+            assert_eq!(existing, name, "conflicting names for one route segment");
+            child.as_mut()
+        } else if *segment == "*" {
+            self.wildcard.get_or_insert_with(Box::default).as_mut()
+        } else if let Some(name) = segment.strip_prefix('*') {
+            assert!(rest.is_empty(), "`*{name}` must be the last route segment");
+            let (existing, child) = self
+                .catch_all
+                .get_or_insert_with(|| (name.to_string(), Box::default()));
+            assert_eq!(existing, name, "conflicting names for one route segment");
+            child.as_mut()
+        } else {
+            self.literals.entry(segment.to_string()).or_default()
+        };
+
+        child.insert(rest)
+    }
+
+    /// The most specific route matching `path`, the part of the request path
+    /// after this node's segment and its `/` (`None` once it is used up).
+    /// Backtracks to a less specific branch when a more specific one leads
+    /// nowhere. Captured params are pushed onto `params` in path order.
+    fn find<'a>(
+        &'a self,
+        path: Option<&'a str>,
+        params: &mut Vec<(&'a str, &'a str)>,
+    ) -> Option<&'a HashMap<Method, FallibleHandler>> {
+        let Some(path) = path else {
+            return (!self.handlers.is_empty()).then_some(&self.handlers);
+        };
+        let (segment, rest) = match path.split_once('/') {
+            Some((segment, rest)) => (segment, Some(rest)),
+            None => (path, None),
+        };
+
+        if let Some(found) = self
+            .literals
+            .get(segment)
+            .and_then(|child| child.find(rest, params))
+        {
+            return Some(found);
+        }
+
+        if segment.is_empty() {
+            return None;
+        }
+
+        if let Some((name, child)) = &self.param {
+            params.push((name, segment));
+            if let Some(found) = child.find(rest, params) {
+                return Some(found);
+            }
+            params.pop();
+        }
+
+        if let Some(found) = self
+            .wildcard
+            .as_ref()
+            .and_then(|child| child.find(rest, params))
+        {
+            return Some(found);
+        }
+
+        let (name, child) = self.catch_all.as_ref()?;
+        params.push((name, path));
+        let found = child.find(None, params);
+        if found.is_none() {
+            params.pop();
+        }
+        found
+    }
+}
+
+fn segments(path: &str) -> Vec<&str> {
+    path.strip_prefix('/').unwrap_or(path).split('/').collect()
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_route<H>(&mut self, method: Method, path: &str, handler: H)
+    where
+        H: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        self.add_route_methods(&[method], path, handler);
+    }
+
+    /// Registers one handler for several methods on the same path.
+    pub fn add_route_methods<H>(&mut self, methods: &[Method], path: &str, handler: H)
+    where
+        H: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        self.insert(methods, path, Arc::new(move |req| Ok(handler(req))));
+    }
+
+    /// Registers a handler that may fail. An `Err` is logged and answered
+    /// with a `500` built by the error handler.
+    pub fn add_fallible_route<H, E>(&mut self, method: Method, path: &str, handler: H)
+    where
+        H: Fn(&Request) -> Result<Response, E> + Send + Sync + 'static,
+        E: Into<BoxError>,
+    {
+        self.insert(
+            &[method],
+            path,
+            Arc::new(move |req| handler(req).map_err(Into::into)),
+        );
+    }
+
+    fn insert(&mut self, methods: &[Method], path: &str, handler: FallibleHandler) {
+        let by_method = &mut self.routes.insert(&segments(path)).handlers;
+
+        for method in methods {
+            by_method.insert(*method, Arc::clone(&handler));
+        }
+    }
+
+    /// Replaces how error responses the router generates itself are built.
+    ///
+    /// Headers the status requires, such as `Allow` on a `405`, are added
+    /// after the handler runs.
+    pub fn set_error_handler<H>(&mut self, handler: H)
+    where
+        H: Fn(StatusCode, &Request) -> Response + Send + Sync + 'static,
+    {
+        self.error_handler = Some(Box::new(handler));
+    }
+
+    /// Finds the handler for `req` and runs it, with the path parameters the
+    /// route captured set on `req` first.
+    pub fn handle(&self, req: &mut Request) -> Response {
+        let Some(line) = &req.request_line else {
+            return self.error_response(StatusCode::BadRequest, req);
+        };
+
+        let Ok(path) = canonical_request_path(line) else {
+            return self.error_response(StatusCode::BadRequest, req);
+        };
+
+        let mut params = Vec::new();
+        let path = path.strip_prefix('/').unwrap_or(&path);
+        let Some(by_method) = self.routes.find(Some(path), &mut params) else {
+            return self.error_response(StatusCode::NotFound, req);
+        };
+
+        let handler = by_method.get(&line.method).or_else(|| match line.method {
+            Method::Head => by_method.get(&Method::Get),
+            _ => None,
+        });
+
+        let result = match handler {
+            Some(handler) => {
+                let params = params
+                    .into_iter()
+                    .map(|(name, value)| (name.to_string(), value.to_string()));
+                let captured = req.params_mut();
+                captured.clear();
+                captured.extend(params);
+                handler(req)
+            }
+            None => {
+                let allow = allowed_methods(by_method)
+                    .iter()
+                    .map(|m| m.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                return self
+                    .error_response(StatusCode::MethodNotAllowed, req)
+                    .with_header("Allow", &allow);
+            }
+        };
+
+        result.unwrap_or_else(|err| {
+            eprintln!("Handler error: {err}");
+            self.error_response(StatusCode::InternalServerError, req)
+        })
+    }
+
+    fn error_response(&self, status: StatusCode, req: &Request) -> Response {
+        match &self.error_handler {
+            Some(handler) => handler(status, req),
+            None => Response::new(status).with_body(status.reason()),
+        }
+    }
+}
+
+/// Picks a [`Router`] by the request's host, for serving several sites from
+/// one listener.
+///
+/// Hosts match case-insensitively and without their port. Requests for an
+/// unknown host, or without one, go to the default router if there is one
+/// and get a `404` otherwise.
+#[derive(Default)]
+pub struct VirtualHosts {
+    hosts: HashMap<String, Router>,
+    default: Option<Router>,
+}
+
+impl VirtualHosts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_host(&mut self, host: &str, router: Router) {
+        self.hosts.insert(host.to_lowercase(), router);
+    }
+
+    pub fn set_default(&mut self, router: Router) {
+        self.default = Some(router);
+    }
+
+    pub fn handle(&self, req: &mut Request) -> Response {
+        let router = req
+            .authority()
+            .and_then(|authority| self.hosts.get(&authority.host))
+            .or(self.default.as_ref());
+
+        match router {
+            Some(router) => router.handle(req),
+            None => Response::new(StatusCode::NotFound).with_body("Not Found"),
+        }
+    }
+}
+
+fn allowed_methods(by_method: &HashMap<Method, FallibleHandler>) -> Vec<Method> {
+    let mut allowed: Vec<Method> = by_method.keys().copied().collect();
+    if by_method.contains_key(&Method::Get) && !by_method.contains_key(&Method::Head) {
+        allowed.push(Method::Head);
+    }
+    allowed.sort();
+    allowed
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chunk_reader::ChunkReader,
+        method::Method,
+        request::{Request, request_from_reader},
+        response::{Response, StatusCode},
+        router::{BoxError, Router, VirtualHosts},
+    };
+
+    fn request(method: &str, target: &str) -> Request {
+        request_for_host(method, target, "localhost")
+    }
+
+    fn request_for_host(method: &str, target: &str, host: &str) -> Request {
+        let data = format!("{method} {target} HTTP/1.1\r\nHost: {host}\r\n\r\n");
+        request_from_reader(ChunkReader::new(&data, 16)).unwrap()
+    }
+
+    fn site(name: &'static str) -> Router {
+        let mut router = Router::new();
+        router.add_route(Method::Get, "/", move |_| {
+            Response::new(StatusCode::Ok).with_body(name)
+        });
+        router
+    }
+
+    fn router() -> Router {
+        let mut router = Router::new();
+        router.add_route(Method::Get, "/x", |_| {
+            Response::new(StatusCode::Ok).with_body("get x")
+        });
+        router.add_route(Method::Post, "/x", |_| {
+            Response::new(StatusCode::Ok).with_body("post x")
+        });
+        router
+    }
+
+    #[test]
+    fn test_dispatch_by_method() {
+        let router = router();
+
+        assert_eq!(router.handle(&mut request("GET", "/x")).body, b"get x");
+        assert_eq!(
+            router.handle(&mut request("POST", "/x?y=1")).body,
+            b"post x"
+        );
+    }
+
+    #[test]
+    fn test_unregistered_method_on_known_path_is_405() {
+        let res = router().handle(&mut request("PUT", "/x"));
+
+        assert_eq!(res.status, StatusCode::MethodNotAllowed);
+        assert_eq!(
+            res.headers.get("Allow"),
+            Some(&"GET, HEAD, POST".to_string())
+        );
+    }
+
+    #[test]
+    fn test_unknown_path_is_404() {
+        let res = router().handle(&mut request("GET", "/y"));
+
+        assert_eq!(res.status, StatusCode::NotFound);
+    }
+
+    #[test]
+    fn test_matches_canonical_path() {
+        let router = router();
+
+        assert_eq!(router.handle(&mut request("GET", "/a/../x")).body, b"get x");
+        assert_eq!(router.handle(&mut request("GET", "//%78")).body, b"get x");
+        assert_eq!(
+            router.handle(&mut request("GET", "/x%2F")).status,
+            StatusCode::BadRequest
+        );
+    }
+
+    #[test]
+    fn test_one_handler_for_several_methods() {
+        let mut router = Router::new();
+        router.add_route_methods(&[Method::Put, Method::Patch], "/item", |req| {
+            let method = req.request_line.as_ref().unwrap().method;
+            Response::new(StatusCode::Ok).with_body(method.as_str())
+        });
+
+        assert_eq!(router.handle(&mut request("PATCH", "/item")).body, b"PATCH");
+        assert_eq!(router.handle(&mut request("PUT", "/item")).body, b"PUT");
+        assert_eq!(
+            router.handle(&mut request("GET", "/item")).status,
+            StatusCode::MethodNotAllowed
+        );
+    }
+
+    #[test]
+    fn test_custom_error_handler() {
+        let mut router = router();
+        router.set_error_handler(|status, req| {
+            let target = &req.request_line.as_ref().unwrap().request_target;
+            Response::new(status)
+                .with_header("Content-Type", "application/json")
+                .with_body(format!(
+                    r#"{{"status":{},"path":"{target}"}}"#,
+                    status.code()
+                ))
+        });
+
+        let res = router.handle(&mut request("GET", "/y"));
+        assert_eq!(res.status, StatusCode::NotFound);
+        assert_eq!(
+            res.headers.get("Content-Type"),
+            Some(&"application/json".to_string())
+        );
+        assert_eq!(res.body, br#"{"status":404,"path":"/y"}"#);
+
+        let res = router.handle(&mut request("PUT", "/x"));
+        assert_eq!(res.body, br#"{"status":405,"path":"/x"}"#);
+        assert_eq!(
+            res.headers.get("Allow"),
+            Some(&"GET, HEAD, POST".to_string())
+        );
+    }
+
+    fn echo(label: &'static str) -> impl Fn(&Request) -> Response {
+        move |req| {
+            let mut params: Vec<_> = req.params().iter().collect();
+            params.sort();
+            Response::new(StatusCode::Ok).with_body(format!("{label} {params:?}"))
+        }
+    }
+
+    #[test]
+    fn test_static_param_and_wildcard_segments() {
+        let mut router = Router::new();
+        router.add_route(Method::Get, "/users/:id", echo("user"));
+        router.add_route(Method::Get, "/users/me", echo("me"));
+        router.add_route(Method::Get, "/users/:id/posts/:post", echo("post"));
+        router.add_route(Method::Get, "/*/health", echo("health"));
+        router.add_route(Method::Get, "/users/:id/health", echo("user health"));
+
+        let body =
+            |target| String::from_utf8(router.handle(&mut request("GET", target)).body).unwrap();
+        assert_eq!(body("/users/42"), r#"user [("id", "42")]"#);
+        assert_eq!(body("/users/me"), "me []");
+        assert_eq!(
+            body("/users/7/posts/x%20y"),
+            r#"post [("id", "7"), ("post", "x y")]"#
+        );
+        assert_eq!(body("/api/health"), "health []");
+        assert_eq!(body("/users/health"), r#"user [("id", "health")]"#);
+        assert_eq!(body("/users/9/health"), r#"user health [("id", "9")]"#);
+
+        for missing in ["/users", "/users/", "/users/1/posts", "/a/b/health"] {
+            assert_eq!(
+                router.handle(&mut request("GET", missing)).status,
+                StatusCode::NotFound,
+                "{missing}"
+            );
+        }
+        assert_eq!(
+            router.handle(&mut request("POST", "/users/1")).status,
+            StatusCode::MethodNotAllowed
+        );
+    }
+
+    #[test]
+    fn test_catch_all_segment() {
+        let mut router = Router::new();
+        router.add_route(Method::Get, "/files/*path", echo("files"));
+        router.add_route(Method::Get, "/files/special", echo("special"));
+        router.add_route(Method::Get, "/files/:name/meta", echo("meta"));
+
+        let body =
+            |target| String::from_utf8(router.handle(&mut request("GET", target)).body).unwrap();
+        assert_eq!(body("/files/a/b/c.txt"), r#"files [("path", "a/b/c.txt")]"#);
+        assert_eq!(body("/files/special"), "special []");
+        assert_eq!(body("/files/special/x"), r#"files [("path", "special/x")]"#);
+        assert_eq!(body("/files/doc/meta"), r#"meta [("name", "doc")]"#);
+        assert_eq!(body("/files/doc"), r#"files [("path", "doc")]"#);
+        assert_eq!(
+            router.handle(&mut request("GET", "/files/")).status,
+            StatusCode::NotFound
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "conflicting names for one route segment")]
+    fn test_conflicting_param_names_panic() {
+        let mut router = Router::new();
+        router.add_route(Method::Get, "/u/:id", echo("id"));
+        router.add_route(Method::Post, "/u/:name", echo("name"));
+    }
+
+    #[test]
+    fn test_many_routes() {
+        let mut router = Router::new();
+        for i in 0..1000 {
+            router.add_route(Method::Get, &format!("/static/{i}"), echo("static"));
+            router.add_route(Method::Get, &format!("/r{i}/:id"), echo("param"));
+        }
+
+        assert_eq!(
+            router.handle(&mut request("GET", "/static/999")).body,
+            b"static []"
+        );
+        assert_eq!(
+            router.handle(&mut request("GET", "/r500/x")).body,
+            br#"param [("id", "x")]"#
+        );
+        assert_eq!(
+            router.handle(&mut request("GET", "/static/1000")).status,
+            StatusCode::NotFound
+        );
+    }
+
+    #[test]
+    fn test_fallible_handler_error_is_500() {
+        let mut router = Router::new();
+        router.add_fallible_route(Method::Get, "/n/:n", |req| {
+            let n: u32 = req.param("n").unwrap_or_default().parse()?;
+            Ok::<_, BoxError>(Response::new(StatusCode::Ok).with_body(format!("{}", n * 2)))
+        });
+
+        assert_eq!(router.handle(&mut request("GET", "/n/21")).body, b"42");
+
+        let res = router.handle(&mut request("GET", "/n/abc"));
+        assert_eq!(res.status, StatusCode::InternalServerError);
+        assert_eq!(res.body, b"Internal Server Error");
+
+        router.set_error_handler(|status, _| Response::new(status).with_body("oops"));
+        assert_eq!(router.handle(&mut request("GET", "/n/abc")).body, b"oops");
+    }
+
+    #[test]
+    fn test_virtual_hosts_route_by_host() {
+        let mut hosts = VirtualHosts::new();
+        hosts.add_host("a.example", site("site a"));
+        hosts.add_host("B.example", site("site b"));
+
+        let res = hosts.handle(&mut request_for_host("GET", "/", "a.example"));
+        assert_eq!(res.body, b"site a");
+
+        let res = hosts.handle(&mut request_for_host("GET", "/", "b.EXAMPLE:8080"));
+        assert_eq!(res.body, b"site b");
+
+        let res = hosts.handle(&mut request_for_host("GET", "/", "c.example"));
+        assert_eq!(res.status, StatusCode::NotFound);
+    }
+
+    #[test]
+    fn test_unknown_host_uses_default() {
+        let mut hosts = VirtualHosts::new();
+        hosts.add_host("a.example", site("site a"));
+        hosts.set_default(site("fallback"));
+
+        let res = hosts.handle(&mut request_for_host("GET", "/", "c.example"));
+        assert_eq!(res.body, b"fallback");
+
+        let mut req = request_from_reader(ChunkReader::new("GET / HTTP/1.0\r\n\r\n", 16)).unwrap();
+        assert_eq!(hosts.handle(&mut req).body, b"fallback");
+    }
+}
@@ -0,0 +1,131 @@
+use std::time::Duration;
+
+use crate::response::Response;
+
+/// A value for `X-Frame-Options`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XFrameOptions {
+    Deny,
+    SameOrigin,
+}
+
+/// A value for `Referrer-Policy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferrerPolicy {
+    NoReferrer,
+    NoReferrerWhenDowngrade,
+    Origin,
+    OriginWhenCrossOrigin,
+    SameOrigin,
+    StrictOrigin,
+    StrictOriginWhenCrossOrigin,
+    UnsafeUrl,
+}
+
+impl ReferrerPolicy {
+    fn as_str(self) -> &'static str {
+        match self {
+            ReferrerPolicy::NoReferrer => "no-referrer",
+            ReferrerPolicy::NoReferrerWhenDowngrade => "no-referrer-when-downgrade",
+            ReferrerPolicy::Origin => "origin",
+            ReferrerPolicy::OriginWhenCrossOrigin => "origin-when-cross-origin",
+            ReferrerPolicy::SameOrigin => "same-origin",
+            ReferrerPolicy::StrictOrigin => "strict-origin",
+            ReferrerPolicy::StrictOriginWhenCrossOrigin => "strict-origin-when-cross-origin",
+            ReferrerPolicy::UnsafeUrl => "unsafe-url",
+        }
+    }
+}
+
+impl Response {
+    /// Sets `Strict-Transport-Security`, telling browsers to use only HTTPS
+    /// for this host for `max_age` (whole seconds). Only meaningful on
+    /// responses sent over HTTPS.
+    pub fn strict_transport_security(
+        self,
+        max_age: Duration,
+        include_subdomains: bool,
+        preload: bool,
+    ) -> Self {
+        let mut value = format!("max-age={}", max_age.as_secs());
+        if include_subdomains {
+            value.push_str("; includeSubDomains");
+        }
+        if preload {
+            value.push_str("; preload");
+        }
+
+        self.with_header("Strict-Transport-Security", &value)
+    }
+
+    /// Sets `Content-Security-Policy` to `policy` as given, e.g.
+    /// `default-src 'self'`.
+    pub fn content_security_policy(self, policy: &str) -> Self {
+        self.with_header("Content-Security-Policy", policy)
+    }
+
+    pub fn x_frame_options(self, value: XFrameOptions) -> Self {
+        let value = match value {
+            XFrameOptions::Deny => "DENY",
+            XFrameOptions::SameOrigin => "SAMEORIGIN",
+        };
+
+        self.with_header("X-Frame-Options", value)
+    }
+
+    pub fn referrer_policy(self, value: ReferrerPolicy) -> Self {
+        self.with_header("Referrer-Policy", value.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        response::{Response, StatusCode},
+        security::{ReferrerPolicy, XFrameOptions},
+    };
+
+    fn header(res: &Response, name: &str) -> String {
+        res.headers.get(name).cloned().unwrap()
+    }
+
+    #[test]
+    fn test_strict_transport_security() {
+        let year = Duration::from_secs(31_536_000);
+
+        let res = Response::new(StatusCode::Ok).strict_transport_security(year, false, false);
+        assert_eq!(
+            header(&res, "Strict-Transport-Security"),
+            "max-age=31536000"
+        );
+
+        let res = Response::new(StatusCode::Ok).strict_transport_security(year, true, true);
+        assert_eq!(
+            header(&res, "Strict-Transport-Security"),
+            "max-age=31536000; includeSubDomains; preload"
+        );
+    }
+
+    #[test]
+    fn test_other_security_headers() {
+        let res = Response::new(StatusCode::Ok)
+            .content_security_policy("default-src 'self'")
+            .x_frame_options(XFrameOptions::SameOrigin)
+            .referrer_policy(ReferrerPolicy::StrictOriginWhenCrossOrigin);
+
+        assert_eq!(
+            header(&res, "Content-Security-Policy"),
+            "default-src 'self'"
+        );
+        assert_eq!(header(&res, "X-Frame-Options"), "SAMEORIGIN");
+        assert_eq!(
+            header(&res, "Referrer-Policy"),
+            "strict-origin-when-cross-origin"
+        );
+
+        let res = Response::new(StatusCode::Ok).x_frame_options(XFrameOptions::Deny);
+        assert_eq!(header(&res, "X-Frame-Options"), "DENY");
+    }
+}
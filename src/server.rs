@@ -0,0 +1,1506 @@
+use std::{
+    any::Any,
+    collections::HashMap,
+    io::{self, Read, Write},
+    net::{
+        IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs,
+    },
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config::RequestParserConfig,
+    error::RequestError,
+    headers::Headers,
+    method::Method,
+    request::{Request, RequestReader},
+    response::{BodyWriter, Response, ResponseLimits, StatusCode},
+    router::{Router, VirtualHosts},
+    upgrade::{OnUpgrade, Upgraded},
+};
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// How long an idle keep-alive connection may wait for the first byte of
+    /// its next request before the server closes it. Must not be zero.
+    pub keep_alive_timeout: Duration,
+    /// How long the rest of a request, head and body, may take to arrive
+    /// after its first byte, however the client spaces it out. The
+    /// connection is closed without a response when it runs out. Must not be
+    /// zero.
+    pub request_read_timeout: Duration,
+    /// How many requests a single connection may serve before the server
+    /// answers with `Connection: close` and hangs up.
+    pub max_requests_per_connection: usize,
+    /// How long a connection may stay open in total, however busy it is.
+    /// Once reached, the connection closes after the current response, or at
+    /// once if idle or still reading a request. `None`, the default, means
+    /// no limit.
+    pub max_connection_lifetime: Option<Duration>,
+    pub parser: RequestParserConfig,
+    /// Added to every response that doesn't already set them.
+    pub default_headers: Headers,
+    /// Bounds on what a handler may respond with; a response over them is
+    /// replaced by a `500`. Unlimited by default.
+    pub response_limits: ResponseLimits,
+}
+
+impl ServerConfig {
+    fn check(&self) -> io::Result<()> {
+        if self.keep_alive_timeout.is_zero() || self.request_read_timeout.is_zero() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "keep_alive_timeout and request_read_timeout must not be zero",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            keep_alive_timeout: Duration::from_secs(5),
+            request_read_timeout: Duration::from_secs(30),
+            max_requests_per_connection: 100,
+            max_connection_lifetime: None,
+            parser: RequestParserConfig::default(),
+            default_headers: Headers::new(),
+            response_limits: ResponseLimits::default(),
+        }
+    }
+}
+
+/// How [`Server::bind_with_options`] sets up the listening socket.
+#[cfg(feature = "socket-options")]
+#[derive(Debug, Clone)]
+pub struct SocketOptions {
+    /// `SO_REUSEADDR`. On Unix this lets a restarted server bind while old
+    /// connections sit in `TIME_WAIT`; it's on by default, as with
+    /// `TcpListener::bind`. On Windows it instead lets another socket take
+    /// over a port in use, so think twice before leaving it on there.
+    pub reuse_address: bool,
+    /// `SO_REUSEPORT`, letting several processes listen on the same port.
+    /// Linux then spreads incoming connections across them; the BSDs and
+    /// macOS allow the binding but send new connections to the most recent
+    /// listener. Unavailable on Windows, Solaris and illumos, where asking
+    /// for it fails the bind. Off by default.
+    pub reuse_port: bool,
+    /// Length of the queue of connections waiting to be accepted; the OS
+    /// may clamp it.
+    pub backlog: i32,
+}
+
+#[cfg(feature = "socket-options")]
+impl Default for SocketOptions {
+    fn default() -> Self {
+        Self {
+            reuse_address: cfg!(unix),
+            reuse_port: false,
+            backlog: 128,
+        }
+    }
+}
+
+pub struct Server {
+    listener: TcpListener,
+    config: ServerConfig,
+    connections: Arc<Connections>,
+}
+
+impl Server {
+    pub fn bind<A: ToSocketAddrs>(addr: A, config: ServerConfig) -> io::Result<Self> {
+        config.check()?;
+        Ok(Self::from_listener(TcpListener::bind(addr)?, config))
+    }
+
+    /// Like [`Server::bind`], with a chosen length for the queue of
+    /// connections waiting to be accepted instead of the platform default.
+    /// The OS may clamp it, e.g. to `net.core.somaxconn` on Linux.
+    #[cfg(feature = "socket-options")]
+    pub fn bind_with_backlog<A: ToSocketAddrs>(
+        addr: A,
+        config: ServerConfig,
+        backlog: i32,
+    ) -> io::Result<Self> {
+        let options = SocketOptions {
+            backlog,
+            ..SocketOptions::default()
+        };
+        Self::bind_with_options(addr, config, options)
+    }
+
+    /// Like [`Server::bind`], with the listening socket set up per `options`.
+    #[cfg(feature = "socket-options")]
+    pub fn bind_with_options<A: ToSocketAddrs>(
+        addr: A,
+        config: ServerConfig,
+        options: SocketOptions,
+    ) -> io::Result<Self> {
+        use socket2::{Domain, Protocol, Socket, Type};
+
+        config.check()?;
+        let mut last_err = None;
+        for addr in addr.to_socket_addrs()? {
+            let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+            socket.set_reuse_address(options.reuse_address)?;
+            if options.reuse_port {
+                #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+                socket.set_reuse_port(true)?;
+                #[cfg(not(all(unix, not(any(target_os = "solaris", target_os = "illumos")))))]
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "SO_REUSEPORT is not available on this platform",
+                ));
+            }
+
+            match socket
+                .bind(&addr.into())
+                .and_then(|()| socket.listen(options.backlog))
+            {
+                Ok(()) => return Ok(Self::from_listener(socket.into(), config)),
+                Err(err) => last_err = Some(err),
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no addresses to bind to")
+        }))
+    }
+
+    fn from_listener(listener: TcpListener, config: ServerConfig) -> Self {
+        Self {
+            listener,
+            config,
+            connections: Arc::default(),
+        }
+    }
+
+    /// Sets headers, like `Server`, that every response carries unless its
+    /// handler set them itself.
+    pub fn default_headers(mut self, headers: Headers) -> Self {
+        self.config.default_headers = headers;
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accepts connections until [`Server::shutdown_with_timeout`] is
+    /// called, serving each one on its own thread.
+    pub fn serve<H>(&self, handler: H) -> io::Result<()>
+    where
+        H: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        self.accept_loop(move |req: &mut Request| handler(req))
+    }
+
+    /// [`Server::serve`] for a handler that may modify the request it is
+    /// given, as a router setting path parameters does.
+    fn accept_loop<H>(&self, handler: H) -> io::Result<()>
+    where
+        H: Fn(&mut Request) -> Response + Send + Sync + 'static,
+    {
+        let handler = Arc::new(handler);
+
+        for stream in self.listener.incoming() {
+            if self.connections.is_stopping() {
+                break;
+            }
+
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    eprintln!("Connection error: {err}");
+                    continue;
+                }
+            };
+
+            let id = match self.connections.register(&stream) {
+                Ok(id) => id,
+                Err(err) => {
+                    eprintln!("Connection error: {err}");
+                    continue;
+                }
+            };
+
+            let handler = Arc::clone(&handler);
+            let config = self.config.clone();
+            let connections = Arc::clone(&self.connections);
+
+            thread::spawn(move || {
+                let result = handle_connection(stream, &config, &*handler, &connections, id);
+                connections.remove(id);
+                if let Err(err) = result {
+                    eprintln!("Failed to serve connection: {err}");
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Stops accepting connections and lets in-flight requests finish.
+    ///
+    /// Idle keep-alive connections are closed right away; busy ones get to
+    /// send their current response, marked `Connection: close`. Whatever is
+    /// still busy after `timeout` is closed under its handler. Returns how
+    /// many connections had to be closed that way.
+    pub fn shutdown_with_timeout(&self, timeout: Duration) -> io::Result<usize> {
+        let deadline = Instant::now() + timeout;
+        self.connections.stopping.store(true, Ordering::SeqCst);
+
+        // `serve` is blocked in `accept`; a connection of our own wakes it up
+        // to see the flag.
+        let mut addr = self.local_addr()?;
+        if addr.ip().is_unspecified() {
+            addr.set_ip(match addr.ip() {
+                IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            });
+        }
+        let _ = TcpStream::connect_timeout(&addr, Duration::from_secs(1));
+
+        let mut open = self.connections.open.lock().unwrap();
+        for conn in open.values().filter(|conn| !conn.busy) {
+            let _ = conn.stream.shutdown(Shutdown::Read);
+        }
+
+        while !open.is_empty() {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                break;
+            }
+            open = self.connections.closed.wait_timeout(open, left).unwrap().0;
+        }
+
+        for conn in open.values() {
+            let _ = conn.stream.shutdown(Shutdown::Both);
+        }
+        Ok(open.len())
+    }
+
+    /// Like [`Server::serve`], dispatching each request to the router for
+    /// its host.
+    pub fn serve_virtual_hosts(&self, hosts: VirtualHosts) -> io::Result<()> {
+        self.accept_loop(move |req: &mut Request| hosts.handle(req))
+    }
+}
+
+/// The connections a [`Server`] is serving, tracked so shutdown can wait for
+/// the busy ones and close the rest.
+#[derive(Default)]
+struct Connections {
+    stopping: AtomicBool,
+    next_id: AtomicU64,
+    open: Mutex<HashMap<u64, OpenConnection>>,
+    /// Notified whenever a connection is removed from `open`.
+    closed: Condvar,
+}
+
+struct OpenConnection {
+    stream: TcpStream,
+    /// Between receiving the first byte of a request and writing its response.
+    busy: bool,
+}
+
+impl Connections {
+    fn register(&self, stream: &TcpStream) -> io::Result<u64> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let conn = OpenConnection {
+            stream: stream.try_clone()?,
+            busy: false,
+        };
+        self.open.lock().unwrap().insert(id, conn);
+        Ok(id)
+    }
+
+    fn set_busy(&self, id: u64, busy: bool) {
+        if let Some(conn) = self.open.lock().unwrap().get_mut(&id) {
+            conn.busy = busy;
+        }
+    }
+
+    fn remove(&self, id: u64) {
+        self.open.lock().unwrap().remove(&id);
+        self.closed.notify_all();
+    }
+
+    fn is_stopping(&self) -> bool {
+        self.stopping.load(Ordering::SeqCst)
+    }
+}
+
+fn handle_connection<H>(
+    stream: TcpStream,
+    config: &ServerConfig,
+    handler: &H,
+    connections: &Connections,
+    id: u64,
+) -> io::Result<()>
+where
+    H: Fn(&mut Request) -> Response,
+{
+    let timed = DeadlineStream {
+        stream: &stream,
+        deadline: Instant::now(),
+    };
+    let mut reader = RequestReader::with_config(timed, config.parser.clone());
+    let mut req = Request::default();
+    let mut served = 0;
+    let deadline = config
+        .max_connection_lifetime
+        .map(|lifetime| Instant::now() + lifetime);
+
+    loop {
+        connections.set_busy(id, false);
+        if connections.is_stopping() {
+            return Ok(());
+        }
+
+        let mut idle_until = Instant::now() + config.keep_alive_timeout;
+        if let Some(deadline) = deadline {
+            if Instant::now() >= deadline {
+                return Ok(());
+            }
+            idle_until = idle_until.min(deadline);
+        }
+
+        reader.get_mut().deadline = idle_until;
+        match reader.fill_buf() {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(err) if is_timeout(&err) => return Ok(()),
+            Err(err) => return Err(err),
+        }
+        let mut read_until = Instant::now() + config.request_read_timeout;
+        if let Some(deadline) = deadline {
+            read_until = read_until.min(deadline);
+        }
+        reader.get_mut().deadline = read_until;
+        connections.set_busy(id, true);
+
+        match reader.next_request_head_into(&mut req) {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(RequestError::Io(err)) if is_timeout(&err) => return Ok(()),
+            Err(err) => {
+                let Some(mut res) = error_response(&err) else {
+                    return Err(err.into());
+                };
+                add_default_headers(&mut res, &config.default_headers);
+                res.with_header("Connection", "close")
+                    .write_to(&mut &stream)?;
+                return close_gracefully(&stream);
+            }
+        }
+        req.peer_addr = stream.peer_addr().ok();
+
+        let mut res = match answer(&mut reader, &mut req, handler, config) {
+            Err(err) if is_timeout(&err) => return Ok(()),
+            res => res?,
+        };
+        served += 1;
+
+        if let Some(on_upgrade) = take_upgrade(&mut res) {
+            write_response(&mut &stream, &req, &mut res)?;
+            let (_, buffered) = reader.into_parts();
+            stream.set_read_timeout(None)?;
+            on_upgrade.call(Upgraded::new(stream, buffered));
+            return Ok(());
+        }
+
+        // The next request starts after this one's body, so a connection
+        // whose body couldn't be read or discarded can't carry another.
+        let close = req.wants_close()
+            || is_close_delimited(&req, &res)
+            || !req.body_fully_read()
+            || served >= config.max_requests_per_connection
+            || deadline.is_some_and(|deadline| Instant::now() >= deadline)
+            || connections.is_stopping();
+        if close {
+            res.headers.set("Connection", "close");
+        }
+
+        write_response(&mut &stream, &req, &mut res)?;
+
+        if !req.body_fully_read() {
+            return close_gracefully(&stream);
+        }
+        if close {
+            return Ok(());
+        }
+    }
+}
+
+/// Reads the body of the request whose head is in `req` and answers it
+/// through `handler`, within the configured response limits and with the
+/// default headers added. A body over `max_body_size` is discarded and
+/// answered with a `413` without calling the handler, keeping the
+/// connection usable; one that can't be read gets an error response and
+/// leaves [`Request::body_fully_read`] false.
+fn answer<S, H>(
+    reader: &mut RequestReader<S>,
+    req: &mut Request,
+    handler: &H,
+    config: &ServerConfig,
+) -> io::Result<Response>
+where
+    S: Read,
+    H: Fn(&mut Request) -> Response,
+{
+    let mut res = match reader.read_body(req, LINGER_MAX_BYTES) {
+        Ok(()) => {
+            let mut res = call_handler(handler, req);
+            res.set_limits(config.response_limits);
+            if let Err(err) = res.check_limits() {
+                eprintln!("Handler response rejected: {err}");
+                res = Response::new(StatusCode::InternalServerError)
+                    .with_body("Internal Server Error");
+            }
+            res
+        }
+        Err(err) => error_response(&err).ok_or_else(|| io::Error::from(err))?,
+    };
+
+    add_default_headers(&mut res, &config.default_headers);
+    Ok(res)
+}
+
+/// The callback to hand the connection to once `res` is written, if it is a
+/// `101` that set one with [`Response::on_upgrade`].
+fn take_upgrade(res: &mut Response) -> Option<OnUpgrade> {
+    res.take_on_upgrade()
+        .filter(|_| res.status == StatusCode::SwitchingProtocols)
+}
+
+/// Serves requests from `stream` through `router` until the client closes
+/// the connection or asks for it to be closed, handing it over after a
+/// `101` like [`Server::serve`] does.
+///
+/// Works over any byte stream, such as a [`DuplexStream`](crate::duplex_stream::DuplexStream)
+/// in tests. Of `config`, everything but the timeouts and connection
+/// lifetime applies; the stream is expected to enforce its own.
+pub fn serve_connection<S>(stream: S, router: &Router, config: &ServerConfig) -> io::Result<()>
+where
+    S: Read + Write + Send + 'static,
+{
+    let mut reader = RequestReader::with_config(stream, config.parser.clone());
+    let mut req = Request::default();
+    let handler = |req: &mut Request| router.handle(req);
+    let mut served = 0;
+
+    loop {
+        match reader.next_request_head_into(&mut req) {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(err) => {
+                let Some(mut res) = error_response(&err) else {
+                    return Err(err.into());
+                };
+                add_default_headers(&mut res, &config.default_headers);
+                return res
+                    .with_header("Connection", "close")
+                    .write_to(reader.get_mut());
+            }
+        }
+
+        let mut res = answer(&mut reader, &mut req, &handler, config)?;
+        served += 1;
+
+        if let Some(on_upgrade) = take_upgrade(&mut res) {
+            write_response(&mut reader.get_mut(), &req, &mut res)?;
+            let (stream, buffered) = reader.into_parts();
+            on_upgrade.call(Upgraded::new(stream, buffered));
+            return Ok(());
+        }
+
+        let close = req.wants_close()
+            || is_close_delimited(&req, &res)
+            || !req.body_fully_read()
+            || served >= config.max_requests_per_connection;
+        if close {
+            res.headers.set("Connection", "close");
+        }
+
+        write_response(&mut reader.get_mut(), &req, &mut res)?;
+
+        if close {
+            return Ok(());
+        }
+    }
+}
+
+/// Writes `res`, preceded by any early hints when the client speaks
+/// HTTP/1.1, and leaving out the body when answering a `HEAD` request.
+/// HTTP/0.9 clients get the bare body, which is all that version knew.
+fn write_response<W: BodyWriter>(w: &mut W, req: &Request, res: &mut Response) -> io::Result<()> {
+    let line = req.request_line.as_ref();
+
+    if line.is_some_and(|line| line.http_version == "0.9") {
+        res.set_close_delimited();
+        return res.write_body(w);
+    }
+
+    let hints = res.take_early_hints();
+    if line.is_some_and(|line| line.http_version == "1.1") {
+        for hints in &hints {
+            hints.write_head_to(w)?;
+        }
+    }
+
+    if is_close_delimited(req, res) {
+        res.set_close_delimited();
+    }
+
+    let is_head = line.is_some_and(|line| line.method == Method::Head);
+
+    if is_head {
+        res.write_head_to(w)
+    } else {
+        res.write(w, false)
+    }
+}
+
+/// Whether `res` streams its body to a client too old for chunked
+/// encoding, so the body has to end with the connection instead.
+fn is_close_delimited(req: &Request, res: &Response) -> bool {
+    res.is_streaming()
+        && req
+            .request_line
+            .as_ref()
+            .is_some_and(|line| line.http_version == "1.0")
+}
+
+fn add_default_headers(res: &mut Response, defaults: &Headers) {
+    for (name, value) in defaults.iter() {
+        if res.headers.get(name).is_none() {
+            res.headers.set(name, value);
+        }
+    }
+}
+
+/// How long, and for how many bytes, a connection closed after an error
+/// keeps reading so the client sees the response rather than a reset.
+const LINGER_TIMEOUT: Duration = Duration::from_secs(1);
+const LINGER_MAX_BYTES: u64 = 1024 * 1024;
+
+/// The response for a request that failed to parse, or `None` when the
+/// connection itself broke and there is no one left to answer.
+fn error_response(err: &RequestError) -> Option<Response> {
+    if matches!(err, RequestError::Io(_) | RequestError::UnexpectedEof) {
+        return None;
+    }
+
+    let status = err.status_code();
+    Some(Response::new(status).with_body(format!("{}: {err}", status.reason())))
+}
+
+/// Stops writing and discards what the client is still sending, so closing
+/// with unread input doesn't reset the connection before the response lands.
+fn close_gracefully(stream: &TcpStream) -> io::Result<()> {
+    stream.shutdown(Shutdown::Write)?;
+    stream.set_read_timeout(Some(LINGER_TIMEOUT))?;
+
+    match io::copy(&mut stream.take(LINGER_MAX_BYTES), &mut io::sink()) {
+        Err(err) if !is_timeout(&err) => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Runs `handler`, turning a panic into a `500` so the connection survives.
+fn call_handler<H>(handler: &H, req: &mut Request) -> Response
+where
+    H: Fn(&mut Request) -> Response,
+{
+    panic::catch_unwind(AssertUnwindSafe(|| handler(req))).unwrap_or_else(|payload| {
+        eprintln!("Handler panicked: {}", panic_message(&*payload));
+        Response::new(StatusCode::InternalServerError).with_body("Internal Server Error")
+    })
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg
+    } else {
+        "unknown panic"
+    }
+}
+
+/// The connection's socket, read with a timeout that runs out at `deadline`
+/// however many reads it takes, so a client can't hold the connection by
+/// trickling bytes.
+struct DeadlineStream<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineStream<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+fn is_timeout(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, Read, Write},
+        net::TcpStream,
+        sync::Arc,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        config::RequestParserConfig,
+        duplex_stream::DuplexStream,
+        headers::Headers,
+        method::Method,
+        request::Request,
+        response::{Response, ResponseLimits, StatusCode},
+        router::Router,
+        server::{LINGER_MAX_BYTES, Server, ServerConfig, serve_connection},
+    };
+
+    fn spawn_server(config: ServerConfig) -> std::net::SocketAddr {
+        spawn_server_with(config, |_| Response::new(StatusCode::Ok).with_body("ok"))
+    }
+
+    fn spawn_server_with<H>(config: ServerConfig, handler: H) -> std::net::SocketAddr
+    where
+        H: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        spawn(Server::bind("127.0.0.1:0", config).unwrap(), handler)
+    }
+
+    fn spawn<H>(server: Server, handler: H) -> std::net::SocketAddr
+    where
+        H: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        let addr = server.local_addr().unwrap();
+
+        thread::spawn(move || server.serve(handler).unwrap());
+
+        addr
+    }
+
+    /// Reads one response whose body is expected to be `body_len` bytes.
+    fn read_response(stream: &mut TcpStream, body_len: usize) -> String {
+        let mut out = Vec::new();
+        let mut tmp = [0u8; 256];
+
+        loop {
+            let head_end = out.windows(4).position(|w| w == b"\r\n\r\n");
+            if head_end.is_some_and(|n| out.len() >= n + 4 + body_len) {
+                break;
+            }
+
+            let n = stream.read(&mut tmp).unwrap();
+            assert!(n > 0, "connection closed mid-response");
+            out.extend_from_slice(&tmp[..n]);
+        }
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_idle_keep_alive_connection_is_closed() {
+        let addr = spawn_server(ServerConfig {
+            keep_alive_timeout: Duration::from_millis(100),
+            ..ServerConfig::default()
+        });
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        assert!(read_response(&mut stream, 2).starts_with("HTTP/1.1 200 OK"));
+
+        let idle_since = Instant::now();
+        let mut tmp = [0u8; 16];
+        let n = stream.read(&mut tmp).unwrap();
+
+        assert_eq!(n, 0);
+        assert!(idle_since.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_trickled_request_is_closed_at_read_timeout() {
+        let addr = spawn_server(ServerConfig {
+            request_read_timeout: Duration::from_millis(200),
+            ..ServerConfig::default()
+        });
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let started = Instant::now();
+
+        // One byte every 50ms keeps each read short, but not the request.
+        let mut writer = stream.try_clone().unwrap();
+        thread::spawn(move || {
+            for byte in b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n" {
+                thread::sleep(Duration::from_millis(50));
+                if writer.write_all(&[*byte]).is_err() {
+                    return;
+                }
+            }
+        });
+
+        let mut out = Vec::new();
+        let _ = stream.read_to_end(&mut out);
+        let lived = started.elapsed();
+        assert!(out.is_empty(), "{:?}", String::from_utf8_lossy(&out));
+        assert!(
+            lived >= Duration::from_millis(200),
+            "closed after {lived:?}"
+        );
+        assert!(lived < Duration::from_secs(1), "closed after {lived:?}");
+    }
+
+    #[test]
+    fn test_zero_timeouts_are_rejected() {
+        for config in [
+            ServerConfig {
+                keep_alive_timeout: Duration::ZERO,
+                ..ServerConfig::default()
+            },
+            ServerConfig {
+                request_read_timeout: Duration::ZERO,
+                ..ServerConfig::default()
+            },
+        ] {
+            let err = Server::bind("127.0.0.1:0", config).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn test_keep_alive_serves_requests_within_idle_window() {
+        let addr = spawn_server(ServerConfig {
+            keep_alive_timeout: Duration::from_millis(500),
+            ..ServerConfig::default()
+        });
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        for _ in 0..2 {
+            stream
+                .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                .unwrap();
+            assert!(read_response(&mut stream, 2).starts_with("HTTP/1.1 200 OK"));
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    #[test]
+    fn test_head_response_has_headers_but_no_body() {
+        let addr = spawn_server(ServerConfig::default());
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        stream
+            .write_all(b"HEAD / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let head = read_response(&mut stream, 0);
+        assert!(head.starts_with("HTTP/1.1 200 OK"));
+        assert!(head.contains("content-length: 2\r\n"));
+        assert!(head.ends_with("\r\n\r\n"));
+
+        // The next response on the connection must start right away, proving
+        // no body bytes were written for the HEAD request.
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let get = read_response(&mut stream, 2);
+        assert!(get.starts_with("HTTP/1.1 200 OK"));
+        assert!(get.contains("content-length: 2\r\n"));
+        assert!(get.ends_with("\r\n\r\nok"));
+    }
+
+    #[test]
+    fn test_connection_closes_after_request_limit() {
+        let addr = spawn_server(ServerConfig {
+            max_requests_per_connection: 3,
+            ..ServerConfig::default()
+        });
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        let request = b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        stream.write_all(&request.repeat(5)).unwrap();
+
+        let mut out = Vec::new();
+        stream.read_to_end(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert_eq!(out.matches("HTTP/1.1 200 OK").count(), 3);
+        assert_eq!(out.matches("connection: close").count(), 1);
+        assert!(out.ends_with("\r\n\r\nok"));
+    }
+
+    #[test]
+    fn test_handler_panic_becomes_500_and_connection_survives() {
+        let addr = spawn_server_with(ServerConfig::default(), |req| {
+            if req.request_line.as_ref().unwrap().request_target == "/boom" {
+                panic!("boom");
+            }
+            Response::new(StatusCode::Ok).with_body("ok")
+        });
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        stream
+            .write_all(b"GET /boom HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let res = read_response(&mut stream, "Internal Server Error".len());
+        assert!(res.starts_with("HTTP/1.1 500 Internal Server Error"));
+
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        assert!(read_response(&mut stream, 2).ends_with("\r\n\r\nok"));
+    }
+
+    #[test]
+    fn test_oversized_body_is_drained_for_next_request() {
+        let config = ServerConfig {
+            parser: RequestParserConfig::builder().max_body_size(16).build(),
+            ..ServerConfig::default()
+        };
+        let addr = spawn_server_with(config, |req| {
+            assert_eq!(req.request_line.as_ref().unwrap().method, Method::Get);
+            Response::new(StatusCode::Ok).with_body("ok")
+        });
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stream
+            .write_all(
+                concat!(
+                    "POST / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n",
+                    "10\r\n0123456789abcdef\r\n10\r\n0123456789abcdef\r\n0\r\n\r\n",
+                    "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n",
+                )
+                .as_bytes(),
+            )
+            .unwrap();
+
+        // Both responses may arrive in one read.
+        let mut out = Vec::new();
+        let mut tmp = [0u8; 256];
+        while !out.ends_with(b"\r\n\r\nok") {
+            let n = stream.read(&mut tmp).unwrap();
+            assert!(n > 0, "connection closed early");
+            out.extend_from_slice(&tmp[..n]);
+        }
+
+        let out = String::from_utf8(out).unwrap();
+        let (first, second) = out.split_once("body exceeds the maximum size").unwrap();
+        assert!(first.starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+        assert!(!first.contains("connection: close"));
+        assert!(second.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    #[test]
+    fn test_chunked_body_too_large_to_drain_gets_413_and_close() {
+        let config = ServerConfig {
+            parser: RequestParserConfig::builder().max_body_size(16).build(),
+            ..ServerConfig::default()
+        };
+        let addr = spawn_server_with(config, |_| Response::new(StatusCode::Ok));
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        // Past what the server discards to keep the connection.
+        let mut writer = stream.try_clone().unwrap();
+        thread::spawn(move || {
+            let chunk = [b'x'; 0x10000];
+            let _ = writer.write_all(
+                b"POST / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n",
+            );
+            for _ in 0..(LINGER_MAX_BYTES / 0x10000 + 2) {
+                if writer.write_all(b"10000\r\n").is_err()
+                    || writer.write_all(&chunk).is_err()
+                    || writer.write_all(b"\r\n").is_err()
+                {
+                    return;
+                }
+            }
+        });
+
+        let mut out = Vec::new();
+        stream.read_to_end(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(
+            out.starts_with("HTTP/1.1 413 Payload Too Large\r\n"),
+            "{out}"
+        );
+        assert!(out.contains("connection: close\r\n"));
+    }
+
+    #[test]
+    fn test_undrainable_body_closes_connection() {
+        let addr = spawn_server_with(ServerConfig::default(), |_| {
+            Response::new(StatusCode::Ok).with_body("ok")
+        });
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stream
+            .write_all(
+                concat!(
+                    "POST / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n",
+                    "zz\r\nnot a chunk\r\n0\r\n\r\n",
+                    "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n",
+                )
+                .as_bytes(),
+            )
+            .unwrap();
+
+        let mut out = String::new();
+        stream.read_to_string(&mut out).unwrap();
+        assert!(out.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert!(out.contains("connection: close\r\n"));
+        assert!(out.ends_with("invalid chunked body: invalid chunk size"));
+    }
+
+    #[test]
+    fn test_over_limit_body_gets_413() {
+        let addr = spawn_server(ServerConfig {
+            parser: RequestParserConfig::builder().max_body_size(1024).build(),
+            ..ServerConfig::default()
+        });
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        let body = vec![b'x'; 256 * 1024];
+        let mut writer = stream.try_clone().unwrap();
+        let sender = thread::spawn(move || {
+            let head = format!(
+                "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n",
+                body.len()
+            );
+            writer.write_all(head.as_bytes()).unwrap();
+            // The server may stop reading once it has answered.
+            let _ = writer.write_all(&body);
+            let _ = writer.shutdown(std::net::Shutdown::Write);
+        });
+
+        let mut out = Vec::new();
+        stream.read_to_end(&mut out).unwrap();
+        sender.join().unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+        assert!(out.contains("connection: close\r\n"));
+        assert!(out.ends_with("\r\n\r\nPayload Too Large: body exceeds the maximum size"));
+    }
+
+    #[test]
+    fn test_parse_errors_get_matching_status() {
+        let addr = spawn_server(ServerConfig {
+            parser: RequestParserConfig::builder().max_header_bytes(64).build(),
+            ..ServerConfig::default()
+        });
+
+        for (request, status_line) in [
+            (
+                "GET / HTTP/2.0\r\n\r\n",
+                "HTTP/1.1 505 HTTP Version Not Supported\r\n",
+            ),
+            ("GET / HTTP/x\r\n\r\n", "HTTP/1.1 400 Bad Request\r\n"),
+            (
+                &format!("GET / HTTP/1.1\r\nX: {}\r\n\r\n", "a".repeat(100)),
+                "HTTP/1.1 431 Request Header Fields Too Large\r\n",
+            ),
+        ] {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+
+            let mut out = String::new();
+            stream.read_to_string(&mut out).unwrap();
+            assert!(out.starts_with(status_line), "{out}");
+        }
+    }
+
+    #[test]
+    fn test_default_headers_unless_handler_overrides() {
+        let mut defaults = Headers::new();
+        defaults.set("Server", "rust-http/0.1");
+        defaults.set("X-Content-Type-Options", "nosniff");
+
+        let server = Server::bind("127.0.0.1:0", ServerConfig::default())
+            .unwrap()
+            .default_headers(defaults);
+        let addr = spawn(server, |req| {
+            let res = Response::new(StatusCode::Ok).with_body("ok");
+            match req.request_line.as_ref().unwrap().request_target.as_str() {
+                "/custom" => res.with_header("Server", "custom"),
+                _ => res,
+            }
+        });
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let res = read_response(&mut stream, 2);
+        assert!(res.contains("server: rust-http/0.1\r\n"));
+        assert!(res.contains("x-content-type-options: nosniff\r\n"));
+
+        stream
+            .write_all(b"GET /custom HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let res = read_response(&mut stream, 2);
+        assert!(res.contains("server: custom\r\n"));
+        assert!(!res.contains("rust-http/0.1"));
+        assert!(res.contains("x-content-type-options: nosniff\r\n"));
+    }
+
+    #[test]
+    fn test_streamed_body_to_http10_client_is_close_delimited() {
+        let (mut client, server) = DuplexStream::pair();
+
+        let worker = thread::spawn(move || {
+            let mut router = Router::new();
+            router.add_route(Method::Get, "/stream", |_| {
+                let chunks = ["hello", ", ", "world"].map(|c| c.as_bytes().to_vec());
+                Response::new(StatusCode::Ok).with_body_iter(chunks)
+            });
+
+            serve_connection(server, &router, &ServerConfig::default()).unwrap();
+        });
+
+        client
+            .write_all(b"GET /stream HTTP/1.0\r\nConnection: keep-alive\r\n\r\n")
+            .unwrap();
+
+        let mut out = Vec::new();
+        client.read_to_end(&mut out).unwrap();
+        worker.join().unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(!out.contains("transfer-encoding"));
+        assert!(out.contains("connection: close\r\n"));
+        assert!(out.ends_with("\r\n\r\nhello, world"));
+    }
+
+    #[test]
+    fn test_serve_connection_routes_over_duplex_stream() {
+        let (mut client, server) = DuplexStream::pair();
+
+        let worker = thread::spawn(move || {
+            let mut router = Router::new();
+            router.add_route(Method::Get, "/hello", |_| {
+                Response::new(StatusCode::Ok).with_body("hello")
+            });
+
+            serve_connection(server, &router, &ServerConfig::default()).unwrap();
+        });
+
+        client
+            .write_all(
+                concat!(
+                    "GET /hello HTTP/1.1\r\nHost: localhost\r\n\r\n",
+                    "GET /missing HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+                )
+                .as_bytes(),
+            )
+            .unwrap();
+
+        let mut out = Vec::new();
+        client.read_to_end(&mut out).unwrap();
+        worker.join().unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        let second = out.find("HTTP/1.1 404 Not Found\r\n").expect("routed 404");
+        assert!(out.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(out[..second].ends_with("\r\n\r\nhello"));
+        assert!(out[second..].contains("connection: close\r\n"));
+        assert!(out.ends_with("\r\n\r\nNot Found"));
+    }
+
+    #[test]
+    fn test_serve_connection_hands_over_upgraded_stream() {
+        let (mut client, server) = DuplexStream::pair();
+
+        let worker = thread::spawn(move || {
+            let mut router = Router::new();
+            router.add_route(Method::Get, "/echo", |_| {
+                Response::switching_protocols("echo").on_upgrade(|mut conn| {
+                    assert!(conn.tcp_stream().is_none());
+                    let mut buf = [0u8; 64];
+                    loop {
+                        match conn.read(&mut buf) {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => conn.write_all(&buf[..n]).unwrap(),
+                        }
+                    }
+                })
+            });
+
+            serve_connection(server, &router, &ServerConfig::default()).unwrap();
+        });
+
+        // Bytes after the request belong to the new protocol, not HTTP.
+        client
+            .write_all(b"GET /echo HTTP/1.1\r\nConnection: Upgrade\r\nUpgrade: echo\r\n\r\nGET / ")
+            .unwrap();
+        client.write_all(b"HTTP/1.1\r\n\r\n").unwrap();
+
+        let mut out = Vec::new();
+        let mut buf = [0u8; 256];
+        while !out.ends_with(b"GET / HTTP/1.1\r\n\r\n") {
+            let n = client.read(&mut buf).unwrap();
+            assert_ne!(n, 0, "connection closed early: {out:?}");
+            out.extend_from_slice(&buf[..n]);
+        }
+        drop(client);
+        worker.join().unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+        assert!(out.ends_with("\r\n\r\nGET / HTTP/1.1\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_early_hints_precede_final_response_on_http_1_1_only() {
+        let (mut client, server) = DuplexStream::pair();
+
+        let worker = thread::spawn(move || {
+            let mut router = Router::new();
+            router.add_route(Method::Get, "/", |_| {
+                Response::new(StatusCode::Ok)
+                    .with_body("ok")
+                    .with_early_hints(Response::early_hints(&[
+                        "</style.css>; rel=preload; as=style",
+                        "</app.js>; rel=preload; as=script",
+                    ]))
+            });
+
+            serve_connection(server, &router, &ServerConfig::default()).unwrap();
+        });
+
+        client
+            .write_all(
+                concat!(
+                    "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n",
+                    "GET / HTTP/1.0\r\n\r\n",
+                )
+                .as_bytes(),
+            )
+            .unwrap();
+
+        let mut out = Vec::new();
+        client.read_to_end(&mut out).unwrap();
+        worker.join().unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with(concat!(
+            "HTTP/1.1 103 Early Hints\r\n",
+            "link: </style.css>; rel=preload; as=style\r\n",
+            "link: </app.js>; rel=preload; as=script\r\n",
+            "\r\n",
+            "HTTP/1.1 200 OK\r\n",
+        )));
+        assert_eq!(out.matches("103 Early Hints").count(), 1);
+        assert_eq!(out.matches("HTTP/1.1 200 OK").count(), 2);
+    }
+
+    #[test]
+    fn test_connection_closes_at_lifetime_cap() {
+        let addr = spawn_server(ServerConfig {
+            keep_alive_timeout: Duration::from_secs(5),
+            max_connection_lifetime: Some(Duration::from_millis(300)),
+            ..ServerConfig::default()
+        });
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let opened = Instant::now();
+
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        assert!(read_response(&mut stream, 2).starts_with("HTTP/1.1 200 OK"));
+
+        // Idle well inside the keep-alive window, yet the lifetime runs out.
+        let mut tmp = [0u8; 16];
+        assert_eq!(stream.read(&mut tmp).unwrap(), 0);
+        let lived = opened.elapsed();
+        assert!(
+            lived >= Duration::from_millis(250),
+            "closed after {lived:?}"
+        );
+        assert!(lived < Duration::from_secs(4), "closed after {lived:?}");
+
+        // Stalling halfway through a request line doesn't outlive it either.
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let opened = Instant::now();
+        stream.write_all(b"GET / HT").unwrap();
+
+        let mut out = Vec::new();
+        stream.read_to_end(&mut out).unwrap();
+        let lived = opened.elapsed();
+        assert!(out.is_empty());
+        assert!(
+            lived >= Duration::from_millis(250),
+            "closed after {lived:?}"
+        );
+        assert!(lived < Duration::from_secs(4), "closed after {lived:?}");
+    }
+
+    /// Starts a server whose handler sleeps for `handler_delay`, sends it one
+    /// request and shuts the server down with `grace` once the handler runs.
+    /// Returns what the client received and how many connections were
+    /// force-closed.
+    fn shut_down_during_request(handler_delay: Duration, grace: Duration) -> (String, usize) {
+        let server = Arc::new(Server::bind("127.0.0.1:0", ServerConfig::default()).unwrap());
+        let addr = server.local_addr().unwrap();
+        let serving = {
+            let server = Arc::clone(&server);
+            thread::spawn(move || {
+                server.serve(move |_| {
+                    thread::sleep(handler_delay);
+                    Response::new(StatusCode::Ok).with_body("done")
+                })
+            })
+        };
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        thread::sleep(Duration::from_millis(50));
+
+        let forced = server.shutdown_with_timeout(grace).unwrap();
+        serving.join().unwrap().unwrap();
+
+        let mut out = Vec::new();
+        let _ = stream.read_to_end(&mut out);
+        (String::from_utf8(out).unwrap(), forced)
+    }
+
+    #[test]
+    fn test_shutdown_lets_slow_request_finish() {
+        let (out, forced) =
+            shut_down_during_request(Duration::from_millis(200), Duration::from_secs(3));
+
+        assert_eq!(forced, 0);
+        assert!(out.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(out.contains("connection: close\r\n"));
+        assert!(out.ends_with("done"));
+    }
+
+    #[test]
+    fn test_shutdown_force_closes_after_grace_period() {
+        let started = Instant::now();
+        let (out, forced) =
+            shut_down_during_request(Duration::from_secs(2), Duration::from_millis(100));
+
+        assert_eq!(forced, 1);
+        assert!(out.is_empty());
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_shutdown_closes_idle_connections() {
+        let server = Arc::new(Server::bind("127.0.0.1:0", ServerConfig::default()).unwrap());
+        let addr = server.local_addr().unwrap();
+        let serving = {
+            let server = Arc::clone(&server);
+            thread::spawn(move || server.serve(|_| Response::new(StatusCode::Ok).with_body("ok")))
+        };
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        read_response(&mut stream, 2);
+
+        assert_eq!(
+            server
+                .shutdown_with_timeout(Duration::from_secs(3))
+                .unwrap(),
+            0
+        );
+        serving.join().unwrap().unwrap();
+        assert_eq!(stream.read(&mut [0u8; 16]).unwrap(), 0);
+    }
+
+    #[cfg(feature = "socket-options")]
+    #[test]
+    fn test_small_backlog_still_accepts() {
+        let server = Server::bind_with_backlog("127.0.0.1:0", ServerConfig::default(), 1).unwrap();
+        let addr = spawn(server, |_| Response::new(StatusCode::Ok).with_body("ok"));
+
+        for _ in 0..3 {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            stream
+                .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            assert!(read_response(&mut stream, 2).starts_with("HTTP/1.1 200 OK"));
+        }
+    }
+
+    #[test]
+    fn test_upgrade_hands_over_connection() {
+        let addr = spawn_server_with(ServerConfig::default(), |req| {
+            assert_eq!(req.upgrade_protocols(), ["echo"]);
+            Response::switching_protocols("echo").on_upgrade(|mut conn| {
+                let mut buf = [0u8; 64];
+                loop {
+                    match conn.read(&mut buf) {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => conn.write_all(&buf[..n]).unwrap(),
+                    }
+                }
+            })
+        });
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        // The first bytes of the new protocol arrive with the request.
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nConnection: Upgrade\r\nUpgrade: echo\r\n\r\nearly")
+            .unwrap();
+        let head = read_response(&mut stream, 5);
+        assert!(head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+        assert!(head.contains("connection: Upgrade\r\n"));
+        assert!(head.ends_with("\r\n\r\nearly"));
+
+        stream.write_all(b"more").unwrap();
+        let mut buf = [0u8; 4];
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"more");
+    }
+
+    #[cfg(all(feature = "socket-options", target_os = "linux"))]
+    #[test]
+    fn test_reuse_port_lets_two_servers_share_a_port() {
+        use crate::server::SocketOptions;
+
+        let options = SocketOptions {
+            reuse_port: true,
+            ..SocketOptions::default()
+        };
+        let first =
+            Server::bind_with_options("127.0.0.1:0", ServerConfig::default(), options.clone())
+                .unwrap();
+        let addr = first.local_addr().unwrap();
+        let second = Server::bind_with_options(addr, ServerConfig::default(), options).unwrap();
+        assert_eq!(second.local_addr().unwrap(), addr);
+
+        assert!(Server::bind(addr, ServerConfig::default()).is_err());
+
+        spawn(first, |_| Response::new(StatusCode::Ok).with_body("ok"));
+        spawn(second, |_| Response::new(StatusCode::Ok).with_body("ok"));
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        assert!(read_response(&mut stream, 2).starts_with("HTTP/1.1 200 OK"));
+    }
+
+    #[test]
+    fn test_oversized_response_becomes_500() {
+        let addr = spawn_server_with(
+            ServerConfig {
+                response_limits: ResponseLimits {
+                    max_body_size: Some(1024),
+                    ..ResponseLimits::default()
+                },
+                ..ServerConfig::default()
+            },
+            |_| Response::new(StatusCode::Ok).with_body(vec![b'x'; 4096]),
+        );
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+
+        let res = read_response(&mut stream, 21);
+        assert!(res.starts_with("HTTP/1.1 500 Internal Server Error\r\n"));
+        assert!(res.ends_with("Internal Server Error"));
+    }
+}
@@ -0,0 +1,131 @@
+use std::sync::mpsc::Receiver;
+
+use crate::response::{Response, StatusCode};
+
+/// One server-sent event, as written to a `text/event-stream` body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseEvent {
+    pub event: Option<String>,
+    pub id: Option<String>,
+    pub data: String,
+}
+
+impl SseEvent {
+    pub fn new(data: impl Into<String>) -> Self {
+        Self {
+            event: None,
+            id: None,
+            data: data.into(),
+        }
+    }
+
+    pub fn with_event(mut self, event: impl Into<String>) -> Self {
+        self.event = Some(event.into());
+        self
+    }
+
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// The event in wire format. Multi-line data becomes one `data:` line per
+    /// line, with `\r\n`, `\r` and `\n` all ending a line as they do for the
+    /// client; line breaks in `event` and `id` would end the field early, so
+    /// they are dropped.
+    pub fn format(&self) -> String {
+        let mut out = String::new();
+
+        if let Some(event) = &self.event {
+            out.push_str(&format!("event: {}\n", single_line(event)));
+        }
+        if let Some(id) = &self.id {
+            out.push_str(&format!("id: {}\n", single_line(id)));
+        }
+        for line in self.data.replace("\r\n", "\n").split(['\r', '\n']) {
+            out.push_str(&format!("data: {line}\n"));
+        }
+
+        out.push('\n');
+        out
+    }
+}
+
+fn single_line(s: &str) -> String {
+    s.chars().filter(|c| !matches!(c, '\r' | '\n')).collect()
+}
+
+impl Response {
+    /// A `text/event-stream` response sending each event received on
+    /// `events` as it arrives, until every sender is dropped. The body is
+    /// streamed with chunked encoding, and proxies are asked not to buffer
+    /// or cache it.
+    pub fn sse(events: Receiver<SseEvent>) -> Self {
+        let chunks = events.into_iter().map(|event| event.format().into_bytes());
+
+        Response::new(StatusCode::Ok)
+            .with_header("Content-Type", "text/event-stream")
+            .with_header("Cache-Control", "no-cache")
+            .with_header("X-Accel-Buffering", "no")
+            .with_body_iter(chunks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use crate::{response::Response, sse::SseEvent};
+
+    #[test]
+    fn test_format_event_with_all_fields() {
+        let event = SseEvent::new("first line\nsecond line")
+            .with_event("update")
+            .with_id("42");
+
+        assert_eq!(
+            event.format(),
+            concat!(
+                "event: update\n",
+                "id: 42\n",
+                "data: first line\n",
+                "data: second line\n",
+                "\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_format_splits_data_on_every_line_break() {
+        let event = SseEvent::new("a\r\nb\rid: 7\nc\r");
+        assert_eq!(
+            event.format(),
+            "data: a\ndata: b\ndata: id: 7\ndata: c\ndata: \n\n"
+        );
+    }
+
+    #[test]
+    fn test_format_data_only_event() {
+        assert_eq!(SseEvent::new("ping").format(), "data: ping\n\n");
+    }
+
+    #[test]
+    fn test_sse_response_streams_events() {
+        let (tx, rx) = mpsc::channel();
+        tx.send(SseEvent::new("a")).unwrap();
+        tx.send(SseEvent::new("b").with_id("2")).unwrap();
+        drop(tx);
+
+        let res = Response::sse(rx);
+        assert!(res.headers.get("Content-Length").is_none());
+        assert_eq!(
+            res.headers.get("Content-Type"),
+            Some(&"text/event-stream".to_string())
+        );
+
+        let mut out = Vec::new();
+        res.write_to(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.ends_with("\r\n\r\n9\r\ndata: a\n\n\r\nf\r\nid: 2\ndata: b\n\n\r\n0\r\n\r\n"));
+    }
+}
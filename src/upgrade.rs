@@ -0,0 +1,174 @@
+use std::{
+    any::Any,
+    fmt,
+    io::{self, Read, Write},
+    net::TcpStream,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    request::Request,
+    response::{Response, StatusCode},
+};
+
+impl Request {
+    /// The protocols offered in `Upgrade`, in the client's order of
+    /// preference, e.g. `["h2c", "websocket"]`. Empty unless `Connection`
+    /// lists `upgrade` too, since an `Upgrade` header a proxy forwarded
+    /// without it wasn't meant for this server (RFC 9110 section 7.8).
+    pub fn upgrade_protocols(&self) -> Vec<String> {
+        let connection_upgrade = self.headers.get("connection").is_some_and(|v| {
+            v.split(',')
+                .any(|t| t.trim().eq_ignore_ascii_case("upgrade"))
+        });
+        if !connection_upgrade {
+            return Vec::new();
+        }
+
+        self.headers
+            .get("upgrade")
+            .map(|v| {
+                v.split(',')
+                    .map(str::trim)
+                    .filter(|p| !p.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl Response {
+    /// A `101 Switching Protocols` response switching to `upgrade`. Pair it
+    /// with [`Response::on_upgrade`] to take over the connection once it has
+    /// been sent.
+    pub fn switching_protocols(upgrade: &str) -> Self {
+        let mut res = Response::new(StatusCode::SwitchingProtocols);
+        res.headers.remove("Content-Length");
+        res.headers.set("Upgrade", upgrade);
+        res.headers.set("Connection", "Upgrade");
+        res
+    }
+}
+
+/// A stream a connection can be served over and handed on from.
+pub(crate) trait Transport: Read + Write + Send + Any {}
+
+impl<T: Read + Write + Send + Any> Transport for T {}
+
+/// The connection after a `101` response, handed to the callback given to
+/// [`Response::on_upgrade`]. Reads first return any bytes the client sent
+/// after its request that the server had already buffered.
+pub struct Upgraded {
+    stream: Box<dyn Transport>,
+    buffered: Vec<u8>,
+}
+
+impl Upgraded {
+    pub(crate) fn new(stream: impl Transport, buffered: Vec<u8>) -> Self {
+        Self {
+            stream: Box::new(stream),
+            buffered,
+        }
+    }
+
+    /// The socket, when the connection is a TCP one, e.g. to set timeouts.
+    /// Reading from it directly skips any bytes still buffered here.
+    pub fn tcp_stream(&self) -> Option<&TcpStream> {
+        (&*self.stream as &dyn Any).downcast_ref()
+    }
+}
+
+impl Read for Upgraded {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.buffered.is_empty() {
+            return self.stream.read(buf);
+        }
+
+        let n = self.buffered.len().min(buf.len());
+        buf[..n].copy_from_slice(&self.buffered[..n]);
+        self.buffered.drain(..n);
+        Ok(n)
+    }
+}
+
+impl Write for Upgraded {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+type UpgradeFn = Box<dyn FnOnce(Upgraded) + Send>;
+
+/// The callback set by [`Response::on_upgrade`]. Shared so the response
+/// stays `Clone`; only the first copy to be taken runs it.
+#[derive(Clone)]
+pub(crate) struct OnUpgrade(Arc<Mutex<Option<UpgradeFn>>>);
+
+impl OnUpgrade {
+    pub(crate) fn new<F>(f: F) -> Self
+    where
+        F: FnOnce(Upgraded) + Send + 'static,
+    {
+        Self(Arc::new(Mutex::new(Some(Box::new(f)))))
+    }
+
+    pub(crate) fn call(self, upgraded: Upgraded) {
+        let f = self.0.lock().unwrap_or_else(|err| err.into_inner()).take();
+        if let Some(f) = f {
+            f(upgraded);
+        }
+    }
+}
+
+impl fmt::Debug for OnUpgrade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OnUpgrade")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        request::parse_request,
+        response::{Response, StatusCode},
+    };
+
+    #[test]
+    fn test_upgrade_protocols() {
+        let req = parse_request(
+            b"GET / HTTP/1.1\r\nConnection: keep-alive, Upgrade\r\nUpgrade: h2c, websocket\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(req.upgrade_protocols(), ["h2c", "websocket"]);
+
+        let req = parse_request(b"GET / HTTP/1.1\r\nUpgrade: h2c\r\n\r\n").unwrap();
+        assert!(req.upgrade_protocols().is_empty());
+    }
+
+    #[test]
+    fn test_switching_protocols_response() {
+        let mut out = Vec::new();
+        Response::switching_protocols("h2c")
+            .write_to_sorted(&mut out)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                "HTTP/1.1 101 Switching Protocols\r\n",
+                "connection: Upgrade\r\n",
+                "upgrade: h2c\r\n",
+                "\r\n",
+            )
+        );
+        assert_eq!(
+            Response::switching_protocols("h2c").status,
+            StatusCode::SwitchingProtocols
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use crate::{method::Method, request::Request, response::Response};
+
+/// Appended to the client's key before hashing, per RFC 6455 section 4.2.2.
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+impl Request {
+    /// Whether this is a WebSocket opening handshake: a `GET` with
+    /// `Upgrade: websocket`, `Connection: Upgrade`, a `Sec-WebSocket-Key` and
+    /// `Sec-WebSocket-Version: 13`.
+    pub fn is_websocket_upgrade(&self) -> bool {
+        let is_get = self
+            .request_line
+            .as_ref()
+            .is_some_and(|line| line.method == Method::Get);
+
+        is_get
+            && has_token(self.headers.get("upgrade"), "websocket")
+            && has_token(self.headers.get("connection"), "upgrade")
+            && self.headers.get("sec-websocket-key").is_some()
+            && self
+                .headers
+                .get("sec-websocket-version")
+                .is_some_and(|v| v.trim() == "13")
+    }
+}
+
+impl Response {
+    /// The `101 Switching Protocols` answer to a WebSocket handshake whose
+    /// `Sec-WebSocket-Key` was `key`.
+    pub fn websocket_accept(key: &str) -> Self {
+        Response::switching_protocols("websocket")
+            .with_header("Sec-WebSocket-Accept", &websocket_accept_key(key))
+    }
+}
+
+fn websocket_accept_key(key: &str) -> String {
+    let digest = sha1(format!("{}{WEBSOCKET_GUID}", key.trim()).as_bytes());
+    base64_encode(&digest)
+}
+
+/// Whether the comma-separated `value` contains `token`, ignoring case.
+fn has_token(value: Option<&String>, token: &str) -> bool {
+    value.is_some_and(|v| v.split(',').any(|t| t.trim().eq_ignore_ascii_case(token)))
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    let mut msg = data.to_vec();
+    msg.push(0x80);
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+    msg.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in msg.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    let mut out = [0u8; 20];
+    for (chunk, word) in out.chunks_mut(4).zip(h) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    out
+}
+
+fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, &b)| acc | (b as u32) << (16 - 8 * i));
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chunk_reader::ChunkReader,
+        request::request_from_reader,
+        response::{Response, StatusCode},
+        websocket::{base64_encode, sha1},
+    };
+
+    #[test]
+    fn test_detects_websocket_upgrade() {
+        let req = request_from_reader(ChunkReader::new(
+            concat!(
+                "GET /chat HTTP/1.1\r\n",
+                "Host: server.example.com\r\n",
+                "Upgrade: websocket\r\n",
+                "Connection: keep-alive, Upgrade\r\n",
+                "Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n",
+                "Sec-WebSocket-Version: 13\r\n",
+                "\r\n",
+            ),
+            16,
+        ))
+        .unwrap();
+
+        assert!(req.is_websocket_upgrade());
+    }
+
+    #[test]
+    fn test_plain_request_is_not_websocket_upgrade() {
+        let req = request_from_reader(ChunkReader::new(
+            concat!(
+                "GET /chat HTTP/1.1\r\n",
+                "Host: server.example.com\r\n",
+                "Upgrade: websocket\r\n",
+                "Connection: Upgrade\r\n",
+                "Sec-WebSocket-Version: 13\r\n",
+                "\r\n",
+            ),
+            16,
+        ))
+        .unwrap();
+
+        assert!(!req.is_websocket_upgrade());
+    }
+
+    #[test]
+    fn test_rfc_6455_accept_key() {
+        let res = Response::websocket_accept("dGhlIHNhbXBsZSBub25jZQ==");
+
+        assert_eq!(res.status, StatusCode::SwitchingProtocols);
+        assert_eq!(
+            res.headers.get("Sec-WebSocket-Accept"),
+            Some(&"s3pPLMBiTxaQ9kYGzzhZRbK+xOo=".to_string())
+        );
+        assert_eq!(res.headers.get("Upgrade"), Some(&"websocket".to_string()));
+        assert!(res.headers.get("Content-Length").is_none());
+    }
+
+    #[test]
+    fn test_sha1_and_base64_vectors() {
+        assert_eq!(base64_encode(&sha1(b"abc")), "qZk+NkcGgWq6PiVxeFDCbJzQ2J0=");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+    }
+}